@@ -0,0 +1,132 @@
+use clap::ValueEnum;
+use std::{env, fmt};
+
+/// Target architecture of the kernel build.
+///
+/// Determines the boot image produced by Kbuild, whether device tree blobs are installed,
+/// and the `ARCH=`/`CROSS_COMPILE=` variables needed when it differs from the host.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum Arch {
+    #[value(name = "x86_64")]
+    X86_64,
+    Arm64,
+    Riscv64,
+}
+
+impl Arch {
+    /// The architecture this program runs on, if supported.
+    pub fn host() -> Option<Self> {
+        Self::from_machine(env::consts::ARCH)
+    }
+
+    /// Maps a machine name (`uname -m`, `std::env::consts::ARCH`) to an architecture.
+    pub fn from_machine(machine: &str) -> Option<Self> {
+        match machine {
+            "x86_64" => Some(Self::X86_64),
+            "aarch64" | "arm64" => Some(Self::Arm64),
+            "riscv64" => Some(Self::Riscv64),
+            _ => None,
+        }
+    }
+
+    /// Value of Kbuild's `ARCH=` variable (the directory under `arch/`).
+    pub fn kernel_arch(&self) -> &'static str {
+        match self {
+            Self::X86_64 => "x86",
+            Self::Arm64 => "arm64",
+            Self::Riscv64 => "riscv",
+        }
+    }
+
+    /// Machine name as reported by `uname -m` (also the pacman/RPM architecture).
+    pub fn machine(&self) -> &'static str {
+        match self {
+            Self::X86_64 => "x86_64",
+            Self::Arm64 => "aarch64",
+            Self::Riscv64 => "riscv64",
+        }
+    }
+
+    /// Conventional GNU toolchain prefix used when cross-compiling for this architecture.
+    pub fn default_cross_compile(&self) -> &'static str {
+        match self {
+            Self::X86_64 => "x86_64-linux-gnu-",
+            Self::Arm64 => "aarch64-linux-gnu-",
+            Self::Riscv64 => "riscv64-linux-gnu-",
+        }
+    }
+
+    /// Boot images produced by the build, relative to the build tree, in order of preference.
+    pub fn image_candidates(&self) -> Vec<String> {
+        let images: &[&str] = match self {
+            Self::X86_64 => &["bzImage"],
+            // The EFI stub needs the uncompressed Image; Image.gz is for U-Boot style loaders
+            Self::Arm64 | Self::Riscv64 => &["Image", "Image.gz"],
+        };
+        images
+            .iter()
+            .map(|image| format!("arch/{}/boot/{image}", self.kernel_arch()))
+            .collect()
+    }
+
+    /// Whether the platform boots with device tree blobs, installed with `make dtbs_install`.
+    pub fn has_device_trees(&self) -> bool {
+        matches!(self, Self::Arm64 | Self::Riscv64)
+    }
+}
+
+impl fmt::Display for Arch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::X86_64 => "x86_64",
+            Self::Arm64 => "arm64",
+            Self::Riscv64 => "riscv64",
+        };
+        write!(f, "{name}")
+    }
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_arch
+#[cfg(test)]
+mod tests_arch {
+    use super::*;
+
+    #[test]
+    fn test_from_machine() {
+        assert_eq!(Arch::from_machine("x86_64"), Some(Arch::X86_64));
+        assert_eq!(Arch::from_machine("aarch64"), Some(Arch::Arm64));
+        assert_eq!(Arch::from_machine("riscv64"), Some(Arch::Riscv64));
+        assert_eq!(Arch::from_machine("mips"), None);
+        assert_eq!(Arch::Arm64.machine(), "aarch64");
+    }
+
+    #[test]
+    fn test_image_candidates() {
+        assert_eq!(
+            Arch::X86_64.image_candidates(),
+            vec!["arch/x86/boot/bzImage"]
+        );
+        assert_eq!(
+            Arch::Arm64.image_candidates(),
+            vec!["arch/arm64/boot/Image", "arch/arm64/boot/Image.gz"]
+        );
+        assert!(!Arch::X86_64.has_device_trees());
+        assert!(Arch::Riscv64.has_device_trees());
+    }
+
+    #[test]
+    fn test_value_names() {
+        assert_eq!(Arch::from_str("x86_64", false).unwrap(), Arch::X86_64);
+        assert_eq!(Arch::from_str("arm64", false).unwrap(), Arch::Arm64);
+        assert_eq!(Arch::Riscv64.to_string(), "riscv64");
+    }
+}
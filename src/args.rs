@@ -1,8 +1,9 @@
-use crate::Version;
+use crate::{Arch, DkmsModuleSpec, OptionRule, Version};
 use clap::{
-    Parser, Subcommand, ValueEnum,
+    Args, Parser, Subcommand, ValueEnum,
     builder::styling::{AnsiColor, Color, Style},
 };
+use std::{ffi::OsString, path::PathBuf};
 
 // --- Structs ---
 
@@ -74,10 +75,10 @@ pub struct Arguments {
     #[arg(
         short,
         long,
-        required = true, // Always required
+        required = false, // Required by every command but `history` - validated in Config::new
         help = "The new kernel version (e.g., \"6.15.4\")"
     )] // Added help
-    pub new: Version, // Parsed directly into a Version
+    pub new: Option<Version>, // Parsed into an Option<Version>
 
     /// The old kernel version ( Major.Minor.Patch, e.g., "6.15.3").
     #[arg(
@@ -90,6 +91,419 @@ pub struct Arguments {
         If provided with these commands, it must be strictly less than the --new version (validated later)."
     )] // Updated long_help to indicate where validation occurs
     pub old: Option<Version>, // Parsed into an Option<Version>
+
+    /// Options controlling how the kernel is configured and built.
+    #[command(flatten)]
+    pub build: BuildArgs,
+
+    /// Options controlling how DKMS modules are built and signed.
+    #[command(flatten)]
+    pub dkms: DkmsArgs,
+
+    /// Options controlling logs and reports.
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+impl Arguments {
+    /// Parses the process arguments, accepting `--edit-config <EDITOR>` as well as
+    /// `--edit-config=<EDITOR>` (see [`join_edit_config_value`]).
+    pub fn parse_args() -> Self {
+        Self::parse_from(join_edit_config_value(std::env::args_os()))
+    }
+}
+
+/// Rewrites `--edit-config <EDITOR>` as `--edit-config=<EDITOR>` when `<EDITOR>` names a
+/// [`ConfigEditor`]. The value of `--edit-config` is optional, so clap only takes it after
+/// `=`; a bare `--edit-config` followed by a subcommand keeps working, since no subcommand
+/// is named after an editor.
+fn join_edit_config_value(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut joined: Vec<OsString> = Vec::new();
+    for arg in args {
+        let is_editor = arg
+            .to_str()
+            .is_some_and(|value| ConfigEditor::from_str(value, false).is_ok());
+        match joined.last_mut() {
+            Some(previous) if is_editor && previous == "--edit-config" => {
+                previous.push("=");
+                previous.push(&arg);
+            }
+            _ => joined.push(arg),
+        }
+    }
+    joined
+}
+
+/// Logging and reporting options, flattened into [`Arguments`].
+#[derive(Args, Debug, Clone, PartialEq)]
+pub struct OutputArgs {
+    /// Directory where logs of each run (e.g. failed DKMS build logs) are archived.
+    #[arg(
+        long,
+        value_name = "DIR",
+        default_value = "/var/log/kernel-updater",
+        help = "Directory where run logs are archived"
+    )]
+    pub log_dir: PathBuf,
+
+    /// Show debug details (command durations and exit statuses) on the console.
+    #[arg(
+        short,
+        long,
+        global = true,
+        conflicts_with = "quiet",
+        help = "Show debug details on the console (command durations, exit statuses)"
+    )]
+    pub verbose: bool,
+
+    /// Only show warnings and errors on the console; everything still goes to the run log.
+    #[arg(
+        short,
+        long,
+        global = true,
+        help = "Only show warnings and errors on the console (the run log keeps everything)"
+    )]
+    pub quiet: bool,
+
+    /// Console output format: human-readable text, or JSON Lines events on stdout.
+    #[arg(
+        long = "output",
+        value_name = "FORMAT",
+        value_enum,
+        global = true,
+        default_value_t = OutputFormat::Text,
+        help = "Console output format: text, or json (JSON Lines events on stdout, text moves to stderr)"
+    )]
+    pub output_format: OutputFormat,
+}
+
+impl Default for OutputArgs {
+    fn default() -> Self {
+        Self {
+            log_dir: PathBuf::from("/var/log/kernel-updater"),
+            verbose: false,
+            quiet: false,
+            output_format: OutputFormat::Text,
+        }
+    }
+}
+
+/// Console output format selected by `--output`.
+#[derive(Debug, Default, Clone, Copy, ValueEnum, PartialEq)]
+pub enum OutputFormat {
+    /// Human-readable progress messages.
+    #[default]
+    Text,
+    /// One JSON event per line on stdout (see the README for the schema).
+    Json,
+}
+
+/// Kernel configuration and build options, flattened into [`Arguments`].
+#[derive(Args, Debug, Default, Clone, PartialEq)]
+pub struct BuildArgs {
+    /// Config fragments merged, in order, on top of the base config before `olddefconfig`.
+    #[arg(
+        long = "config-fragment",
+        value_name = "FILE",
+        help = "Config fragment merged on top of the base config (repeatable, applied in order)",
+        long_help = "Config fragment (e.g. debug.config) merged on top of the base config before \
+        `make olddefconfig`, like scripts/kconfig/merge_config.sh. Repeatable; later fragments win.\n\
+        A warning is printed when Kconfig dependencies override a requested value."
+    )]
+    pub config_fragments: Vec<PathBuf>,
+
+    /// Named set of fragments stored under `<config base>/profiles/<NAME>/*.config`.
+    #[arg(
+        long = "config-profile",
+        value_name = "NAME",
+        help = "Apply every fragment in <config base>/profiles/<NAME>/ (before --config-fragment)"
+    )]
+    pub config_profile: Option<String>,
+
+    /// Additional options the final `.config` must satisfy, checked before compiling.
+    #[arg(
+        long = "require-option",
+        value_name = "RULE",
+        value_delimiter = ',',
+        help = "Kernel option the .config must satisfy: CONFIG_X, !CONFIG_X or CONFIG_X=value",
+        long_help = "Kernel option the final .config must satisfy after `make olddefconfig` \
+        (repeatable or comma-separated):\n\
+        CONFIG_X        enabled (y or m)\n\
+        !CONFIG_X       not set\n\
+        CONFIG_X=value  exactly this value (e.g. CONFIG_EFI_STUB=y)\n\
+        These are checked together with built-in rules (CONFIG_MODULES, CONFIG_BLK_DEV_INITRD, ...)."
+    )]
+    pub required_options: Vec<OptionRule>,
+
+    /// Where to take the base config from when the config file does not exist yet.
+    #[arg(
+        long = "seed-config",
+        value_enum,
+        value_name = "SOURCE",
+        help = "Seed a missing base config from the running or previous kernel",
+        long_help = "When the base config (<config base>/config-<suffix>) does not exist, create it from:\n\
+        running   /proc/config.gz, then /boot/config-$(uname -r)\n\
+        previous  /boot/config-<old ident>, then the .config of the --old source tree\n\
+        The seeded config is saved back to the base config path for future runs."
+    )]
+    pub seed_config: Option<ConfigSeed>,
+
+    /// `lsmod` snapshot used to trim the seeded config with `make localmodconfig`.
+    #[arg(
+        long = "localmodconfig",
+        value_name = "LSMOD_FILE",
+        requires = "seed_config",
+        help = "Trim the seeded config with `make localmodconfig` using an lsmod snapshot",
+        long_help = "Trim the seeded config with `make LSMOD=<LSMOD_FILE> localmodconfig`, keeping only \
+        the modules listed in the snapshot (create it with `lsmod > FILE`, ideally with every device in use).\n\
+        Requires --seed-config."
+    )]
+    pub localmodconfig: Option<PathBuf>,
+
+    /// Replace the base config with the final `.config` after installing (a timestamped backup is kept).
+    #[arg(
+        long = "save-config",
+        help = "Save the final .config back to the base config after installing (keeps a backup)",
+        long_help = "After installing, replace the base config (<config base>/config-<suffix>) with the \
+        final .config produced by `make olddefconfig`, so the next upgrade starts from the latest answers.\n\
+        The previous base config is kept as config-<suffix>.<YYYYmmdd-HHMMSS>.bak."
+    )]
+    pub save_config: bool,
+
+    /// Interactive configuration tool run between `olddefconfig` and `make`.
+    #[arg(
+        long = "edit-config",
+        value_enum,
+        value_name = "EDITOR",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "menuconfig",
+        help = "Edit the config interactively before building (default: menuconfig)",
+        long_help = "Run `make menuconfig`, `make nconfig` or `make xconfig` between `make olddefconfig` \
+        and `make`, then show the diff from the base config and ask whether to save it back.\n\
+        Requires an interactive terminal (and a display for xconfig)."
+    )]
+    pub edit_config: Option<ConfigEditor>,
+
+    /// Target architecture; defaults to the host.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ARCH",
+        help = "Target architecture (default: the host's)",
+        long_help = "Target architecture (default: the host's). Selects the boot image \
+        (bzImage, or Image/Image.gz on arm64 and riscv64), installs device trees with `make dtbs_install` \
+        on arm64/riscv64, and passes ARCH=/CROSS_COMPILE= to make when it differs from the host."
+    )]
+    pub arch: Option<Arch>,
+
+    /// Toolchain prefix for cross builds (`CROSS_COMPILE=`), overriding the default for `--arch`.
+    #[arg(
+        long = "cross-compile",
+        value_name = "PREFIX",
+        help = "Cross toolchain prefix, e.g. aarch64-linux-gnu- (default for a foreign --arch)"
+    )]
+    pub cross_compile: Option<String>,
+
+    /// Root directory every path (sources, modules, /boot, DKMS) is derived from.
+    #[arg(
+        long = "target-root",
+        value_name = "DIR",
+        help = "Stage everything under DIR (e.g. a mounted rootfs) instead of /",
+        long_help = "Derive every path from DIR instead of /: sources and configs under DIR/lib/modules, \
+        modules via INSTALL_MOD_PATH, the kernel image, config, System.map and device trees under DIR/boot.\n\
+        The /lib/modules/<ident>/{build,source} links are relative to DIR, and mkinitcpio, update-grub \
+        and dkms run inside it with arch-chroot (cross-architecture roots need qemu-user binfmt)."
+    )]
+    pub target_root: Option<PathBuf>,
+
+    /// Base directory for out-of-tree builds; artifacts go to `<DIR>/<ident>` via `make O=`.
+    #[arg(
+        long = "build-dir",
+        value_name = "DIR",
+        help = "Build out of tree in <DIR>/<ident> (make O=), e.g. on an SSD or tmpfs",
+        long_help = "Keep build artifacts out of the source tree: every make invocation gets \
+        O=<DIR>/<new ident>, `kernel-install` reads bzImage from there and /lib/modules/<ident>/build \
+        points at it. A completely extracted (clean) source tree is reused instead of downloading it again."
+    )]
+    pub build_dir: Option<PathBuf>,
+
+    /// Install a pruned headers tree and point `build`/`source` at it instead of the source tree.
+    #[arg(
+        long = "install-headers",
+        help = "Install a pruned headers tree into /usr/src/linux-<ident> for DKMS (source tree becomes disposable)",
+        long_help = "On install, copy the subset of the source and build trees needed to build external modules \
+        (Makefiles, Kconfig files, include/, arch headers, scripts/, Module.symvers, .config, ...) into \
+        /usr/src/linux-<ident>, like distributions' linux-headers packages, and point \
+        /lib/modules/<ident>/build and source at it. The multi-GB source tree can then be deleted without \
+        breaking DKMS."
+    )]
+    pub install_headers: bool,
+
+    /// Build with the Clang/LLVM toolchain (`LLVM=1`), also used for DKMS modules.
+    #[arg(long, help = "Build with the Clang/LLVM toolchain (LLVM=1)")]
+    pub llvm: bool,
+
+    /// Override the integrated assembler choice (`LLVM_IAS=0|1`).
+    #[arg(
+        long = "llvm-ias",
+        value_name = "0|1",
+        value_parser = clap::value_parser!(u8).range(0..=1),
+        requires = "llvm",
+        help = "Use (1) or disable (0) Clang's integrated assembler (LLVM_IAS)"
+    )]
+    pub llvm_ias: Option<u8>,
+
+    /// Custom target compiler (`CC=`).
+    #[arg(
+        long,
+        value_name = "CC",
+        help = "C compiler for the kernel (make CC=...)"
+    )]
+    pub cc: Option<String>,
+
+    /// Custom host compiler for build tools (`HOSTCC=`).
+    #[arg(
+        long,
+        value_name = "HOSTCC",
+        help = "C compiler for host build tools (make HOSTCC=...)"
+    )]
+    pub hostcc: Option<String>,
+
+    /// Enable Clang ThinLTO (`CONFIG_LTO_CLANG_THIN`).
+    #[arg(
+        long = "thin-lto",
+        requires = "llvm",
+        help = "Enable Clang ThinLTO (sets CONFIG_LTO_CLANG_THIN=y, requires --llvm)"
+    )]
+    pub thin_lto: bool,
+
+    /// Compiler cache wrapping `CC`; statistics are reported after the build.
+    #[arg(
+        long = "compiler-cache",
+        value_enum,
+        value_name = "CACHE",
+        help = "Wrap the compiler with ccache or sccache and report cache hits"
+    )]
+    pub compiler_cache: Option<CompilerCache>,
+
+    /// Extra flags for kernel and module C files (`KCFLAGS=`), e.g. `-march=native`.
+    #[arg(
+        long,
+        value_name = "FLAGS",
+        allow_hyphen_values = true,
+        help = "Extra compiler flags for the kernel and DKMS modules (KCFLAGS), e.g. -march=native"
+    )]
+    pub kcflags: Option<String>,
+
+    /// Number of parallel make jobs; disables the memory-aware default.
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Number of parallel make jobs (default: cores - 1, capped by available memory)"
+    )]
+    pub jobs: Option<u32>,
+
+    /// Do not start new jobs while the load average is above this value (`make -l`).
+    #[arg(
+        long = "load-average",
+        value_name = "LOAD",
+        help = "Don't start new make jobs above this load average (make -l)"
+    )]
+    pub load_average: Option<f64>,
+
+    /// Memory budget per job used to cap the default job count.
+    #[arg(
+        long = "mem-per-job",
+        value_name = "MiB",
+        help = "Memory budgeted per make job when choosing the default job count",
+        long_help = "Memory (MiB) budgeted per make job: without --jobs, the job count is capped to \
+        MemAvailable / MiB. Defaults to 1024, or 2048 with --thin-lto."
+    )]
+    pub mem_per_job: Option<u64>,
+
+    /// CPU niceness of the build processes (`nice -n`).
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(i32).range(-20..=19),
+        allow_negative_numbers = true,
+        help = "Run the kernel build with this CPU niceness (nice -n, -20..19)"
+    )]
+    pub nice: Option<i32>,
+
+    /// I/O scheduling class of the build processes (`ionice -c`).
+    #[arg(
+        long,
+        value_enum,
+        value_name = "CLASS",
+        help = "Run the kernel build with this I/O scheduling class (ionice)"
+    )]
+    pub ionice: Option<IoSchedulingClass>,
+}
+
+/// DKMS related options, flattened into [`Arguments`].
+#[derive(Args, Debug, Default, Clone, PartialEq)]
+pub struct DkmsArgs {
+    /// DKMS modules to manage, as `name[@version][:required|:optional]` (repeatable or comma-separated).
+    #[arg(
+        long = "dkms-module",
+        value_name = "MODULE",
+        value_delimiter = ',',
+        help = "DKMS module to manage (default: nvidia,v4l2loopback)",
+        long_help = "DKMS module to manage, as `name[@version][:required|:optional]`. Repeatable or comma-separated.\n\
+        Without `@version`, the newest version registered in DKMS is selected.\n\
+        Use `all` to act on every module registered in `dkms status`.\n\
+        A failing required module aborts the run; a failing optional module only warns.\n\
+        Defaults to `nvidia,v4l2loopback` (both required)."
+    )]
+    pub modules: Vec<DkmsModuleSpec>,
+
+    /// Check every DKMS module's `dkms.conf` restrictions against the new kernel right after compiling.
+    #[arg(
+        long = "dkms-precheck",
+        help = "Check DKMS module compatibility with the new kernel after compiling",
+        long_help = "Right after `compile`, evaluate each DKMS module's dkms.conf \
+        (BUILD_EXCLUSIVE_KERNEL, BUILD_EXCLUSIVE_CONFIG, PATCH_MATCH) against the new kernel and its .config.\n\
+        A required module that cannot build stops the pipeline before the kernel is installed."
+    )]
+    pub precheck: bool,
+
+    /// Additionally build every DKMS module into a scratch tree after compiling (implies --dkms-precheck).
+    #[arg(
+        long = "dkms-trial-build",
+        help = "Trial-build DKMS modules in a scratch tree after compiling (implies --dkms-precheck)"
+    )]
+    pub trial_build: bool,
+
+    /// Sign every DKMS-built module for the new kernel (required with CONFIG_MODULE_SIG_FORCE).
+    #[arg(
+        long,
+        help = "Sign DKMS-built modules with the kernel's module signing key",
+        long_help = "Sign every DKMS-built module for the new kernel using `scripts/sign-file`.\n\
+        By default the key generated by the kernel build (certs/signing_key.pem) is used.\n\
+        Signatures are verified afterwards via `modinfo`."
+    )]
+    pub sign_modules: bool,
+
+    /// Private key of a Machine Owner Key (MOK) used instead of the kernel build key.
+    #[arg(
+        long,
+        value_name = "KEY",
+        requires = "mok_cert",
+        help = "MOK private key used to sign DKMS modules"
+    )]
+    pub mok_key: Option<PathBuf>,
+
+    /// Certificate of the Machine Owner Key (MOK) used instead of the kernel build certificate.
+    #[arg(
+        long,
+        value_name = "CERT",
+        requires = "mok_key",
+        help = "MOK certificate (DER/PEM) used to sign DKMS modules"
+    )]
+    pub mok_cert: Option<PathBuf>,
 }
 
 // --- Enums ---
@@ -105,12 +519,187 @@ pub enum Commands {
     /// Install the compiled kernel modules and binary to system directories (/lib/modules, /boot).
     /// Requires a compiled source tree for --new to exist. Runs mkinitcpio and update-grub.
     #[command(name = "kernel-install", about = "Install the compiled kernel")] // Added about
-    KernelInstall,
+    KernelInstall {
+        /// Install from a bundle created by the `bundle` command instead of the source tree.
+        #[arg(
+            long,
+            value_name = "BUNDLE",
+            help = "Install modules, image, config and System.map from a bundle (no source tree needed)"
+        )]
+        from_bundle: Option<PathBuf>,
+    },
 
     /// Build and install DKMS modules for the new kernel, and remove old modules.
     /// Requires --new AND --old, and NEW > OLD. Runs mkinitcpio and update-grub.
     #[command(name = "dkms-install", about = "Build/install DKMS modules")] // Added about
     DkmsInstall,
+
+    /// Archive the compiled kernel into a self-describing bundle (manifest with version,
+    /// suffix, arch and checksums) for `kernel-install --from-bundle` on another machine.
+    #[command(
+        name = "bundle",
+        about = "Bundle the compiled kernel for another machine"
+    )]
+    Bundle {
+        /// Directory receiving the bundle.
+        #[arg(
+            long,
+            value_name = "DIR",
+            default_value = "/var/cache/kernel-updater/bundles",
+            help = "Directory receiving the bundle"
+        )]
+        bundle_dir: PathBuf,
+    },
+
+    /// Package the compiled kernel, modules, headers and config instead of installing them.
+    /// Requires a compiled source tree for --new to exist. The host system is left untouched.
+    #[command(name = "package", about = "Package the compiled kernel")]
+    Package {
+        /// Package format to produce.
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = PackageFormat::Arch,
+            help = "Package format: arch (.pkg.tar.zst), deb (make bindeb-pkg), rpm (make binrpm-pkg) or tar (make tar-pkg)"
+        )]
+        format: PackageFormat,
+
+        /// Directory receiving the produced packages.
+        #[arg(
+            long,
+            value_name = "DIR",
+            default_value = "/var/cache/kernel-updater/packages",
+            help = "Directory receiving the produced packages"
+        )]
+        package_dir: PathBuf,
+    },
+
+    /// List past runs from the reports stored next to the run logs.
+    /// Does not require --new.
+    #[command(name = "history", about = "List past runs and their timings")]
+    History {
+        /// Number of most recent runs to show.
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 20,
+            help = "Number of most recent runs to show"
+        )]
+        limit: usize,
+    },
+}
+
+impl Commands {
+    /// Name of the subcommand on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::KernelCompile => "kernel-compile",
+            Self::KernelInstall { .. } => "kernel-install",
+            Self::DkmsInstall => "dkms-install",
+            Self::Bundle { .. } => "bundle",
+            Self::Package { .. } => "package",
+            Self::History { .. } => "history",
+        }
+    }
+}
+
+/// Distributable package produced by the `package` command.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+pub enum PackageFormat {
+    /// Arch Linux package (`.pkg.tar.zst`) assembled from a staged tree.
+    Arch,
+    /// Debian packages built by `make bindeb-pkg`.
+    Deb,
+    /// RPM packages built by `make binrpm-pkg`.
+    Rpm,
+    /// Plain tarball built by `make tar-pkg`.
+    Tar,
+}
+
+impl PackageFormat {
+    /// The Kbuild target producing this format, or `None` when the package is assembled here.
+    pub fn make_target(&self) -> Option<&'static str> {
+        match self {
+            Self::Arch => None,
+            Self::Deb => Some("bindeb-pkg"),
+            Self::Rpm => Some("binrpm-pkg"),
+            Self::Tar => Some("tar-pkg"),
+        }
+    }
+
+    /// Whether `file_name` is a package of this format.
+    pub fn matches(&self, file_name: &str) -> bool {
+        match self {
+            Self::Arch => file_name.ends_with(".pkg.tar.zst"),
+            Self::Deb => file_name.ends_with(".deb"),
+            Self::Rpm => file_name.ends_with(".rpm"),
+            Self::Tar => file_name.starts_with("linux-") && file_name.contains(".tar"),
+        }
+    }
+}
+
+/// Compiler cache wrapper selected by `--compiler-cache`.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+pub enum CompilerCache {
+    Ccache,
+    Sccache,
+}
+
+impl CompilerCache {
+    /// The executable wrapping the compiler.
+    pub fn program(&self) -> &'static str {
+        match self {
+            Self::Ccache => "ccache",
+            Self::Sccache => "sccache",
+        }
+    }
+}
+
+/// I/O scheduling class selected by `--ionice`.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+pub enum IoSchedulingClass {
+    /// Only use the disk when no other process needs it.
+    Idle,
+    /// Default class, at the lowest priority.
+    BestEffort,
+}
+
+impl IoSchedulingClass {
+    /// Arguments selecting the class with `ionice`.
+    pub fn ionice_args(&self) -> &'static [&'static str] {
+        match self {
+            Self::Idle => &["-c", "3"],
+            Self::BestEffort => &["-c", "2", "-n", "7"],
+        }
+    }
+}
+
+/// Interactive configuration front-end run by `--edit-config`.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+pub enum ConfigEditor {
+    Menuconfig,
+    Nconfig,
+    Xconfig,
+}
+
+impl ConfigEditor {
+    /// The `make` target that starts the editor.
+    pub fn make_target(&self) -> &'static str {
+        match self {
+            Self::Menuconfig => "menuconfig",
+            Self::Nconfig => "nconfig",
+            Self::Xconfig => "xconfig",
+        }
+    }
+}
+
+/// Source of a base config created by `--seed-config`.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+pub enum ConfigSeed {
+    /// The configuration of the running kernel.
+    Running,
+    /// The configuration of the kernel given by `--old`.
+    Previous,
 }
 
 #[derive(Debug, Default, Clone, ValueEnum, PartialEq)]
@@ -119,3 +708,39 @@ pub enum Downloader {
     Curl,
     Wget,
 }
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_args
+#[cfg(test)]
+mod tests_args {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Arguments {
+        let args = ["kernel-updater", "-n", "6.15.4"].iter().chain(args);
+        Arguments::parse_from(join_edit_config_value(args.map(OsString::from)))
+    }
+
+    #[test]
+    fn test_edit_config_value_forms() {
+        let args = parse(&["--edit-config", "nconfig", "kernel-compile"]);
+        assert_eq!(args.build.edit_config, Some(ConfigEditor::Nconfig));
+        assert_eq!(args.command, Some(Commands::KernelCompile));
+
+        let args = parse(&["--edit-config=xconfig"]);
+        assert_eq!(args.build.edit_config, Some(ConfigEditor::Xconfig));
+
+        // Without a value, the next argument is not taken as the editor
+        let args = parse(&["--edit-config", "kernel-compile"]);
+        assert_eq!(args.build.edit_config, Some(ConfigEditor::Menuconfig));
+        assert_eq!(args.command, Some(Commands::KernelCompile));
+        assert_eq!(parse(&[]).build.edit_config, None);
+    }
+}
@@ -0,0 +1,734 @@
+use crate::{
+    Arch, AtomicWriteExt, Config, KernelBuilder, Version,
+    error::KernelUpdaterError,
+    headers::KernelHeaders,
+    info,
+    utils::{copy_tree, run_command, run_command_output},
+};
+use clap::ValueEnum;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Manifest at the root of every bundle.
+pub const MANIFEST_NAME: &str = "MANIFEST";
+
+/// Layout version of the bundle, bumped on incompatible changes.
+/// Version 2 records symlink targets in the manifest.
+const BUNDLE_FORMAT: u32 = 2;
+
+/// Files hashed per `sha256sum` invocation, keeping command lines short.
+const CHECKSUM_BATCH: usize = 256;
+
+/// Locations of the boot artifacts inside a bundle (modules keep their `lib/modules` path).
+const BUNDLE_IMAGE: &str = "boot/vmlinuz";
+const BUNDLE_CONFIG: &str = "boot/config";
+const BUNDLE_SYSTEM_MAP: &str = "boot/System.map";
+const BUNDLE_DTBS: &str = "boot/dtbs";
+
+/// Separator between the path and the target of a `symlink` manifest line.
+const SYMLINK_SEPARATOR: &str = " -> ";
+
+/// Self-description of a bundle: the kernel it holds, the SHA-256 of every file and the
+/// target of every symlink.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BundleManifest {
+    pub version: Version,
+    pub suffix: String,
+    pub arch: Arch,
+    /// Checksums by path relative to the bundle root.
+    pub checksums: BTreeMap<PathBuf, String>,
+    /// Symlink targets (as stored in the link) by path relative to the bundle root.
+    pub symlinks: BTreeMap<PathBuf, PathBuf>,
+}
+
+impl BundleManifest {
+    /// Kernel ident (`<version>-<suffix>`) of the bundled kernel.
+    pub fn ident(&self) -> String {
+        format!("{}-{}", self.version, self.suffix)
+    }
+
+    /// Renders the manifest as `key = value` lines, one `sha256` line per file and one
+    /// `symlink` line per symlink.
+    pub fn render(&self) -> String {
+        let mut manifest = format!(
+            "# kernel-updater bundle manifest\n\
+            format = {BUNDLE_FORMAT}\n\
+            version = {}\n\
+            suffix = {}\n\
+            ident = {}\n\
+            arch = {}\n",
+            self.version,
+            self.suffix,
+            self.ident(),
+            self.arch
+        );
+        for (path, checksum) in &self.checksums {
+            manifest.push_str(&format!("sha256 = {checksum}  {}\n", path.display()));
+        }
+        for (path, target) in &self.symlinks {
+            manifest.push_str(&format!(
+                "symlink = {}{SYMLINK_SEPARATOR}{}\n",
+                path.display(),
+                target.display()
+            ));
+        }
+        manifest
+    }
+
+    /// Parses a manifest produced by [`Self::render`], returning the reason on failure.
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut version = None;
+        let mut suffix = None;
+        let mut arch = None;
+        let mut checksums = BTreeMap::new();
+        let mut symlinks = BTreeMap::new();
+
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(" = ")
+                .ok_or_else(|| format!("line {}: expected `key = value`", index + 1))?;
+
+            match key {
+                "format" if value == BUNDLE_FORMAT.to_string() => {}
+                "format" => return Err(format!("unsupported bundle format {value}")),
+                "version" => {
+                    version = Some(Version::from_str(value).map_err(|err| err.to_string())?)
+                }
+                "suffix" => suffix = Some(value.to_string()),
+                // Derived from version and suffix, kept for human readers
+                "ident" => {}
+                "arch" => arch = Some(Arch::from_str(value, false)?),
+                "sha256" => {
+                    let (checksum, path) = value.split_once("  ").ok_or_else(|| {
+                        format!("line {}: expected `<sha256>  <path>`", index + 1)
+                    })?;
+                    checksums.insert(PathBuf::from(path), checksum.to_string());
+                }
+                "symlink" => {
+                    let (path, target) = value.split_once(SYMLINK_SEPARATOR).ok_or_else(|| {
+                        format!("line {}: expected `<path> -> <target>`", index + 1)
+                    })?;
+                    symlinks.insert(PathBuf::from(path), PathBuf::from(target));
+                }
+                _ => return Err(format!("line {}: unknown key `{key}`", index + 1)),
+            }
+        }
+
+        Ok(Self {
+            version: version.ok_or("missing `version`")?,
+            suffix: suffix.ok_or("missing `suffix`")?,
+            arch: arch.ok_or("missing `arch`")?,
+            checksums,
+            symlinks,
+        })
+    }
+
+    /// Checks that the bundle holds the kernel selected by `--new`, the suffix and `--arch`.
+    pub fn check_matches(&self, config: &Config, bundle: &Path) -> Result<(), KernelUpdaterError> {
+        let fields = [
+            (
+                "version",
+                config.version_new.to_string(),
+                self.version.to_string(),
+            ),
+            (
+                "suffix",
+                config.custom_kernel_suffix.clone(),
+                self.suffix.clone(),
+            ),
+            (
+                "architecture",
+                config.arch.to_string(),
+                self.arch.to_string(),
+            ),
+        ];
+
+        for (field, expected, found) in fields {
+            if expected != found {
+                return Err(KernelUpdaterError::BundleMismatch {
+                    bundle: bundle.to_path_buf(),
+                    field: field.to_string(),
+                    expected,
+                    found,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Creates bundles of a compiled kernel and installs them without the source tree,
+/// so a kernel built on one machine can be deployed on others.
+///
+/// A bundle is a `tar --zstd` archive laid out like the target root: `lib/modules/<ident>/`
+/// (with a pruned headers tree as `build/` for DKMS), `boot/vmlinuz`, `boot/config`,
+/// `boot/System.map`, `boot/dtbs/` on device tree platforms, and the `MANIFEST`.
+pub struct KernelBundle<'a> {
+    config: &'a Config,
+}
+
+impl<'a> KernelBundle<'a> {
+    /// Creates a new `KernelBundle` instance.
+    pub fn new(config: &'a Config) -> Self {
+        Self { config }
+    }
+
+    /// Location of the bundle of the new kernel in `bundle_dir`.
+    pub fn bundle_path(&self, bundle_dir: &Path) -> PathBuf {
+        bundle_dir.join(format!(
+            "kernel-{}-{}.tar.zst",
+            self.config.kernel_ident_name_new, self.config.arch
+        ))
+    }
+
+    /// Bundles the compiled tree into `bundle_dir`, returning the bundle path.
+    pub fn create(&self, bundle_dir: &Path) -> Result<PathBuf, KernelUpdaterError> {
+        // Fail early, with the usual hint, if the tree has not been compiled
+        KernelBuilder::new(self.config).kernel_image()?;
+
+        fs::create_dir_all(bundle_dir)?;
+        let bundle_dir = bundle_dir.canonicalize()?;
+        let staging = bundle_dir.join(format!(".staging-{}", self.config.kernel_ident_name_new));
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+
+        let manifest = self.stage(&staging)?;
+
+        let bundle_path = self.bundle_path(&bundle_dir);
+        info!(
+            "Archiving {} files into bundle: {}",
+            manifest.checksums.len(),
+            bundle_path.display()
+        );
+        let bundle_arg = bundle_path.display().to_string();
+        let staging_arg = staging.display().to_string();
+        run_command(
+            "tar",
+            &["--zstd", "-cf", &bundle_arg, "-C", &staging_arg, "."],
+        )?;
+
+        fs::remove_dir_all(&staging)?;
+        Ok(bundle_path)
+    }
+
+    /// Installs the modules, headers and boot artifacts under `staging` and writes the manifest.
+    pub fn stage(&self, staging: &Path) -> Result<BundleManifest, KernelUpdaterError> {
+        let builder = KernelBuilder::new(self.config);
+        env::set_current_dir(&self.config.kernel_src_dir_path)?;
+        let image = builder.kernel_image()?;
+
+        fs::create_dir_all(staging)?;
+        info!("Staging modules under {}...", staging.display());
+        let install_mod_path = format!("INSTALL_MOD_PATH={}", staging.display());
+        builder.make(&["modules_install", &install_mod_path])?;
+
+        let modules_dir = staging
+            .join("lib/modules")
+            .join(&self.config.kernel_ident_name_new);
+        fs::create_dir_all(&modules_dir)?;
+
+        // Kbuild links these to this machine's trees; the headers replace them
+        for link in ["build", "source"] {
+            let link_path = modules_dir.join(link);
+            if link_path.is_symlink() {
+                fs::remove_file(&link_path)?;
+            }
+        }
+        KernelHeaders::new(self.config).install(&modules_dir.join("build"))?;
+
+        let build_dir = &self.config.kernel_build_dir_path;
+        fs::create_dir_all(staging.join("boot"))?;
+        staging.join(BUNDLE_IMAGE).atomic_copy_from(&image)?;
+        staging
+            .join(BUNDLE_CONFIG)
+            .atomic_copy_from(&build_dir.join(".config"))?;
+        staging
+            .join(BUNDLE_SYSTEM_MAP)
+            .atomic_copy_from(&build_dir.join("System.map"))?;
+
+        if self.config.arch.has_device_trees() {
+            let install_dtbs_path =
+                format!("INSTALL_DTBS_PATH={}", staging.join(BUNDLE_DTBS).display());
+            builder.make(&["dtbs_install", &install_dtbs_path])?;
+        }
+
+        let manifest = BundleManifest {
+            version: self.config.version_new.clone(),
+            suffix: self.config.custom_kernel_suffix.clone(),
+            arch: self.config.arch,
+            checksums: checksum_tree(staging)?,
+            symlinks: symlink_tree(staging)?,
+        };
+        fs::write(staging.join(MANIFEST_NAME), manifest.render())?;
+        Ok(manifest)
+    }
+
+    /// Extracts and verifies `bundle`, then installs the kernel it holds into the target root.
+    pub fn install(&self, bundle: &Path) -> Result<BundleManifest, KernelUpdaterError> {
+        info!(
+            "Initializing installation from bundle: {}",
+            bundle.display()
+        );
+        let extract_dir = self
+            .config
+            .kernel_src_base
+            .join(format!(".bundle-{}", self.config.kernel_ident_name_new));
+        if extract_dir.exists() {
+            fs::remove_dir_all(&extract_dir)?;
+        }
+        fs::create_dir_all(&extract_dir)?;
+
+        let bundle_arg = bundle.display().to_string();
+        let extract_arg = extract_dir.display().to_string();
+        let result = run_command("tar", &["--zstd", "-xf", &bundle_arg, "-C", &extract_arg])
+            .and_then(|()| self.install_extracted(&extract_dir, bundle));
+
+        let _ = fs::remove_dir_all(&extract_dir);
+        result
+    }
+
+    /// Verifies an extracted bundle against its manifest and installs it.
+    pub fn install_extracted(
+        &self,
+        dir: &Path,
+        bundle: &Path,
+    ) -> Result<BundleManifest, KernelUpdaterError> {
+        let manifest_path = dir.join(MANIFEST_NAME);
+        let content =
+            fs::read_to_string(&manifest_path).map_err(|io_error| KernelUpdaterError::IOError {
+                path: manifest_path.clone(),
+                io_error,
+            })?;
+        let manifest = BundleManifest::parse(&content).map_err(|reason| {
+            KernelUpdaterError::BundleManifestInvalid {
+                bundle: bundle.to_path_buf(),
+                reason,
+            }
+        })?;
+
+        manifest.check_matches(self.config, bundle)?;
+        verify_checksums(dir, &manifest, bundle)?;
+        info!(
+            "Bundle verified: {} {} ({} files).",
+            manifest.ident(),
+            manifest.arch,
+            manifest.checksums.len()
+        );
+
+        let ident = &self.config.kernel_ident_name_new;
+        let modules_target = self.config.kernel_module_base.join(ident);
+        info!("Installing modules to: {}", modules_target.display());
+        if modules_target.exists() {
+            fs::remove_dir_all(&modules_target)?;
+        }
+        copy_tree(&dir.join("lib/modules").join(ident), &modules_target)?;
+
+        info!(
+            "Deploying boot image target to: {}",
+            self.config.vmlinuz_install_path.display()
+        );
+        self.config
+            .vmlinuz_install_path
+            .atomic_copy_from(&dir.join(BUNDLE_IMAGE))?;
+
+        let boot_dir = self.config.boot_dir();
+        for (source, target) in [
+            (BUNDLE_CONFIG, boot_dir.join(format!("config-{ident}"))),
+            (
+                BUNDLE_SYSTEM_MAP,
+                boot_dir.join(format!("System.map-{ident}")),
+            ),
+        ] {
+            info!("Installing {source} to: {}", target.display());
+            target.atomic_copy_from(&dir.join(source))?;
+        }
+
+        let bundled_dtbs = dir.join(BUNDLE_DTBS);
+        if let Some(dtbs_path) = &self.config.dtbs_install_path
+            && bundled_dtbs.is_dir()
+        {
+            info!("Installing device tree blobs to: {}", dtbs_path.display());
+            if dtbs_path.exists() {
+                fs::remove_dir_all(dtbs_path)?;
+            }
+            copy_tree(&bundled_dtbs, dtbs_path)?;
+        }
+
+        info!("Kernel installation from bundle successfully completed.");
+        Ok(manifest)
+    }
+}
+
+/// Compares the files and symlinks of an extracted bundle with its manifest, failing with
+/// every missing, altered or unlisted one.
+fn verify_checksums(
+    dir: &Path,
+    manifest: &BundleManifest,
+    bundle: &Path,
+) -> Result<(), KernelUpdaterError> {
+    let mut files = mismatched_entries(&manifest.checksums, &checksum_tree(dir)?);
+    files.extend(mismatched_entries(&manifest.symlinks, &symlink_tree(dir)?));
+
+    if files.is_empty() {
+        Ok(())
+    } else {
+        files.sort();
+        Err(KernelUpdaterError::BundleChecksumMismatch {
+            bundle: bundle.to_path_buf(),
+            files,
+        })
+    }
+}
+
+/// Paths whose expected and actual values differ, including missing and unlisted ones.
+fn mismatched_entries<T: PartialEq>(
+    expected: &BTreeMap<PathBuf, T>,
+    actual: &BTreeMap<PathBuf, T>,
+) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = expected
+        .iter()
+        .filter(|(path, value)| actual.get(*path) != Some(*value))
+        .map(|(path, _)| path.clone())
+        .collect();
+    paths.extend(
+        actual
+            .keys()
+            .filter(|path| !expected.contains_key(*path))
+            .cloned(),
+    );
+    paths
+}
+
+/// SHA-256 of every regular file under `root` (except the manifest), by relative path.
+/// Fails on paths the manifest cannot record, so no file escapes verification.
+pub fn checksum_tree(root: &Path) -> Result<BTreeMap<PathBuf, String>, KernelUpdaterError> {
+    let mut files = Vec::new();
+    collect_entries(root, root, &mut files, &mut Vec::new())?;
+    files.retain(|path| path != &root.join(MANIFEST_NAME));
+
+    let mut checksums = BTreeMap::new();
+    for batch in files.chunks(CHECKSUM_BATCH) {
+        // NUL-terminated lines: file names are printed as is, without escaping
+        let mut args = vec!["--zero", "--"];
+        for path in batch {
+            args.push(manifest_text(root, path)?);
+        }
+        let output = run_command_output("sha256sum", &args)?;
+
+        for line in output.split('\0') {
+            if let Some((checksum, path)) = line.split_once("  ")
+                && let Ok(relative) = Path::new(path).strip_prefix(root)
+            {
+                checksums.insert(relative.to_path_buf(), checksum.to_string());
+            }
+        }
+    }
+
+    if let Some(missing) = files.iter().find(|path| {
+        !path
+            .strip_prefix(root)
+            .is_ok_and(|relative| checksums.contains_key(relative))
+    }) {
+        return Err(unrecordable(
+            root,
+            missing,
+            "sha256sum reported no checksum for it",
+        ));
+    }
+    Ok(checksums)
+}
+
+/// Target of every symlink under `root`, by relative path. Symlinks are recorded, not followed.
+pub fn symlink_tree(root: &Path) -> Result<BTreeMap<PathBuf, PathBuf>, KernelUpdaterError> {
+    let mut links = Vec::new();
+    collect_entries(root, root, &mut Vec::new(), &mut links)?;
+
+    let mut symlinks = BTreeMap::new();
+    for link in links {
+        if manifest_text(root, &link)?.contains(SYMLINK_SEPARATOR) {
+            return Err(unrecordable(
+                root,
+                &link,
+                &format!("the name contains `{}`", SYMLINK_SEPARATOR.trim()),
+            ));
+        }
+        let target = fs::read_link(&link)?;
+        manifest_text(root, &target)?;
+        if let Ok(relative) = link.strip_prefix(root) {
+            symlinks.insert(relative.to_path_buf(), target);
+        }
+    }
+    Ok(symlinks)
+}
+
+/// Lists the regular files and the symlinks under `dir`, without following symlinks.
+/// Other file types (devices, FIFOs, sockets) cannot be verified and are rejected.
+fn collect_entries(
+    root: &Path,
+    dir: &Path,
+    files: &mut Vec<PathBuf>,
+    symlinks: &mut Vec<PathBuf>,
+) -> Result<(), KernelUpdaterError> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_entries(root, &entry.path(), files, symlinks)?;
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        } else if file_type.is_symlink() {
+            symlinks.push(entry.path());
+        } else {
+            return Err(unrecordable(
+                root,
+                &entry.path(),
+                "not a file, directory or symlink",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A path as written in the manifest, which holds one UTF-8 entry per line.
+fn manifest_text<'a>(root: &Path, path: &'a Path) -> Result<&'a str, KernelUpdaterError> {
+    match path.to_str() {
+        Some(text) if !text.contains('\n') => Ok(text),
+        Some(_) => Err(unrecordable(root, path, "the name contains a newline")),
+        None => Err(unrecordable(root, path, "the name is not valid UTF-8")),
+    }
+}
+
+/// Error for an entry of the bundle tree `root` that cannot be recorded in, or verified
+/// against, the manifest.
+fn unrecordable(root: &Path, path: &Path, reason: &str) -> KernelUpdaterError {
+    KernelUpdaterError::BundleManifestInvalid {
+        bundle: root.to_path_buf(),
+        reason: format!("{} cannot be recorded: {reason}", path.display()),
+    }
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_bundle
+#[cfg(test)]
+mod tests_bundle {
+    use super::*;
+    use crate::Commands;
+    use crate::test_utils::{CurrentDirGuard, TempDirGuard, staged_config};
+
+    fn sample_manifest() -> BundleManifest {
+        BundleManifest {
+            version: Version::new(6, 15, 4),
+            suffix: "TestSuffix".to_string(),
+            arch: Arch::X86_64,
+            checksums: BTreeMap::from([
+                (PathBuf::from("boot/vmlinuz"), "ab12".to_string()),
+                (
+                    PathBuf::from("lib/modules/6.15.4-TestSuffix/modules.dep"),
+                    "cd34".to_string(),
+                ),
+            ]),
+            symlinks: BTreeMap::from([(
+                PathBuf::from(
+                    "lib/modules/6.15.4-TestSuffix/build/scripts/dtc/include-prefixes/arm",
+                ),
+                PathBuf::from("../../../arch/arm/boot/dts"),
+            )]),
+        }
+    }
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let manifest = sample_manifest();
+        let rendered = manifest.render();
+
+        assert!(rendered.contains("ident = 6.15.4-TestSuffix\n"));
+        assert!(rendered.contains("sha256 = ab12  boot/vmlinuz\n"));
+        assert!(rendered.contains(
+            "symlink = lib/modules/6.15.4-TestSuffix/build/scripts/dtc/include-prefixes/arm -> ../../../arch/arm/boot/dts\n"
+        ));
+        assert_eq!(BundleManifest::parse(&rendered).unwrap(), manifest);
+    }
+
+    #[test]
+    fn test_manifest_parse_errors() {
+        let rendered = sample_manifest().render();
+
+        let newer = rendered.replace("format = 2", "format = 3");
+        assert_eq!(
+            BundleManifest::parse(&newer).unwrap_err(),
+            "unsupported bundle format 3"
+        );
+        let without_arch = rendered.replace("arch = x86_64\n", "");
+        assert_eq!(
+            BundleManifest::parse(&without_arch).unwrap_err(),
+            "missing `arch`"
+        );
+        assert!(BundleManifest::parse("garbage").is_err());
+    }
+
+    #[test]
+    fn test_manifest_must_match_new_kernel() {
+        let temp_dir = TempDirGuard::new("bundle-mismatch");
+        let config = staged_config(
+            &temp_dir.path,
+            Commands::KernelInstall { from_bundle: None },
+        );
+        let mut manifest = sample_manifest();
+        assert!(manifest.check_matches(&config, Path::new("b")).is_ok());
+
+        manifest.arch = Arch::Arm64;
+        let err = manifest.check_matches(&config, Path::new("b")).unwrap_err();
+        assert!(
+            matches!(&err, KernelUpdaterError::BundleMismatch { field, found, .. } if field == "architecture" && found == "arm64")
+        );
+    }
+
+    #[test]
+    fn test_bundle_round_trip_between_roots() {
+        let _cwd_guard = CurrentDirGuard::new();
+        let build_root = TempDirGuard::new("bundle-build");
+        let config = staged_config(
+            &build_root.path,
+            Commands::Bundle {
+                bundle_dir: build_root.path.join("bundles"),
+            },
+        );
+
+        // A compiled tree whose Makefile stands in for Kbuild's modules_install
+        let src_dir = &config.kernel_src_dir_path;
+        let image = src_dir.join(&config.arch.image_candidates()[0]);
+        fs::create_dir_all(image.parent().unwrap()).unwrap();
+        fs::write(&image, "kernel image").unwrap();
+        fs::write(src_dir.join(".config"), "CONFIG_MODULES=y\n").unwrap();
+        fs::write(src_dir.join("System.map"), "symbols\n").unwrap();
+        fs::write(src_dir.join("Module.symvers"), "").unwrap();
+        fs::write(
+            src_dir.join("Makefile"),
+            "modules_install:\n\
+             \tmkdir -p $(INSTALL_MOD_PATH)/lib/modules/6.15.4-TestSuffix/kernel\n\
+             \techo module > $(INSTALL_MOD_PATH)/lib/modules/6.15.4-TestSuffix/kernel/dummy.ko\n",
+        )
+        .unwrap();
+
+        let bundle = KernelBundle::new(&config)
+            .create(&build_root.path.join("bundles"))
+            .unwrap();
+        assert!(bundle.ends_with("kernel-6.15.4-TestSuffix-x86_64.tar.zst"));
+
+        // Install on a "machine" without the source tree
+        let install_root = TempDirGuard::new("bundle-install");
+        fs::create_dir_all(install_root.path.join("lib/modules")).unwrap();
+        fs::create_dir_all(install_root.path.join("boot")).unwrap();
+        let install_config = staged_config(
+            &install_root.path,
+            Commands::KernelInstall {
+                from_bundle: Some(bundle.clone()),
+            },
+        );
+        let manifest = KernelBundle::new(&install_config).install(&bundle).unwrap();
+
+        let root = &install_root.path;
+        let modules_dir = root.join("lib/modules/6.15.4-TestSuffix");
+        assert_eq!(manifest.ident(), "6.15.4-TestSuffix");
+        assert!(modules_dir.join("kernel/dummy.ko").is_file());
+        assert!(modules_dir.join("build/Module.symvers").is_file());
+        assert_eq!(
+            fs::read_to_string(root.join("boot/vmlinuz-6.15")).unwrap(),
+            "kernel image"
+        );
+        assert!(root.join("boot/config-6.15.4-TestSuffix").is_file());
+        assert!(root.join("boot/System.map-6.15.4-TestSuffix").is_file());
+        // The extraction directory is cleaned up
+        assert!(!root.join("lib/modules/.bundle-6.15.4-TestSuffix").exists());
+        // DKMS and module signing use the bundled headers
+        assert_eq!(
+            install_config.kernel_build_dir_path,
+            modules_dir.join("build")
+        );
+    }
+
+    #[test]
+    fn test_verify_checksums_reports_altered_files() {
+        let temp_dir = TempDirGuard::new("bundle-verify");
+        let dir = &temp_dir.path;
+        fs::create_dir_all(dir.join("boot")).unwrap();
+        fs::write(dir.join("boot/vmlinuz"), "kernel image").unwrap();
+        fs::write(dir.join("boot/config"), "CONFIG_MODULES=y\n").unwrap();
+
+        std::os::unix::fs::symlink("vmlinuz", dir.join("boot/vmlinuz-linux")).unwrap();
+
+        let mut manifest = sample_manifest();
+        manifest.checksums = checksum_tree(dir).unwrap();
+        manifest.symlinks = symlink_tree(dir).unwrap();
+        assert_eq!(
+            manifest.symlinks,
+            BTreeMap::from([(
+                PathBuf::from("boot/vmlinuz-linux"),
+                PathBuf::from("vmlinuz")
+            )])
+        );
+        fs::write(dir.join(MANIFEST_NAME), manifest.render()).unwrap();
+        assert!(verify_checksums(dir, &manifest, Path::new("b")).is_ok());
+
+        fs::write(dir.join("boot/vmlinuz"), "tampered").unwrap();
+        fs::write(dir.join("boot/extra"), "").unwrap();
+        fs::remove_file(dir.join("boot/vmlinuz-linux")).unwrap();
+        std::os::unix::fs::symlink("/etc/shadow", dir.join("boot/vmlinuz-linux")).unwrap();
+        let err = verify_checksums(dir, &manifest, Path::new("b")).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                KernelUpdaterError::BundleChecksumMismatch { files, .. }
+                    if files == &[
+                        PathBuf::from("boot/extra"),
+                        PathBuf::from("boot/vmlinuz"),
+                        PathBuf::from("boot/vmlinuz-linux"),
+                    ]
+            ),
+            "Unexpected error: {err:?}"
+        );
+    }
+
+    #[test]
+    fn test_checksum_tree_rejects_unrecordable_paths() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let temp_dir = TempDirGuard::new("bundle-paths");
+        let dir = &temp_dir.path;
+        fs::write(dir.join(OsStr::from_bytes(b"latin1-\xe9.ko")), "").unwrap();
+        let err = checksum_tree(dir).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                KernelUpdaterError::BundleManifestInvalid { bundle, reason }
+                    if bundle == dir && reason.ends_with("the name is not valid UTF-8")
+            ),
+            "Unexpected error: {err:?}"
+        );
+
+        let temp_dir = TempDirGuard::new("bundle-links");
+        let dir = &temp_dir.path;
+        std::os::unix::fs::symlink("target", dir.join("odd -> name")).unwrap();
+        assert!(matches!(
+            symlink_tree(dir),
+            Err(KernelUpdaterError::BundleManifestInvalid { .. })
+        ));
+    }
+}
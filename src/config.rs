@@ -1,9 +1,10 @@
 use crate::{
-    Version,
-    args::{Arguments, Commands, Downloader},
+    Arch, Toolchain, Version,
+    args::{Arguments, BuildArgs, Commands, DkmsArgs, Downloader, OutputArgs},
     error::KernelUpdaterError,
+    info,
 };
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Represents the final, validated configuration derived from command-line arguments and constants.
 /// Contains all paths, versions, and settings needed to perform an operation.
@@ -14,6 +15,7 @@ pub struct Config {
     pub version_new: Version,
     pub command: Option<Commands>,
     pub kernel_url_base: String,
+    pub target_root: PathBuf,
     pub kernel_src_base: PathBuf,
     pub kernel_module_base: PathBuf,
     pub kernel_config_base: PathBuf,
@@ -21,15 +23,84 @@ pub struct Config {
     pub config_file_path: PathBuf,
     pub kernel_src_dir_name: String,
     pub kernel_src_dir_path: PathBuf,
+    pub kernel_build_dir_path: PathBuf,
     pub tarball_name: String,
     pub download_link: String,
     pub kernel_ident_name_new: String,
     pub kernel_ident_name_old: Option<String>,
     pub vmlinuz_install_path: PathBuf,
+    pub arch: Arch,
+    pub dtbs_install_path: Option<PathBuf>,
     pub downloader: Downloader,
+    pub build: BuildArgs,
+    pub dkms: DkmsArgs,
+    pub output: OutputArgs,
 }
 
 impl Config {
+    /// Directory holding the kernel image, its config and `System.map`.
+    pub fn boot_dir(&self) -> &Path {
+        self.vmlinuz_install_path
+            .parent()
+            .unwrap_or(Path::new("/boot"))
+    }
+
+    /// Whether the target architecture differs from the host, requiring `ARCH=`/`CROSS_COMPILE=`.
+    pub fn is_cross_compiling(&self) -> bool {
+        Arch::host() != Some(self.arch)
+    }
+
+    /// `CROSS_COMPILE=` prefix: `--cross-compile`, or the default one for a foreign architecture.
+    pub fn cross_compile_prefix(&self) -> Option<&str> {
+        match &self.build.cross_compile {
+            Some(prefix) => Some(prefix),
+            None if self.is_cross_compiling() => Some(self.arch.default_cross_compile()),
+            None => None,
+        }
+    }
+
+    /// Whether the system is staged into a root other than `/` (`--target-root`).
+    pub fn is_staged(&self) -> bool {
+        self.target_root != Path::new("/")
+    }
+
+    /// Resolves an absolute system path (e.g. `/var/lib/dkms`) inside the target root.
+    pub fn target_path(&self, system_path: &str) -> PathBuf {
+        self.target_root.join(system_path.trim_start_matches('/'))
+    }
+
+    /// Converts a path under the target root to the path seen from inside it, so links
+    /// stay valid once the root is booted. Paths outside the root are returned unchanged.
+    pub fn path_in_target(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.target_root) {
+            Ok(relative) => Path::new("/").join(relative),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+
+    /// Command line running a system tool for the target: directly, or through
+    /// `arch-chroot` when staging into another root.
+    pub fn target_command(&self, command: &str, args: &[&str]) -> Vec<String> {
+        let mut command_line = Vec::with_capacity(args.len() + 3);
+        if self.is_staged() {
+            command_line.push("arch-chroot".to_string());
+            command_line.push(self.target_root.display().to_string());
+        }
+        command_line.push(command.to_string());
+        command_line.extend(args.iter().map(|arg| arg.to_string()));
+        command_line
+    }
+
+    /// Where `--install-headers` puts the pruned headers tree of the new kernel.
+    pub fn headers_install_path(&self) -> PathBuf {
+        self.target_path(&format!("usr/src/linux-{}", self.kernel_ident_name_new))
+    }
+
+    /// Whether build artifacts are kept outside the source tree (`make O=`).
+    pub fn is_out_of_tree(&self) -> bool {
+        self.kernel_build_dir_path != self.kernel_src_dir_path
+    }
+
     /// Checks if a given command (or the default pipeline) requires the old kernel version argument.
     pub fn requires_old(command: &Option<Commands>) -> bool {
         matches!(command, Some(Commands::DkmsInstall) | None)
@@ -38,32 +109,48 @@ impl Config {
     /// Creates a new `Config` instance from the parsed `Arguments`.
     ///
     /// Performs validation:
-    /// 1. If `--old` is provided, validates that `--new > --old`.
-    /// 2. If the command requires `--old` (dkms-install or default), validates that `--old` is provided.
+    /// 1. Validates that `--new` is provided.
+    /// 2. If `--old` is provided, validates that `--new > --old`.
+    /// 3. If the command requires `--old` (dkms-install or default), validates that `--old` is provided.
     ///
     /// Returns `KernelUpdaterError` on failure.
     pub fn new(args: Arguments) -> Result<Self, KernelUpdaterError> {
-        let kernel_version_major = args.new.major;
+        // --- Validation 1: Every command working on a kernel needs --new ---
+        let Some(version_new) = args.new else {
+            return Err(KernelUpdaterError::MissingRequiredArgument {
+                argument_name: "--new".to_string(),
+                command: args.command,
+            });
+        };
+
+        let kernel_version_major = version_new.major;
         let kernel_url_base = format!(
             "https://cdn.kernel.org/pub/linux/kernel/v{}.x",
             kernel_version_major
         );
-        let kernel_src_base = PathBuf::from("/lib/modules");
-        let kernel_module_base = PathBuf::from("/lib/modules");
-        let kernel_config_base = PathBuf::from("/lib/modules");
+        // Every system path is derived from the target root ("/" unless staging elsewhere)
+        let target_root = args
+            .build
+            .target_root
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("/"));
+        let kernel_src_base = target_root.join("lib/modules");
+        let kernel_module_base = target_root.join("lib/modules");
+        let kernel_config_base = target_root.join("lib/modules");
+        let boot_dir = target_root.join("boot");
         let custom_kernel_suffix = args.suffix;
 
-        // --- Validation 1: If old version is provided, new MUST be strictly greater ---
+        // --- Validation 2: If old version is provided, new MUST be strictly greater ---
         if let Some(ref old_version) = args.old
-            && args.new <= *old_version
+            && version_new <= *old_version
         {
             return Err(KernelUpdaterError::VersionComparisonError {
-                new: args.new.clone(),
+                new: version_new.clone(),
                 old: old_version.clone(),
             });
         }
 
-        // --- Validation 2: Check if --old is required by the command ---
+        // --- Validation 3: Check if --old is required by the command ---
         if Self::requires_old(&args.command) && args.old.is_none() {
             return Err(KernelUpdaterError::MissingRequiredArgument {
                 argument_name: "--old".to_string(),
@@ -74,37 +161,58 @@ impl Config {
         // --- Calculate Derived Paths and Names (Only reached if all validation passes) ---
         let config_file_path = kernel_config_base.join(format!("config-{}", custom_kernel_suffix));
 
-        let kernel_src_dir_name = if args.new.is_major_point_release() {
-            format!("linux-{}", args.new.major_minor())
+        let kernel_src_dir_name = if version_new.is_major_point_release() {
+            format!("linux-{}", version_new.major_minor())
         } else {
-            format!("linux-{}", args.new)
+            format!("linux-{}", version_new)
         };
 
         let kernel_src_dir_path = kernel_src_base.join(&kernel_src_dir_name);
 
-        let tarball_name = if args.new.is_major_point_release() {
-            format!("linux-{}.tar.xz", args.new.major_minor())
+        let tarball_name = if version_new.is_major_point_release() {
+            format!("linux-{}.tar.xz", version_new.major_minor())
         } else {
-            format!("linux-{}.tar.xz", args.new)
+            format!("linux-{}.tar.xz", version_new)
         };
 
         let download_link = format!("{}/{}", kernel_url_base, tarball_name);
 
-        let kernel_ident_name_new = format!("{}-{}", args.new, custom_kernel_suffix);
+        let kernel_ident_name_new = format!("{}-{}", version_new, custom_kernel_suffix);
 
         let kernel_ident_name_old = args
             .old
             .as_ref()
             .map(|v| format!("{}-{}", v, custom_kernel_suffix));
 
-        let vmlinuz_install_path =
-            PathBuf::from("/boot").join(format!("vmlinuz-{}", args.new.major_minor()));
+        // Out-of-tree builds get one output directory per kernel ident; a bundle brings
+        // its own headers tree, used by DKMS and module signing
+        let kernel_build_dir_path = match (&args.command, &args.build.build_dir) {
+            (
+                Some(Commands::KernelInstall {
+                    from_bundle: Some(_),
+                }),
+                _,
+            ) => kernel_module_base
+                .join(&kernel_ident_name_new)
+                .join("build"),
+            (_, Some(build_dir)) => build_dir.join(&kernel_ident_name_new),
+            (_, None) => kernel_src_dir_path.clone(),
+        };
+
+        let vmlinuz_install_path = boot_dir.join(format!("vmlinuz-{}", version_new.major_minor()));
+
+        // Build for the host unless another architecture is requested
+        let arch = args.build.arch.or_else(Arch::host).unwrap_or(Arch::X86_64);
+        let dtbs_install_path = arch
+            .has_device_trees()
+            .then(|| boot_dir.join("dtbs").join(&kernel_ident_name_new));
 
         Ok(Self {
             version_old: args.old,
-            version_new: args.new,
+            version_new,
             command: args.command,
             kernel_url_base,
+            target_root,
             kernel_src_base,
             kernel_module_base,
             kernel_config_base,
@@ -112,32 +220,109 @@ impl Config {
             config_file_path,
             kernel_src_dir_name,
             kernel_src_dir_path,
+            kernel_build_dir_path,
             tarball_name,
             download_link,
             kernel_ident_name_new,
             kernel_ident_name_old,
             vmlinuz_install_path,
+            arch,
+            dtbs_install_path,
             downloader: args.downloader,
+            build: args.build,
+            dkms: args.dkms,
+            output: args.output,
         })
     }
 
     /// Show summary information
     pub fn show_summary(&self) {
-        println!("Running with configuration:");
+        info!("Running with configuration:");
         if let Some(old) = &self.version_old {
-            println!("  Old version: {:?}", old);
+            info!("  Old version: {:?}", old);
         }
-        println!("  New version: {:?}", self.version_new);
-        println!("  Command: {:?}\n", self.command);
+        info!("  New version: {:?}", self.version_new);
+        info!("  Command: {:?}\n", self.command);
 
-        println!("  Downloader: {:?}", self.downloader);
-        println!("  Kernel Source Base: {}", self.kernel_src_base.display());
-        println!("  Custom Suffix: {}", self.custom_kernel_suffix);
-        println!("  New Kernel Ident: {}", self.kernel_ident_name_new);
+        info!("  Downloader: {:?}", self.downloader);
+        info!("  Kernel Source Base: {}", self.kernel_src_base.display());
+        info!("  Custom Suffix: {}", self.custom_kernel_suffix);
+        info!("  New Kernel Ident: {}", self.kernel_ident_name_new);
         if let Some(old_ident) = &self.kernel_ident_name_old {
-            println!("  Old Kernel Ident: {}", old_ident);
+            info!("  Old Kernel Ident: {}", old_ident);
+        }
+        if let Some(profile) = &self.build.config_profile {
+            info!("  Config Profile: {profile}");
+        }
+        for fragment in &self.build.config_fragments {
+            info!("  Config Fragment: {}", fragment.display());
+        }
+        if let Some(seed) = &self.build.seed_config {
+            info!("  Seed Config: {seed:?}");
+        }
+        if let Some(lsmod_file) = &self.build.localmodconfig {
+            info!("  Localmodconfig Snapshot: {}", lsmod_file.display());
+        }
+        if self.is_out_of_tree() {
+            info!(
+                "  Build Directory: {}",
+                self.kernel_build_dir_path.display()
+            );
+        }
+        match self.cross_compile_prefix() {
+            Some(prefix) => info!(
+                "  Architecture: {} (cross-compiling, ARCH={} CROSS_COMPILE={prefix})",
+                self.arch,
+                self.arch.kernel_arch()
+            ),
+            None => info!("  Architecture: {}", self.arch),
+        }
+        if self.is_staged() {
+            info!("  Target Root: {}", self.target_root.display());
+        }
+        info!(
+            "  Boot Image: {}",
+            self.arch.image_candidates().join(" or ")
+        );
+        if let Some(dtbs_path) = &self.dtbs_install_path {
+            info!("  Device Trees: {}", dtbs_path.display());
+        }
+        info!("  Toolchain: {}", Toolchain::new(&self.build).describe());
+        if let Some(jobs) = self.build.jobs {
+            info!("  Jobs: {jobs}");
+        }
+        if let Some(load) = self.build.load_average {
+            info!("  Load Average Limit: {load}");
+        }
+        if let Some(niceness) = self.build.nice {
+            info!("  Niceness: {niceness}");
         }
-        println!();
+        if let Some(class) = &self.build.ionice {
+            info!("  I/O Scheduling Class: {class:?}");
+        }
+        if self.build.install_headers {
+            info!(
+                "  Kernel Headers: {}",
+                self.headers_install_path().display()
+            );
+        }
+        if let Some(editor) = &self.build.edit_config {
+            info!("  Edit Config: {}", editor.make_target());
+        }
+        if self.build.save_config {
+            info!("  Save Config Back: {}", self.config_file_path.display());
+        }
+        if !self.dkms.modules.is_empty() {
+            let modules: Vec<String> = self.dkms.modules.iter().map(|m| m.to_string()).collect();
+            info!("  DKMS Modules: {}", modules.join(", "));
+        }
+        if self.dkms.sign_modules {
+            match &self.dkms.mok_key {
+                Some(key) => info!("  Module Signing: MOK key {}", key.display()),
+                None => info!("  Module Signing: kernel build key"),
+            }
+        }
+        info!();
     }
 }
 
@@ -151,7 +336,7 @@ impl Config {
 mod tests_config {
     use super::*;
     use crate::Version;
-    use crate::args::{Arguments, Commands, Downloader};
+    use crate::args::{Arguments, BuildArgs, Commands, DkmsArgs, Downloader, OutputArgs};
     use std::str::FromStr;
 
     // Helper to create Version, includes panic on parse error for simplicity in test setup
@@ -167,8 +352,11 @@ mod tests_config {
             downloader: Downloader::Curl, // Use a default value
             suffix: "ClaudioFSR".to_string(),
             old: old_version,
-            new: new_version,
+            new: Some(new_version),
             command,
+            build: BuildArgs::default(),
+            dkms: DkmsArgs::default(),
+            output: OutputArgs::default(),
         }
     }
 
@@ -177,10 +365,10 @@ mod tests_config {
     fn expected_config_valid(old: Option<&str>, new: &str, command: Option<Commands>) -> Config {
         let args = create_test_args(old, new, command); // Create corresponding args
         let version_old_val = args.old;
-        let version_new_val = args.new.clone();
+        let version_new_val = args.new.clone().unwrap();
 
         let custom_kernel_suffix = "ClaudioFSR".to_string();
-        let kernel_version_major = args.new.major;
+        let kernel_version_major = version_new_val.major;
         let kernel_url_base = format!(
             "https://cdn.kernel.org/pub/linux/kernel/v{}.x",
             kernel_version_major
@@ -191,23 +379,26 @@ mod tests_config {
 
         let config_file_path = kernel_config_base.join(format!("config-{}", custom_kernel_suffix));
 
-        let kernel_src_dir_name = if args.new.patch == 0 {
-            format!("linux-{}.{}", args.new.major, args.new.minor)
+        let kernel_src_dir_name = if version_new_val.patch == 0 {
+            format!("linux-{}.{}", version_new_val.major, version_new_val.minor)
         } else {
-            format!("linux-{}", args.new)
+            format!("linux-{}", version_new_val)
         };
 
         let kernel_src_dir_path = kernel_src_base.join(&kernel_src_dir_name);
 
-        let tarball_name = if args.new.patch == 0 {
-            format!("linux-{}.{}.tar.xz", args.new.major, args.new.minor)
+        let tarball_name = if version_new_val.patch == 0 {
+            format!(
+                "linux-{}.{}.tar.xz",
+                version_new_val.major, version_new_val.minor
+            )
         } else {
-            format!("linux-{}.tar.xz", args.new)
+            format!("linux-{}.tar.xz", version_new_val)
         };
 
         let download_link = format!("{}/{}", kernel_url_base, tarball_name);
 
-        let kernel_ident_name_new = format!("{}-{}", args.new, custom_kernel_suffix);
+        let kernel_ident_name_new = format!("{}-{}", version_new_val, custom_kernel_suffix);
 
         let kernel_ident_name_old = version_old_val
             .as_ref()
@@ -216,25 +407,36 @@ mod tests_config {
             "vmlinuz-{}.{}",
             version_new_val.major, version_new_val.minor
         ));
+        let arch = Arch::host().unwrap_or(Arch::X86_64);
+        let dtbs_install_path = arch
+            .has_device_trees()
+            .then(|| PathBuf::from("/boot/dtbs").join(&kernel_ident_name_new));
 
         Config {
             version_old: version_old_val,
             version_new: version_new_val,
             command: args.command.clone(),
             kernel_url_base,
+            target_root: PathBuf::from("/"),
             kernel_src_base,
             kernel_module_base,
             kernel_config_base,
             custom_kernel_suffix,
             config_file_path,
             kernel_src_dir_name,
-            kernel_src_dir_path,
+            kernel_src_dir_path: kernel_src_dir_path.clone(),
+            kernel_build_dir_path: kernel_src_dir_path,
             tarball_name,
             download_link,
             kernel_ident_name_new,
             kernel_ident_name_old,
             vmlinuz_install_path,
+            arch,
+            dtbs_install_path,
             downloader: args.downloader,
+            build: args.build,
+            dkms: args.dkms,
+            output: args.output,
         }
     }
 
@@ -278,21 +480,36 @@ mod tests_config {
 
     #[test]
     fn test_config_new_kernel_install_valid_no_old() {
-        let args = create_test_args(None, "6.14.4", Some(Commands::KernelInstall));
+        let args = create_test_args(
+            None,
+            "6.14.4",
+            Some(Commands::KernelInstall { from_bundle: None }),
+        );
         let config = Config::new(args.clone())
             .expect("Config::new should succeed for kernel-install args without old");
-        let expected = expected_config_valid(None, "6.14.4", Some(Commands::KernelInstall));
+        let expected = expected_config_valid(
+            None,
+            "6.14.4",
+            Some(Commands::KernelInstall { from_bundle: None }),
+        );
         assert_eq!(config, expected);
     }
 
     #[test]
     fn test_config_new_kernel_install_valid_with_old() {
         // Providing a valid old version should not fail config creation for this command
-        let args = create_test_args(Some("6.14.3"), "6.14.4", Some(Commands::KernelInstall));
+        let args = create_test_args(
+            Some("6.14.3"),
+            "6.14.4",
+            Some(Commands::KernelInstall { from_bundle: None }),
+        );
         let config = Config::new(args.clone())
             .expect("Config::new should succeed for kernel-install args with valid old");
-        let expected =
-            expected_config_valid(Some("6.14.3"), "6.14.4", Some(Commands::KernelInstall));
+        let expected = expected_config_valid(
+            Some("6.14.3"),
+            "6.14.4",
+            Some(Commands::KernelInstall { from_bundle: None }),
+        );
         assert_eq!(config, expected);
     }
 
@@ -309,6 +526,79 @@ mod tests_config {
         assert_eq!(config_default.downloader, Downloader::Curl); // Assuming Curl is Default in Args struct
     }
 
+    #[test]
+    fn test_config_build_dir_per_ident() {
+        let mut args = create_test_args(None, "6.15.4", Some(Commands::KernelCompile));
+        args.build.build_dir = Some(PathBuf::from("/mnt/ssd/kbuild"));
+        let config = Config::new(args).expect("Config::new should handle --build-dir");
+        assert!(config.is_out_of_tree());
+        assert_eq!(
+            config.kernel_build_dir_path,
+            PathBuf::from("/mnt/ssd/kbuild/6.15.4-ClaudioFSR")
+        );
+        assert_eq!(
+            config.kernel_src_dir_path,
+            PathBuf::from("/lib/modules/linux-6.15.4")
+        );
+    }
+
+    #[test]
+    fn test_config_target_arch() {
+        let mut args = create_test_args(None, "6.15.4", Some(Commands::KernelCompile));
+        args.build.arch = Some(Arch::Arm64);
+        let config = Config::new(args).expect("Config::new should handle --arch");
+
+        assert_eq!(config.arch, Arch::Arm64);
+        assert_eq!(
+            config.dtbs_install_path,
+            Some(PathBuf::from("/boot/dtbs/6.15.4-ClaudioFSR"))
+        );
+        assert_eq!(
+            config.is_cross_compiling(),
+            Arch::host() != Some(Arch::Arm64)
+        );
+    }
+
+    #[test]
+    fn test_config_target_root() {
+        let mut args = create_test_args(Some("6.15.3"), "6.15.4", None);
+        args.build.target_root = Some(PathBuf::from("/mnt/board"));
+        let config = Config::new(args).expect("Config::new should handle --target-root");
+
+        assert!(config.is_staged());
+        assert_eq!(
+            config.kernel_src_dir_path,
+            PathBuf::from("/mnt/board/lib/modules/linux-6.15.4")
+        );
+        assert_eq!(
+            config.config_file_path,
+            PathBuf::from("/mnt/board/lib/modules/config-ClaudioFSR")
+        );
+        assert_eq!(
+            config.vmlinuz_install_path,
+            PathBuf::from("/mnt/board/boot/vmlinuz-6.15")
+        );
+        assert_eq!(
+            config.target_path("/var/lib/dkms"),
+            PathBuf::from("/mnt/board/var/lib/dkms")
+        );
+        assert_eq!(
+            config.path_in_target(&config.kernel_src_dir_path),
+            PathBuf::from("/lib/modules/linux-6.15.4")
+        );
+        assert_eq!(
+            config.target_command("mkinitcpio", &["-P"]),
+            vec!["arch-chroot", "/mnt/board", "mkinitcpio", "-P"]
+        );
+
+        let config = Config::new(create_test_args(Some("6.15.3"), "6.15.4", None)).unwrap();
+        assert!(!config.is_staged());
+        assert_eq!(
+            config.target_command("update-grub", &[]),
+            vec!["update-grub"]
+        );
+    }
+
     // --- Validation Failure Tests (checking for specific KernelUpdaterError variants) ---
 
     #[test]
@@ -336,6 +626,19 @@ mod tests_config {
         println!("Received expected error: {:?}", err);
     }
 
+    #[test]
+    fn test_config_new_missing_new_invalid() {
+        let mut args = create_test_args(None, "6.14.4", Some(Commands::KernelCompile));
+        args.new = None; // Missing --new, only `history` works without it
+        let result = Config::new(args);
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert!(
+            matches!(&err, KernelUpdaterError::MissingRequiredArgument { argument_name, command } if argument_name == "--new" && *command == Some(Commands::KernelCompile))
+        );
+        println!("Received expected error: {:?}", err);
+    }
+
     #[test]
     fn test_config_new_default_new_eq_old_invalid() {
         let old = "6.14.4";
@@ -427,7 +730,11 @@ mod tests_config {
     fn test_config_new_kernel_install_new_eq_old_invalid() {
         let old = "6.14.4";
         let new = "6.14.4";
-        let args = create_test_args(Some(old), new, Some(Commands::KernelInstall)); // new == old, Kernel Install
+        let args = create_test_args(
+            Some(old),
+            new,
+            Some(Commands::KernelInstall { from_bundle: None }),
+        ); // new == old, Kernel Install
         let result = Config::new(args);
         assert!(result.is_err());
         let err = result.unwrap_err();
@@ -441,7 +748,11 @@ mod tests_config {
     fn test_config_new_kernel_install_new_lt_old_invalid() {
         let old = "6.15.0";
         let new = "6.14.4";
-        let args = create_test_args(Some(old), new, Some(Commands::KernelInstall)); // new < old, Kernel Install
+        let args = create_test_args(
+            Some(old),
+            new,
+            Some(Commands::KernelInstall { from_bundle: None }),
+        ); // new < old, Kernel Install
         let result = Config::new(args);
         assert!(result.is_err());
         let err = result.unwrap_err();
@@ -1,8 +1,142 @@
 use crate::{
-    Config,
+    AtomicWriteExt, Config, ModuleSigner, Toolchain,
+    dkms_conf::{DkmsCompatibility, DkmsConf},
     error::KernelUpdaterError,
-    utils::{run_command, run_command_output},
+    info,
+    kconfig::KernelConfig,
+    logging::run_log_path,
+    report::{self, DkmsModuleVersion},
+    utils::{run_command, run_command_line, run_command_line_output, run_command_with_env},
+    warn,
 };
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Modules managed when no `--dkms-module` is given on the command line.
+const DEFAULT_MODULES: [&str; 2] = ["nvidia", "v4l2loopback"];
+
+/// Special module name selecting every module registered in `dkms status`.
+const ALL_MODULES: &str = "all";
+
+/// Directory holding the registered DKMS module sources (`<name>-<version>/dkms.conf`).
+const DKMS_SOURCE_TREE: &str = "/usr/src";
+
+/// DKMS working tree holding build directories and logs (`<name>/<version>/...`).
+const DKMS_TREE: &str = "/var/lib/dkms";
+
+/// Number of log lines shown around the first compiler error of a failed build.
+const LOG_CONTEXT_BEFORE: usize = 3;
+const LOG_CONTEXT_AFTER: usize = 10;
+
+/// Locates the `make.log` written by DKMS for a module build.
+///
+/// Checks both the per-kernel log directory of recent dkms 3.x releases
+/// (`<name>/<version>/<kernel>/<arch>/log/make.log`) and the legacy shared build
+/// directory (`<name>/<version>/build/make.log`), preferring the most recent one.
+pub fn find_build_log(
+    dkms_tree: &Path,
+    module: &str,
+    version: &str,
+    kernel: &str,
+) -> Option<PathBuf> {
+    let module_dir = dkms_tree.join(module).join(version);
+    let candidates = [
+        module_dir
+            .join(kernel)
+            .join(std::env::consts::ARCH)
+            .join("log/make.log"),
+        module_dir.join("build/make.log"),
+    ];
+
+    candidates
+        .into_iter()
+        .filter_map(|path| {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+            Some((modified, path))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+/// Copies a DKMS build log next to the run log, as
+/// `run-<stamp>.dkms-<module>-<version>.make.log`, and returns the copy's path.
+fn archive_build_log(
+    log_path: &Path,
+    run_log: &Path,
+    module: &str,
+    version: &str,
+) -> Result<PathBuf, KernelUpdaterError> {
+    let archived = run_log.with_extension(format!("dkms-{module}-{version}.make.log"));
+    archived.atomic_copy_from(log_path)?;
+    Ok(archived)
+}
+
+/// Extracts the lines around the first compiler error of a build log,
+/// or its last lines when no error marker is found.
+pub fn extract_log_excerpt(log: &str) -> Vec<&str> {
+    let lines: Vec<&str> = log.lines().collect();
+    let first_error = lines
+        .iter()
+        .position(|line| line.contains("error:") || line.contains("Error:"));
+
+    let (start, end) = match first_error {
+        Some(index) => (
+            index.saturating_sub(LOG_CONTEXT_BEFORE),
+            (index + LOG_CONTEXT_AFTER + 1).min(lines.len()),
+        ),
+        None => (
+            lines
+                .len()
+                .saturating_sub(LOG_CONTEXT_BEFORE + LOG_CONTEXT_AFTER + 1),
+            lines.len(),
+        ),
+    };
+    lines[start..end].to_vec()
+}
+
+/// State of a module/kernel pair as reported by `dkms status`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DkmsStatus {
+    Added,
+    Built,
+    Installed,
+    /// Installed for another kernel and reused through weak-modules (`installed-weak from <kernel>`).
+    InstalledWeak,
+    Broken,
+    Unknown(String),
+}
+
+impl DkmsStatus {
+    /// Parses the status word printed after the colon of a `dkms status` line.
+    fn parse(word: &str) -> Self {
+        match word {
+            "added" => Self::Added,
+            "built" => Self::Built,
+            "installed" => Self::Installed,
+            "installed-weak" => Self::InstalledWeak,
+            "broken" => Self::Broken,
+            other => Self::Unknown(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for DkmsStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Added => write!(f, "added"),
+            Self::Built => write!(f, "built"),
+            Self::Installed => write!(f, "installed"),
+            Self::InstalledWeak => write!(f, "installed-weak"),
+            Self::Broken => write!(f, "broken"),
+            Self::Unknown(other) => write!(f, "{other}"),
+        }
+    }
+}
 
 /// Representation of a parsed DKMS module status entry.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -11,32 +145,164 @@ pub struct DkmsEntry {
     pub module_version: String,
     pub kernel_version: String,
     pub architecture: String,
-    pub status: String,
+    pub status: DkmsStatus,
+    /// Parenthesized remarks such as "WARNING! Diff between built and installed module!".
+    pub warnings: Vec<String>,
+}
+
+/// A DKMS module requested on the command line, in the form `name[@version][:required|:optional]`.
+///
+/// A failure of a required module aborts the run, while an optional one only warns.
+/// Without a pinned `@version`, the newest registered version is selected.
+/// The name `all` selects every module registered in `dkms status`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DkmsModuleSpec {
+    pub name: String,
+    pub version: Option<String>,
+    pub required: bool,
+}
+
+impl DkmsModuleSpec {
+    /// Creates a required module specification selecting the newest version.
+    pub fn required(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            version: None,
+            required: true,
+        }
+    }
+
+    /// Checks if this specification selects every registered module.
+    pub fn is_all(&self) -> bool {
+        self.name == ALL_MODULES
+    }
+}
+
+impl FromStr for DkmsModuleSpec {
+    type Err = KernelUpdaterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, required) = match s.trim().split_once(':') {
+            Some((name, "required")) => (name, true),
+            Some((name, "optional")) => (name, false),
+            Some(_) => {
+                return Err(KernelUpdaterError::DkmsModuleSpecParseError {
+                    input: s.to_string(),
+                });
+            }
+            None => (s.trim(), true),
+        };
+
+        let (name, version) = match name.split_once('@') {
+            Some((name, version)) => (name, Some(version)),
+            None => (name, None),
+        };
+
+        if name.is_empty() || name.contains('/') || version.is_some_and(str::is_empty) {
+            return Err(KernelUpdaterError::DkmsModuleSpecParseError {
+                input: s.to_string(),
+            });
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            version: version.map(str::to_string),
+            required,
+        })
+    }
+}
+
+impl fmt::Display for DkmsModuleSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.required {
+            "required"
+        } else {
+            "optional"
+        };
+        match &self.version {
+            Some(version) => write!(f, "{}@{version}:{kind}", self.name),
+            None => write!(f, "{}:{kind}", self.name),
+        }
+    }
+}
+
+/// Compares two DKMS module versions chunk by chunk, numerically where possible
+/// (so that `550.100` is newer than `550.40.07`).
+pub fn compare_module_versions(a: &str, b: &str) -> Ordering {
+    fn chunks(version: &str) -> Vec<&str> {
+        version
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|chunk| !chunk.is_empty())
+            .collect()
+    }
+
+    let (chunks_a, chunks_b) = (chunks(a), chunks(b));
+    for (x, y) in chunks_a.iter().zip(chunks_b.iter()) {
+        let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => x.cmp(y),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    chunks_a.len().cmp(&chunks_b.len())
+}
+
+/// Outcome of installing a single DKMS module for the new kernel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DkmsOutcome {
+    Installed { version: String },
+    NotRegistered,
+    Failed { reason: String },
+}
+
+/// Per-module result reported at the end of `DkmsManager::install_modules`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DkmsModuleReport {
+    pub module: DkmsModuleSpec,
+    pub outcome: DkmsOutcome,
 }
 
 /// Object-oriented manager for executing actions over multiple DKMS modules.
 pub struct DkmsManager<'a> {
     config: &'a Config,
-    target_modules: Vec<&'static str>,
+    target_modules: Vec<DkmsModuleSpec>,
 }
 
 impl<'a> DkmsManager<'a> {
-    /// Instantiates a `DkmsManager` with default target modules.
+    /// Instantiates a `DkmsManager` targeting the modules selected in `Config`,
+    /// falling back to the default modules when none were given.
     pub fn new(config: &'a Config) -> Self {
+        let target_modules = if config.dkms.modules.is_empty() {
+            DEFAULT_MODULES
+                .iter()
+                .map(|name| DkmsModuleSpec::required(name))
+                .collect()
+        } else {
+            config.dkms.modules.clone()
+        };
+
         Self {
             config,
-            target_modules: vec!["nvidia", "v4l2loopback"],
+            target_modules,
         }
     }
 
     /// Queries the operational system via `dkms status` and parses the response.
     pub fn get_installed_modules(&self) -> Result<Vec<DkmsEntry>, KernelUpdaterError> {
-        println!("Querying current DKMS module statuses...");
-        let dkms_output = run_command_output("dkms", &["status"])?;
-        Ok(Self::parse_status_output(&dkms_output))
+        info!("Querying current DKMS module statuses...");
+        let dkms_output =
+            run_command_line_output(&self.config.target_command("dkms", &["status"]))?;
+        let registered = Self::parse_status_output(&dkms_output);
+        Self::report_registry_warnings(&registered);
+        Ok(registered)
     }
 
     /// Parses the raw string output of the `dkms status` command.
+    ///
+    /// Accepts both the dkms 3.x format (`name/version, kernel, arch: status`) and the
+    /// older dkms 2.x format (`name, version, kernel, arch: status`).
     fn parse_status_output(output: &str) -> Vec<DkmsEntry> {
         output
             .lines()
@@ -45,76 +311,455 @@ impl<'a> DkmsManager<'a> {
             .filter_map(|line| {
                 // Expected format e.g.:
                 // nvidia/610.43.03, 7.1.4-1-MANJARO, x86_64: installed
+                // nvidia, 550.40.01, 6.12.0-1-MANJARO, x86_64: installed (WARNING! Diff between built and installed module!)
                 let (left_side, status) = line.split_once(':')?;
-                let status = status.trim().to_string();
+                let (status, warnings) = Self::parse_status_field(status);
 
-                let mut comma_parts = left_side.split(',');
-                let mod_ver_part = comma_parts.next()?.trim();
+                let mut comma_parts = left_side.split(',').map(str::trim);
+                let first_part = comma_parts.next()?;
 
-                let (module_name, module_version) = mod_ver_part.split_once('/')?;
-                let module_name = module_name.trim().to_string();
-                let module_version = module_version.trim().to_string();
+                let (module_name, module_version) = match first_part.split_once('/') {
+                    Some((name, version)) => (name.trim(), version.trim()),
+                    // dkms 2.x: the version is the second comma-separated field
+                    None => (first_part, comma_parts.next()?),
+                };
 
                 // Safety check: a valid module version should not contain further directories or slashes
-                if module_version.contains('/') {
+                if module_name.is_empty()
+                    || module_version.is_empty()
+                    || module_version.contains('/')
+                {
                     return None;
                 }
 
-                let kernel_version = comma_parts
-                    .next()
-                    .map_or(String::new(), |s| s.trim().to_string());
-                let architecture = comma_parts
-                    .next()
-                    .map_or(String::new(), |s| s.trim().to_string());
+                let kernel_version = comma_parts.next().unwrap_or_default().to_string();
+                let architecture = comma_parts.next().unwrap_or_default().to_string();
 
                 Some(DkmsEntry {
-                    module_name,
-                    module_version,
+                    module_name: module_name.to_string(),
+                    module_version: module_version.to_string(),
                     kernel_version,
                     architecture,
                     status,
+                    warnings,
                 })
             })
             .collect()
     }
 
+    /// Splits the status field into the typed status and its parenthesized warnings.
+    fn parse_status_field(field: &str) -> (DkmsStatus, Vec<String>) {
+        let field = field.trim();
+        let (head, remarks) = match field.find('(') {
+            Some(index) => field.split_at(index),
+            None => (field, ""),
+        };
+
+        let status = DkmsStatus::parse(head.split_whitespace().next().unwrap_or_default());
+        let warnings = remarks
+            .split(['(', ')'])
+            .map(str::trim)
+            .filter(|remark| !remark.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        (status, warnings)
+    }
+
+    /// Groups registry entries by module name, preserving every kernel each module is registered for.
+    pub fn group_by_module(registered: &[DkmsEntry]) -> BTreeMap<String, Vec<DkmsEntry>> {
+        let mut groups: BTreeMap<String, Vec<DkmsEntry>> = BTreeMap::new();
+        for entry in registered {
+            groups
+                .entry(entry.module_name.clone())
+                .or_default()
+                .push(entry.clone());
+        }
+        groups
+    }
+
+    /// Prints broken entries and DKMS warnings so they are not silently ignored.
+    fn report_registry_warnings(registered: &[DkmsEntry]) {
+        for entry in registered {
+            let location = format!(
+                "{}/{} ({})",
+                entry.module_name, entry.module_version, entry.kernel_version
+            );
+            if entry.status == DkmsStatus::Broken {
+                warn!("DKMS reports module {location} as broken.");
+            }
+            for warning in &entry.warnings {
+                warn!("DKMS module {location}: {warning}");
+            }
+        }
+    }
+
     /// Checks if a module with the specified name is currently registered/installed in the system.
     pub fn is_module_installed(&self, name: &str) -> Result<bool, KernelUpdaterError> {
         let registered = self.get_installed_modules()?;
         Ok(registered.iter().any(|entry| entry.module_name == name))
     }
 
-    /// Searches active version for a target module in the parsed registry.
+    /// Selects the version of a target module to act on from the parsed registry.
+    ///
+    /// A version pinned with `name@version` is used if registered; otherwise the newest
+    /// registered version wins, using a version-aware comparison.
     fn find_installed_version(&self, name: &str, registered: &[DkmsEntry]) -> Option<String> {
-        registered
+        let groups = Self::group_by_module(registered);
+        let entries = groups.get(name)?;
+
+        let pinned = self
+            .target_modules
             .iter()
-            .find(|entry| entry.module_name == name)
-            .map(|entry| entry.module_version.clone())
+            .find(|spec| spec.name == name)
+            .and_then(|spec| spec.version.as_ref());
+
+        match pinned {
+            Some(version) => {
+                let found = entries.iter().any(|entry| &entry.module_version == version);
+                if !found {
+                    warn!("Pinned version '{name}/{version}' is not registered in DKMS.");
+                }
+                found.then(|| version.clone())
+            }
+            None => entries
+                .iter()
+                .map(|entry| entry.module_version.as_str())
+                .max_by(|a, b| compare_module_versions(a, b))
+                .map(str::to_string),
+        }
+    }
+
+    /// Error for a required module pinned to a version that is not registered. Other modules
+    /// without a registered version are skipped.
+    fn missing_pin_error(target: &DkmsModuleSpec) -> Option<KernelUpdaterError> {
+        let version = target.version.as_ref().filter(|_| target.required)?;
+        Some(KernelUpdaterError::DkmsPinnedVersionNotRegistered {
+            module: target.name.clone(),
+            version: version.clone(),
+        })
+    }
+
+    /// Expands the `all` selection against the registry and removes duplicate module names.
+    fn resolve_targets(&self, registered: &[DkmsEntry]) -> Vec<DkmsModuleSpec> {
+        let mut resolved: Vec<DkmsModuleSpec> = Vec::new();
+
+        for spec in &self.target_modules {
+            let expanded = if spec.is_all() {
+                registered
+                    .iter()
+                    .map(|entry| DkmsModuleSpec {
+                        name: entry.module_name.clone(),
+                        version: None,
+                        required: spec.required,
+                    })
+                    .collect()
+            } else {
+                vec![spec.clone()]
+            };
+
+            for candidate in expanded {
+                if !resolved.iter().any(|known| known.name == candidate.name) {
+                    resolved.push(candidate);
+                }
+            }
+        }
+        resolved
     }
 
     /// Builds and installs target modules for the newly compiled kernel.
-    pub fn install_modules(&self) -> Result<(), KernelUpdaterError> {
+    ///
+    /// Every module is attempted; a summary is printed at the end. Failures of optional
+    /// modules only warn, while the first failure of a required module is returned.
+    pub fn install_modules(&self) -> Result<Vec<DkmsModuleReport>, KernelUpdaterError> {
         let registered = self.get_installed_modules()?;
+        self.install_registered_modules(&registered)
+    }
+
+    /// Builds and installs target modules against an already queried DKMS registry.
+    fn install_registered_modules(
+        &self,
+        registered: &[DkmsEntry],
+    ) -> Result<Vec<DkmsModuleReport>, KernelUpdaterError> {
         let kernel_name_new = &self.config.kernel_ident_name_new;
 
-        for target in &self.target_modules {
-            if let Some(version) = self.find_installed_version(target, &registered) {
-                println!(
-                    "Installing DKMS module '{target}' version '{version}' for kernel {kernel_name_new}..."
+        let mut reports = Vec::new();
+        let mut first_required_error = None;
+
+        for target in self.resolve_targets(registered) {
+            let name = &target.name;
+            let outcome = match self.find_installed_version(name, registered) {
+                Some(version) => {
+                    info!(
+                        "Installing DKMS module '{name}' version '{version}' for kernel {kernel_name_new}..."
+                    );
+
+                    let module_spec = format!("{name}/{version}");
+                    let install_args = ["install", "--force", &module_spec, "-k", kernel_name_new];
+
+                    let toolchain_env = Toolchain::new(&self.config.build).dkms_environment();
+                    let install_command = self.config.target_command("dkms", &install_args);
+                    match run_command_line(&install_command, &toolchain_env) {
+                        Ok(()) => {
+                            info!(
+                                "DKMS module '{name}' installed successfully for {kernel_name_new}.\n"
+                            );
+                            DkmsOutcome::Installed { version }
+                        }
+                        Err(err) => {
+                            let dkms_tree = self.config.target_path(DKMS_TREE);
+                            let run_log = run_log_path();
+                            let err = self.build_failure(
+                                &dkms_tree,
+                                name,
+                                &version,
+                                run_log.as_deref(),
+                                err,
+                            );
+                            let reason = err.to_string();
+                            if target.required {
+                                first_required_error.get_or_insert(err);
+                            } else {
+                                warn!("Optional DKMS module '{name}' failed: {reason}");
+                            }
+                            DkmsOutcome::Failed { reason }
+                        }
+                    }
+                }
+                None => match Self::missing_pin_error(&target) {
+                    Some(err) => {
+                        let reason = err.to_string();
+                        first_required_error.get_or_insert(err);
+                        DkmsOutcome::Failed { reason }
+                    }
+                    None => {
+                        warn!("Module '{name}' is not registered on system. Skipping build.");
+                        DkmsOutcome::NotRegistered
+                    }
+                },
+            };
+
+            reports.push(DkmsModuleReport {
+                module: target,
+                outcome,
+            });
+        }
+
+        Self::print_summary(&reports);
+        report::record(|report| {
+            report.dkms = reports.iter().map(DkmsModuleVersion::from).collect()
+        });
+
+        if let Some(err) = first_required_error {
+            return Err(err);
+        }
+
+        if self.config.dkms.sign_modules {
+            ModuleSigner::new(self.config).sign_dkms_modules()?;
+        }
+        Ok(reports)
+    }
+
+    /// Turns a failed DKMS command into `DkmsBuildFailed`, printing the relevant part of
+    /// the build log and archiving the log next to `run_log`, if any.
+    fn build_failure(
+        &self,
+        dkms_tree: &Path,
+        module: &str,
+        version: &str,
+        run_log: Option<&Path>,
+        source: KernelUpdaterError,
+    ) -> KernelUpdaterError {
+        let kernel = &self.config.kernel_ident_name_new;
+        let log_path = find_build_log(dkms_tree, module, version, kernel);
+
+        let log_path = log_path.map(|path| {
+            if let Ok(content) = fs::read_to_string(&path) {
+                let excerpt: Vec<String> = extract_log_excerpt(&content)
+                    .iter()
+                    .map(|line| format!("  {line}"))
+                    .collect();
+                warn!(
+                    "Excerpt of {}:\n{}\n--- End of excerpt ---",
+                    path.display(),
+                    excerpt.join("\n")
                 );
+            }
 
-                let module_spec = format!("{target}/{version}");
-                let install_args = ["install", "--force", &module_spec, "-k", kernel_name_new];
+            // Without a run log the build log is referenced where DKMS left it
+            let Some(run_log) = run_log else {
+                return path;
+            };
+            match archive_build_log(&path, run_log, module, version) {
+                Ok(archived) => {
+                    info!("DKMS build log archived to {}", archived.display());
+                    archived
+                }
+                Err(err) => {
+                    warn!("Could not archive DKMS build log: {err}");
+                    path
+                }
+            }
+        });
+
+        KernelUpdaterError::DkmsBuildFailed {
+            module: module.to_string(),
+            version: version.to_string(),
+            kernel: kernel.clone(),
+            log_path,
+            source: Box::new(source),
+        }
+    }
+
+    /// Prints one line per module with the outcome of `install_modules`.
+    fn print_summary(reports: &[DkmsModuleReport]) {
+        info!("DKMS module summary:");
+        for report in reports {
+            let status = match &report.outcome {
+                DkmsOutcome::Installed { version } => format!("installed ({version})"),
+                DkmsOutcome::NotRegistered => "skipped (not registered)".to_string(),
+                DkmsOutcome::Failed { reason } => format!("FAILED: {reason}"),
+            };
+            info!("  {:<24} {status}", report.module.to_string());
+        }
+    }
+
+    /// Checks, before the kernel is installed, that every target module can build for it.
+    ///
+    /// Evaluates each module's `dkms.conf` build restrictions against the new kernel release and
+    /// its `.config`. With `--dkms-trial-build`, also builds the module into a scratch DKMS tree.
+    /// An incompatible required module aborts the run; an optional one only warns.
+    pub fn precheck_modules(&self) -> Result<(), KernelUpdaterError> {
+        let registered = self.get_installed_modules()?;
+        let kernel_name_new = &self.config.kernel_ident_name_new;
+        let kernel_config =
+            KernelConfig::from_file(&self.config.kernel_build_dir_path.join(".config"))?;
 
-                run_command("dkms", &install_args)?;
-                println!("DKMS module '{target}' installed successfully for {kernel_name_new}.\n");
+        for target in self.resolve_targets(&registered) {
+            let name = &target.name;
+            let Some(version) = self.find_installed_version(name, &registered) else {
+                if let Some(err) = Self::missing_pin_error(&target) {
+                    return Err(err);
+                }
+                warn!("Module '{name}' is not registered on system. Skipping check.");
+                continue;
+            };
+
+            let conf_path = self
+                .config
+                .target_path(DKMS_SOURCE_TREE)
+                .join(format!("{name}-{version}"))
+                .join("dkms.conf");
+
+            let compatibility = if conf_path.exists() {
+                DkmsConf::from_file(&conf_path)?.evaluate(kernel_name_new, &kernel_config)?
             } else {
-                println!("Warning: Module '{target}' is not registered on system. Skipping build.");
+                warn!(
+                    "{} not found. Skipping build restriction check for '{name}'.",
+                    conf_path.display()
+                );
+                DkmsCompatibility::Compatible {
+                    patches: Vec::new(),
+                }
+            };
+
+            let result = match compatibility {
+                DkmsCompatibility::Excluded { reason } => Err(reason),
+                DkmsCompatibility::Compatible { patches } => {
+                    if !patches.is_empty() {
+                        info!(
+                            "DKMS will apply patches to '{name}': {}",
+                            patches.join(", ")
+                        );
+                    }
+                    if self.config.dkms.trial_build {
+                        self.trial_build(name, &version)
+                            .map_err(|err| format!("trial build failed: {err}"))
+                    } else {
+                        Ok(())
+                    }
+                }
+            };
+
+            match result {
+                Ok(()) => info!(
+                    "DKMS module '{name}/{version}' is compatible with kernel {kernel_name_new}."
+                ),
+                Err(reason) if target.required => {
+                    return Err(KernelUpdaterError::DkmsModuleIncompatible {
+                        module: name.clone(),
+                        version,
+                        kernel: kernel_name_new.clone(),
+                        reason,
+                    });
+                }
+                Err(reason) => warn!(
+                    "Optional DKMS module '{name}/{version}' will not build for {kernel_name_new}: {reason}"
+                ),
             }
         }
         Ok(())
     }
 
+    /// Builds a module against the compiled (not yet installed) source tree inside a
+    /// throw-away DKMS tree, leaving the system registry untouched.
+    fn trial_build(&self, name: &str, version: &str) -> Result<(), KernelUpdaterError> {
+        let scratch_tree: PathBuf =
+            std::env::temp_dir().join(format!("kernel-updater-dkms-{}", std::process::id()));
+        fs::create_dir_all(&scratch_tree)?;
+
+        let scratch = scratch_tree.to_string_lossy();
+        let kernel_source = self.config.kernel_build_dir_path.to_string_lossy();
+        let kernel_name_new = &self.config.kernel_ident_name_new;
+        let source_tree_path = self.config.target_path(DKMS_SOURCE_TREE);
+        let source_tree = source_tree_path.to_string_lossy();
+
+        info!("Trial-building DKMS module '{name}/{version}' in {scratch}...");
+        let build_result = run_command(
+            "dkms",
+            &[
+                "add",
+                "-m",
+                name,
+                "-v",
+                version,
+                "--dkmstree",
+                &scratch,
+                "--sourcetree",
+                &source_tree,
+            ],
+        )
+        .and_then(|()| {
+            run_command_with_env(
+                "dkms",
+                &[
+                    "build",
+                    "-m",
+                    name,
+                    "-v",
+                    version,
+                    "-k",
+                    kernel_name_new,
+                    "--dkmstree",
+                    &scratch,
+                    "--sourcetree",
+                    &source_tree,
+                    "--kernelsourcedir",
+                    &kernel_source,
+                ],
+                &Toolchain::new(&self.config.build).dkms_environment(),
+            )
+        });
+
+        // The log must be rescued before the scratch tree is discarded
+        let result = build_result.map_err(|err| {
+            self.build_failure(&scratch_tree, name, version, run_log_path().as_deref(), err)
+        });
+
+        let _ = fs::remove_dir_all(&scratch_tree);
+        result
+    }
+
     /// Safely uninstalls target modules from the older kernel version.
     pub fn remove_modules(&self) -> Result<(), KernelUpdaterError> {
         let kernel_name_old = match &self.config.kernel_ident_name_old {
@@ -124,25 +769,39 @@ impl<'a> DkmsManager<'a> {
 
         let registered = self.get_installed_modules()?;
 
-        for target in &self.target_modules {
-            if let Some(version) = self.find_installed_version(target, &registered) {
-                println!(
+        for target in self.resolve_targets(&registered) {
+            let target = &target.name;
+            // Prefer the version actually built for the old kernel over the selection policy
+            let old_kernel_version = registered
+                .iter()
+                .find(|entry| {
+                    entry.module_name == *target && entry.kernel_version == *kernel_name_old
+                })
+                .map(|entry| entry.module_version.clone());
+
+            if let Some(version) =
+                old_kernel_version.or_else(|| self.find_installed_version(target, &registered))
+            {
+                info!(
                     "Uninstalling DKMS module '{target}' version '{version}' from old kernel {kernel_name_old}..."
                 );
 
                 let module_spec = format!("{target}/{version}");
                 let remove_args = ["remove", &module_spec, "-k", kernel_name_old];
 
-                if let Err(e) = run_command("dkms", &remove_args) {
-                    eprintln!(
-                        "Warning: Failed to clean up '{target}' for old kernel {kernel_name_old}: {e}"
-                    );
+                let remove_command = self.config.target_command("dkms", &remove_args);
+                if let Err(e) = run_command_line(&remove_command, &[]) {
+                    warn!("Failed to clean up '{target}' for old kernel {kernel_name_old}: {e}");
                 } else {
-                    println!("Successfully removed '{target}' from old kernel registry.");
+                    info!("Successfully removed '{target}' from old kernel registry.");
                 }
 
-                let leftover_var_dir =
-                    format!("/var/lib/dkms/{target}/{version}/{kernel_name_old}");
+                let leftover_var_dir = self
+                    .config
+                    .target_path(DKMS_TREE)
+                    .join(target)
+                    .join(&version)
+                    .join(kernel_name_old);
                 let _ = std::fs::remove_dir_all(leftover_var_dir);
             }
         }
@@ -163,19 +822,8 @@ impl<'a> DkmsManager<'a> {
 #[cfg(test)]
 mod tests_dkms {
     use super::*;
-    use crate::Arguments;
-
-    // Helper to generate a basic config for the manager
-    fn get_stub_config() -> Config {
-        let args = Arguments {
-            downloader: crate::args::Downloader::Curl,
-            suffix: "TestSuffix".to_string(),
-            new: crate::Version::new(6, 15, 4),
-            old: Some(crate::Version::new(6, 15, 3)),
-            command: None,
-        };
-        Config::new(args).expect("Failed to initialize test config")
-    }
+    use crate::test_utils::{TempDirGuard, stub_config};
+    use std::str::FromStr;
 
     #[test]
     fn test_parse_status_output_valid() {
@@ -191,13 +839,13 @@ mod tests_dkms {
         assert_eq!(parsed[0].module_version, "610.43.03");
         assert_eq!(parsed[0].kernel_version, "7.1.4-1-MANJARO");
         assert_eq!(parsed[0].architecture, "x86_64");
-        assert_eq!(parsed[0].status, "installed");
+        assert_eq!(parsed[0].status, DkmsStatus::Installed);
 
         assert_eq!(parsed[1].module_name, "v4l2loopback");
         assert_eq!(parsed[1].module_version, "0.15.4");
         assert_eq!(parsed[1].kernel_version, "7.1.4-1-MANJARO");
         assert_eq!(parsed[1].architecture, "x86_64");
-        assert_eq!(parsed[1].status, "installed");
+        assert_eq!(parsed[1].status, DkmsStatus::Installed);
     }
 
     #[test]
@@ -219,7 +867,7 @@ mod tests_dkms {
 
     #[test]
     fn test_find_installed_version_found() {
-        let config = get_stub_config();
+        let config = stub_config();
         let manager = DkmsManager::new(&config);
 
         let registered = vec![
@@ -228,14 +876,16 @@ mod tests_dkms {
                 module_version: "550.40.01".to_string(),
                 kernel_version: "6.12.0".to_string(),
                 architecture: "x86_64".to_string(),
-                status: "installed".to_string(),
+                status: DkmsStatus::Installed,
+                warnings: Vec::new(),
             },
             DkmsEntry {
                 module_name: "v4l2loopback".to_string(),
                 module_version: "0.12.7".to_string(),
                 kernel_version: "6.12.0".to_string(),
                 architecture: "x86_64".to_string(),
-                status: "installed".to_string(),
+                status: DkmsStatus::Installed,
+                warnings: Vec::new(),
             },
         ];
 
@@ -245,7 +895,7 @@ mod tests_dkms {
 
     #[test]
     fn test_find_installed_version_not_found() {
-        let config = get_stub_config();
+        let config = stub_config();
         let manager = DkmsManager::new(&config);
 
         let registered = vec![DkmsEntry {
@@ -253,10 +903,333 @@ mod tests_dkms {
             module_version: "0.12.7".to_string(),
             kernel_version: "6.12.0".to_string(),
             architecture: "x86_64".to_string(),
-            status: "installed".to_string(),
+            status: DkmsStatus::Installed,
+            warnings: Vec::new(),
         }];
 
         let version = manager.find_installed_version("nvidia", &registered);
         assert_eq!(version, None);
     }
+
+    fn entry(name: &str, version: &str) -> DkmsEntry {
+        DkmsEntry {
+            module_name: name.to_string(),
+            module_version: version.to_string(),
+            kernel_version: "6.12.0".to_string(),
+            architecture: "x86_64".to_string(),
+            status: DkmsStatus::Installed,
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_module_spec_from_str() {
+        let spec = DkmsModuleSpec::from_str("zfs").unwrap();
+        assert_eq!(spec, DkmsModuleSpec::required("zfs"));
+
+        let spec = DkmsModuleSpec::from_str("evdi:optional").unwrap();
+        assert_eq!(spec.name, "evdi");
+        assert!(!spec.required);
+
+        let spec = DkmsModuleSpec::from_str("vboxhost:required").unwrap();
+        assert!(spec.required);
+        assert_eq!(spec.to_string(), "vboxhost:required");
+
+        let spec = DkmsModuleSpec::from_str("nvidia@550.40.07:optional").unwrap();
+        assert_eq!(spec.name, "nvidia");
+        assert_eq!(spec.version.as_deref(), Some("550.40.07"));
+        assert_eq!(spec.to_string(), "nvidia@550.40.07:optional");
+
+        for invalid in ["", "zfs:maybe", "nvidia/550.40.01", ":optional", "nvidia@"] {
+            assert!(
+                matches!(
+                    DkmsModuleSpec::from_str(invalid),
+                    Err(KernelUpdaterError::DkmsModuleSpecParseError { .. })
+                ),
+                "Expected parse error for {invalid:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_default_target_modules() {
+        let config = stub_config();
+        let manager = DkmsManager::new(&config);
+        let names: Vec<&str> = manager
+            .target_modules
+            .iter()
+            .map(|spec| spec.name.as_str())
+            .collect();
+        assert_eq!(names, DEFAULT_MODULES);
+        assert!(manager.target_modules.iter().all(|spec| spec.required));
+    }
+
+    #[test]
+    fn test_resolve_targets_all_expands_registry() {
+        let mut config = stub_config();
+        config.dkms.modules = vec![
+            DkmsModuleSpec::from_str("zfs:optional").unwrap(),
+            DkmsModuleSpec::from_str("all").unwrap(),
+        ];
+        let manager = DkmsManager::new(&config);
+
+        let registered = vec![
+            entry("nvidia", "550.40.01"),
+            entry("zfs", "2.2.4"),
+            entry("nvidia", "550.40.01"),
+        ];
+
+        let resolved = manager.resolve_targets(&registered);
+        assert_eq!(
+            resolved,
+            vec![
+                DkmsModuleSpec {
+                    name: "zfs".to_string(),
+                    version: None,
+                    required: false,
+                },
+                DkmsModuleSpec::required("nvidia"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_status_output_typed_states_and_warnings() {
+        let raw_output = r#"
+            nvidia/550.40.01: added
+            nvidia/550.54.14, 6.12.0-1-MANJARO, x86_64: built
+            nvidia/550.54.14, 6.13.2-1-MANJARO, x86_64: installed (WARNING! Diff between built and installed module!)
+            zfs/2.2.4, 6.12.0-1-MANJARO, x86_64: installed-weak from 6.12.0-0-MANJARO
+            evdi/1.14.4, 6.12.0-1-MANJARO, x86_64: broken
+        "#;
+
+        let parsed = DkmsManager::parse_status_output(raw_output);
+        assert_eq!(parsed.len(), 5);
+
+        assert_eq!(parsed[0].status, DkmsStatus::Added);
+        assert_eq!(parsed[0].kernel_version, "");
+        assert_eq!(parsed[1].status, DkmsStatus::Built);
+        assert_eq!(parsed[2].status, DkmsStatus::Installed);
+        assert_eq!(
+            parsed[2].warnings,
+            vec!["WARNING! Diff between built and installed module!".to_string()]
+        );
+        assert_eq!(parsed[3].status, DkmsStatus::InstalledWeak);
+        assert_eq!(parsed[4].status, DkmsStatus::Broken);
+        assert!(parsed[4].warnings.is_empty());
+    }
+
+    #[test]
+    fn test_parse_status_output_dkms2_format() {
+        let raw_output = r#"
+            nvidia, 470.256.02, 6.1.90-1-MANJARO, x86_64: installed
+            v4l2loopback, 0.12.7: added
+        "#;
+
+        let parsed = DkmsManager::parse_status_output(raw_output);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].module_name, "nvidia");
+        assert_eq!(parsed[0].module_version, "470.256.02");
+        assert_eq!(parsed[0].kernel_version, "6.1.90-1-MANJARO");
+        assert_eq!(parsed[0].architecture, "x86_64");
+        assert_eq!(parsed[0].status, DkmsStatus::Installed);
+
+        assert_eq!(parsed[1].module_name, "v4l2loopback");
+        assert_eq!(parsed[1].module_version, "0.12.7");
+        assert_eq!(parsed[1].status, DkmsStatus::Added);
+    }
+
+    #[test]
+    fn test_compare_module_versions() {
+        assert_eq!(
+            compare_module_versions("550.100", "550.40.07"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_module_versions("550.40.07", "550.40.7"),
+            Ordering::Equal
+        );
+        assert_eq!(compare_module_versions("2.2.4", "2.2.4-1"), Ordering::Less);
+        assert_eq!(compare_module_versions("0.12.7", "0.13.0"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_group_by_module() {
+        let registered = vec![
+            entry("nvidia", "550.40.01"),
+            entry("zfs", "2.2.4"),
+            entry("nvidia", "550.54.14"),
+        ];
+
+        let groups = DkmsManager::group_by_module(&registered);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups["nvidia"].len(), 2);
+        assert_eq!(groups["zfs"].len(), 1);
+    }
+
+    #[test]
+    fn test_find_installed_version_prefers_newest() {
+        let config = stub_config();
+        let manager = DkmsManager::new(&config);
+
+        let registered = vec![
+            entry("nvidia", "550.100"),
+            entry("nvidia", "550.40.07"),
+            entry("nvidia", "545.29.06"),
+        ];
+
+        let version = manager.find_installed_version("nvidia", &registered);
+        assert_eq!(version, Some("550.100".to_string()));
+    }
+
+    #[test]
+    fn test_find_installed_version_pinned() {
+        let mut config = stub_config();
+        config.dkms.modules = vec![DkmsModuleSpec::from_str("nvidia@550.40.07").unwrap()];
+        let manager = DkmsManager::new(&config);
+
+        let registered = vec![entry("nvidia", "550.100"), entry("nvidia", "550.40.07")];
+        let version = manager.find_installed_version("nvidia", &registered);
+        assert_eq!(version, Some("550.40.07".to_string()));
+
+        // A pinned version that is not registered is never substituted
+        let registered = vec![entry("nvidia", "550.100")];
+        let version = manager.find_installed_version("nvidia", &registered);
+        assert_eq!(version, None);
+    }
+
+    #[test]
+    fn test_extract_log_excerpt_first_error() {
+        let mut log: Vec<String> = (0..30).map(|i| format!("  CC [M]  file{i}.o")).collect();
+        log.insert(
+            12,
+            "nvidia/nv.c:42:5: error: implicit declaration of function 'foo'".to_string(),
+        );
+        log.push("nvidia/nv.c:99:1: error: second error".to_string());
+        let log = log.join("\n");
+
+        let excerpt = extract_log_excerpt(&log);
+        assert_eq!(excerpt.len(), LOG_CONTEXT_BEFORE + LOG_CONTEXT_AFTER + 1);
+        assert_eq!(
+            excerpt[LOG_CONTEXT_BEFORE],
+            "nvidia/nv.c:42:5: error: implicit declaration of function 'foo'"
+        );
+    }
+
+    #[test]
+    fn test_extract_log_excerpt_tail_without_error() {
+        let log: Vec<String> = (0..40).map(|i| format!("line {i}")).collect();
+        let log = log.join("\n");
+
+        let excerpt = extract_log_excerpt(&log);
+        assert_eq!(excerpt.len(), LOG_CONTEXT_BEFORE + LOG_CONTEXT_AFTER + 1);
+        assert_eq!(excerpt.last(), Some(&"line 39"));
+
+        assert!(extract_log_excerpt("").is_empty());
+    }
+
+    #[test]
+    fn test_unregistered_module_emits_warning_event() {
+        let mut config = stub_config();
+        config.dkms.modules = vec![DkmsModuleSpec::from_str("zfs").unwrap()];
+        let manager = DkmsManager::new(&config);
+
+        let mut result = None;
+        let events = crate::events::capture_events(|| {
+            result = Some(manager.install_registered_modules(&[entry("nvidia", "550.54.14")]));
+        });
+
+        let reports = result.unwrap().expect("Skipping a module must not fail");
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].outcome, DkmsOutcome::NotRegistered);
+        assert_eq!(
+            events,
+            vec![crate::events::Event::Warning {
+                message: "Module 'zfs' is not registered on system. Skipping build.".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_missing_pinned_version() {
+        let mut config = stub_config();
+        config.dkms.modules = vec![
+            DkmsModuleSpec::from_str("nvidia@550.40.07").unwrap(),
+            DkmsModuleSpec::from_str("zfs@2.2.4:optional").unwrap(),
+        ];
+        let manager = DkmsManager::new(&config);
+        let registered = [entry("nvidia", "550.100"), entry("zfs", "2.3.0")];
+
+        // A required pin that is not registered fails instead of being skipped
+        let err = manager.install_registered_modules(&registered).unwrap_err();
+        assert!(
+            matches!(&err, KernelUpdaterError::DkmsPinnedVersionNotRegistered { module, version }
+                if module == "nvidia" && version == "550.40.07"),
+            "Unexpected error: {err:?}"
+        );
+
+        // An optional one is skipped
+        config.dkms.modules.remove(0);
+        let manager = DkmsManager::new(&config);
+        let reports = manager.install_registered_modules(&registered).unwrap();
+        assert_eq!(reports[0].outcome, DkmsOutcome::NotRegistered);
+    }
+
+    #[test]
+    fn test_build_failure_archives_log() {
+        let temp_dir = TempDirGuard::new("dkms-build-log");
+        let config = stub_config();
+        let manager = DkmsManager::new(&config);
+
+        let dkms_tree = temp_dir.path.join("dkms");
+        assert_eq!(
+            find_build_log(
+                &dkms_tree,
+                "nvidia",
+                "550.54.14",
+                &config.kernel_ident_name_new
+            ),
+            None
+        );
+
+        let build_dir = dkms_tree.join("nvidia/550.54.14/build");
+        fs::create_dir_all(&build_dir).unwrap();
+        fs::write(build_dir.join("make.log"), "nv.c:1:1: error: boom\n").unwrap();
+        assert_eq!(
+            find_build_log(
+                &dkms_tree,
+                "nvidia",
+                "550.54.14",
+                &config.kernel_ident_name_new
+            ),
+            Some(build_dir.join("make.log"))
+        );
+
+        // Without a run log, the error points at the log in the DKMS tree
+        let source = KernelUpdaterError::IoError(std::io::Error::other("dkms install failed"));
+        let err = manager.build_failure(&dkms_tree, "nvidia", "550.54.14", None, source);
+        assert!(
+            matches!(&err, KernelUpdaterError::DkmsBuildFailed { module, log_path: Some(path), .. }
+                if module == "nvidia" && *path == build_dir.join("make.log")),
+            "Unexpected error: {err:?}"
+        );
+
+        // With a run log, the log is archived next to it, one copy per run
+        let run_log = temp_dir.path.join("runs/run-20250615-150640.log");
+        fs::create_dir_all(run_log.parent().unwrap()).unwrap();
+        let archived = temp_dir
+            .path
+            .join("runs/run-20250615-150640.dkms-nvidia-550.54.14.make.log");
+        let source = KernelUpdaterError::IoError(std::io::Error::other("dkms install failed"));
+        let err = manager.build_failure(&dkms_tree, "nvidia", "550.54.14", Some(&run_log), source);
+        assert!(
+            matches!(&err, KernelUpdaterError::DkmsBuildFailed { log_path: Some(path), .. }
+                if *path == archived),
+            "Unexpected error: {err:?}"
+        );
+        assert_eq!(
+            fs::read_to_string(&archived).unwrap(),
+            "nv.c:1:1: error: boom\n"
+        );
+    }
 }
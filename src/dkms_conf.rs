@@ -0,0 +1,283 @@
+use crate::{error::KernelUpdaterError, kconfig::KernelConfig};
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// The subset of a module's `dkms.conf` that decides whether it can build for a given kernel.
+///
+/// `dkms.conf` is a shell fragment; only plain `KEY=value` and `KEY[n]=value` assignments are read.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DkmsConf {
+    /// Bash regular expression the kernel release must match (`BUILD_EXCLUSIVE_KERNEL`).
+    pub build_exclusive_kernel: Option<String>,
+    /// Options that must be enabled (`CONFIG_X`) or disabled (`!CONFIG_X`) (`BUILD_EXCLUSIVE_CONFIG`).
+    pub build_exclusive_config: Vec<String>,
+    /// Patches (`PATCH[n]`) paired with the kernel regular expression that enables them (`PATCH_MATCH[n]`).
+    pub patches: Vec<(String, Option<String>)>,
+}
+
+/// Result of evaluating a `dkms.conf` against the target kernel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DkmsCompatibility {
+    /// The module may build; lists the patches DKMS will apply for this kernel.
+    Compatible { patches: Vec<String> },
+    /// The module declares itself incompatible with this kernel.
+    Excluded { reason: String },
+}
+
+impl DkmsConf {
+    /// Reads and parses a `dkms.conf` file.
+    pub fn from_file(path: &Path) -> Result<Self, KernelUpdaterError> {
+        let content = fs::read_to_string(path).map_err(|io_error| KernelUpdaterError::IOError {
+            path: path.to_path_buf(),
+            io_error,
+        })?;
+        Ok(Self::parse(&content))
+    }
+
+    /// Parses the content of a `dkms.conf` file.
+    pub fn parse(content: &str) -> Self {
+        let mut conf = Self::default();
+        let mut patches: HashMap<usize, String> = HashMap::new();
+        let mut patch_matches: HashMap<usize, String> = HashMap::new();
+
+        for line in content.lines().map(str::trim) {
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = unquote(value);
+
+            // Array assignments look like PATCH[0]="fix.patch"
+            let (name, index) = match key.split_once('[') {
+                Some((name, rest)) => (name, rest.trim_end_matches(']').parse::<usize>().ok()),
+                None => (key, None),
+            };
+
+            match (name, index) {
+                ("BUILD_EXCLUSIVE_KERNEL", None) => conf.build_exclusive_kernel = Some(value),
+                ("BUILD_EXCLUSIVE_CONFIG", None) => {
+                    conf.build_exclusive_config =
+                        value.split_whitespace().map(str::to_string).collect();
+                }
+                ("PATCH", Some(index)) => {
+                    patches.insert(index, value);
+                }
+                ("PATCH_MATCH", Some(index)) => {
+                    patch_matches.insert(index, value);
+                }
+                _ => {}
+            }
+        }
+
+        let mut indexes: Vec<usize> = patches.keys().copied().collect();
+        indexes.sort_unstable();
+        conf.patches = indexes
+            .into_iter()
+            .filter_map(|index| {
+                let patch = patches.remove(&index)?;
+                Some((patch, patch_matches.remove(&index)))
+            })
+            .collect();
+
+        conf
+    }
+
+    /// Evaluates the build restrictions against a kernel release and its `.config`.
+    pub fn evaluate(
+        &self,
+        kernel_release: &str,
+        kernel_config: &KernelConfig,
+    ) -> Result<DkmsCompatibility, KernelUpdaterError> {
+        if let Some(pattern) = &self.build_exclusive_kernel
+            && !bash_regex_matches(pattern, kernel_release)?
+        {
+            return Ok(DkmsCompatibility::Excluded {
+                reason: format!(
+                    "BUILD_EXCLUSIVE_KERNEL=\"{pattern}\" does not match {kernel_release}"
+                ),
+            });
+        }
+
+        for requirement in &self.build_exclusive_config {
+            let (symbol, must_be_enabled) = match requirement.strip_prefix('!') {
+                Some(symbol) => (symbol, false),
+                None => (requirement.as_str(), true),
+            };
+            let enabled = kernel_config.is_enabled(symbol);
+
+            if enabled != must_be_enabled {
+                let state = if enabled { "enabled" } else { "not enabled" };
+                return Ok(DkmsCompatibility::Excluded {
+                    reason: format!(
+                        "BUILD_EXCLUSIVE_CONFIG requires {requirement}, but {symbol} is {state}"
+                    ),
+                });
+            }
+        }
+
+        let mut patches = Vec::new();
+        for (patch, pattern) in &self.patches {
+            let applies = match pattern {
+                Some(pattern) => bash_regex_matches(pattern, kernel_release)?,
+                None => true,
+            };
+            if applies {
+                patches.push(patch.clone());
+            }
+        }
+
+        Ok(DkmsCompatibility::Compatible { patches })
+    }
+}
+
+/// Strips surrounding whitespace and a single pair of shell quotes from a value.
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value)
+        .to_string()
+}
+
+/// Matches `text` against `pattern` with bash's `=~` operator, the engine DKMS itself uses.
+fn bash_regex_matches(pattern: &str, text: &str) -> Result<bool, KernelUpdaterError> {
+    let status = Command::new("bash")
+        .args(["-c", r#"[[ "$1" =~ $2 ]]"#, "bash", text, pattern])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+
+    match status.code() {
+        Some(0) => Ok(true),
+        Some(1) => Ok(false),
+        _ => Err(KernelUpdaterError::CommandExecutionError {
+            command: "bash".to_string(),
+            args: format!("-c '[[ \"{text}\" =~ {pattern} ]]'"),
+            status,
+            stderr_tail: Vec::new(),
+        }),
+    }
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_dkms_conf
+#[cfg(test)]
+mod tests_dkms_conf {
+    use super::*;
+
+    const NVIDIA_DKMS_CONF: &str = r#"
+        PACKAGE_NAME="nvidia"
+        PACKAGE_VERSION="550.54.14"
+        AUTOINSTALL="yes"
+
+        # Only kernels up to 6.13 are supported by this release
+        BUILD_EXCLUSIVE_KERNEL="^6\.(1[0-3]|[0-9])\."
+        BUILD_EXCLUSIVE_CONFIG="CONFIG_DRM !CONFIG_TRIM_UNUSED_KSYMS"
+
+        PATCH[0]="kernel-6.12.patch"
+        PATCH_MATCH[0]="^6\.1[2-3]"
+        PATCH[1]='always.patch'
+    "#;
+
+    fn symbols(pairs: &[(&str, &str)]) -> KernelConfig {
+        let content: Vec<String> = pairs.iter().map(|(k, v)| format!("{k}={v}")).collect();
+        KernelConfig::parse(&content.join("\n"))
+    }
+
+    #[test]
+    fn test_parse_dkms_conf() {
+        let conf = DkmsConf::parse(NVIDIA_DKMS_CONF);
+
+        assert_eq!(
+            conf.build_exclusive_kernel.as_deref(),
+            Some(r"^6\.(1[0-3]|[0-9])\.")
+        );
+        assert_eq!(
+            conf.build_exclusive_config,
+            vec!["CONFIG_DRM", "!CONFIG_TRIM_UNUSED_KSYMS"]
+        );
+        assert_eq!(
+            conf.patches,
+            vec![
+                (
+                    "kernel-6.12.patch".to_string(),
+                    Some(r"^6\.1[2-3]".to_string())
+                ),
+                ("always.patch".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_evaluate_compatible_with_patches() {
+        let conf = DkmsConf::parse(NVIDIA_DKMS_CONF);
+        let result = conf
+            .evaluate("6.12.4-ClaudioFSR", &symbols(&[("CONFIG_DRM", "m")]))
+            .unwrap();
+
+        assert_eq!(
+            result,
+            DkmsCompatibility::Compatible {
+                patches: vec!["kernel-6.12.patch".to_string(), "always.patch".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn test_evaluate_excluded_kernel() {
+        let conf = DkmsConf::parse(NVIDIA_DKMS_CONF);
+        let result = conf
+            .evaluate("6.15.4-ClaudioFSR", &symbols(&[("CONFIG_DRM", "y")]))
+            .unwrap();
+
+        assert!(
+            matches!(&result, DkmsCompatibility::Excluded { reason } if reason.contains("BUILD_EXCLUSIVE_KERNEL")),
+            "Unexpected result: {result:?}"
+        );
+    }
+
+    #[test]
+    fn test_evaluate_excluded_config() {
+        let conf = DkmsConf::parse(NVIDIA_DKMS_CONF);
+
+        // Missing required option
+        let result = conf.evaluate("6.12.4-ClaudioFSR", &symbols(&[])).unwrap();
+        assert!(
+            matches!(&result, DkmsCompatibility::Excluded { reason } if reason.contains("CONFIG_DRM is not enabled"))
+        );
+
+        // Forbidden option enabled
+        let result = conf
+            .evaluate(
+                "6.12.4-ClaudioFSR",
+                &symbols(&[("CONFIG_DRM", "y"), ("CONFIG_TRIM_UNUSED_KSYMS", "y")]),
+            )
+            .unwrap();
+        assert!(
+            matches!(&result, DkmsCompatibility::Excluded { reason } if reason.contains("CONFIG_TRIM_UNUSED_KSYMS is enabled"))
+        );
+    }
+
+    #[test]
+    fn test_evaluate_unrestricted_module() {
+        let conf = DkmsConf::parse("PACKAGE_NAME=\"v4l2loopback\"\nPACKAGE_VERSION=0.13.2\n");
+        let result = conf.evaluate("7.0.1-ClaudioFSR", &symbols(&[])).unwrap();
+        assert_eq!(result, DkmsCompatibility::Compatible { patches: vec![] });
+    }
+}
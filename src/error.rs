@@ -1,5 +1,10 @@
-use crate::{Version, args::Commands};
-use std::{io, num::ParseIntError, path::PathBuf, process::ExitStatus, string::FromUtf8Error};
+use crate::{
+    OptionViolation, Version,
+    args::{Commands, ConfigEditor, ConfigSeed},
+};
+use clap::ValueEnum;
+use serde_json::{Value, json};
+use std::{fmt, io, num::ParseIntError, path::PathBuf, process::ExitStatus, string::FromUtf8Error};
 use thiserror::Error;
 
 /**
@@ -33,6 +38,8 @@ pub enum KernelUpdaterError {
         command: String,
         args: String, // Join args for cleaner display
         status: ExitStatus,
+        /// Last lines written on stderr (already shown live), used to derive a hint.
+        stderr_tail: Vec<String>,
     },
 
     // Error for external command returning invalid UTF-8 output
@@ -73,10 +80,92 @@ pub enum KernelUpdaterError {
         reason: String,
     },
 
+    #[error(
+        "Invalid DKMS module '{input}': expected `name[@version][:required|:optional]` (e.g., zfs:optional, nvidia@550.40.07)"
+    )]
+    DkmsModuleSpecParseError { input: String },
+
+    #[error(
+        "DKMS module '{module}' is pinned to version {version}, which is not registered in DKMS"
+    )]
+    DkmsPinnedVersionNotRegistered { module: String, version: String },
+
+    #[error("DKMS module '{module}/{version}' cannot be built for kernel {kernel}: {reason}")]
+    DkmsModuleIncompatible {
+        module: String,
+        version: String,
+        kernel: String,
+        reason: String,
+    },
+
+    #[error(
+        "DKMS build of '{module}/{version}' for kernel {kernel} failed ({source}).\nBuild log: {}",
+        log_path.as_ref().map_or("<not found>".to_string(), |path| path.display().to_string())
+    )]
+    DkmsBuildFailed {
+        module: String,
+        version: String,
+        kernel: String,
+        log_path: Option<PathBuf>,
+        #[source]
+        source: Box<KernelUpdaterError>,
+    },
+
+    // --- Module Signing Errors ---
+    #[error(
+        "Module signing key not found at {}. \
+        Was the kernel built with CONFIG_MODULE_SIG_ALL, or is the MOK path correct?",
+        path.display()
+    )]
+    ModuleSigningKeyNotFound { path: PathBuf },
+
+    #[error("Module {} is not signed after running sign-file (modinfo reports no signer)", module.display())]
+    ModuleSignatureMissing { module: PathBuf },
+
     // --- Kernel File/Path/Build Errors ---
     #[error("Kernel config file not found at {}", path.display())]
     KernelConfigNotFound { path: PathBuf },
 
+    #[error(
+        "No {seed:?} kernel config found to seed the base config. Tried: {}",
+        candidates.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", ")
+    )]
+    SeedConfigNotFound {
+        seed: ConfigSeed,
+        candidates: Vec<PathBuf>,
+    },
+
+    #[error(
+        "--edit-config {} cannot run here: {reason}. Drop the flag for unattended runs.",
+        editor.make_target()
+    )]
+    NonInteractiveEditConfig {
+        editor: ConfigEditor,
+        reason: String,
+    },
+
+    #[error("lsmod snapshot for localmodconfig not found at {}", path.display())]
+    LsmodSnapshotNotFound { path: PathBuf },
+
+    #[error("Kernel config fragment or profile not found at {}", path.display())]
+    ConfigFragmentNotFound { path: PathBuf },
+
+    #[error(
+        "Invalid kernel option rule '{input}': expected CONFIG_X, !CONFIG_X or CONFIG_X=value (e.g., CONFIG_EFI_STUB=y)"
+    )]
+    OptionRuleParseError { input: String },
+
+    #[error(
+        "Kernel configuration in {} violates {} required option(s):\n{}",
+        config_path.display(),
+        violations.len(),
+        format_violations(violations)
+    )]
+    RequiredOptionsViolated {
+        config_path: PathBuf,
+        violations: Vec<OptionViolation>,
+    },
+
     #[error(
         "Kernel source tree ({}) for version {} is not configured.\n\
         Required '.config' file is missing.\n\
@@ -94,6 +183,44 @@ pub enum KernelUpdaterError {
         version: Version,
     },
 
+    #[error(
+        "`make {target}` completed but no package was found in {}.",
+        dir.display()
+    )]
+    PackageNotProduced { target: String, dir: PathBuf },
+
+    #[error("Bundle {} has an invalid manifest: {reason}", bundle.display())]
+    BundleManifestInvalid { bundle: PathBuf, reason: String },
+
+    #[error(
+        "Bundle {} holds a kernel built for {field} {found}, but {expected} is being installed.",
+        bundle.display()
+    )]
+    BundleMismatch {
+        bundle: PathBuf,
+        field: String,
+        expected: String,
+        found: String,
+    },
+
+    #[error(
+        "Bundle {} failed verification: {} file(s) are missing, altered or not listed in its manifest:\n{}",
+        bundle.display(),
+        files.len(),
+        format_paths(files)
+    )]
+    BundleChecksumMismatch {
+        bundle: PathBuf,
+        files: Vec<PathBuf>,
+    },
+
+    // --- Run Report Errors ---
+    #[error("Failed to serialize the run report: {source}")]
+    RunReportSerializationError {
+        #[source]
+        source: serde_json::Error,
+    },
+
     // --- Version Parsing Errors ---
     #[error("Invalid version component: failed to parse as integer ({source})")]
     VersionParseIntError {
@@ -105,4 +232,505 @@ pub enum KernelUpdaterError {
         "Invalid version format '{input}': expected exactly three dot-separated numbers (e.g., X.Y.Z as 6.15.3)"
     )]
     VersionParseFormatError { input: String },
+
+    // --- Pipeline Errors ---
+    /// Wraps the error that stopped a pipeline phase, recording the phase for the message
+    /// and the exit code.
+    #[error("{phase} failed: {source}")]
+    PhaseFailed {
+        phase: Phase,
+        #[source]
+        source: Box<KernelUpdaterError>,
+    },
+}
+
+/// Pipeline phase in which a failure occurred, deciding the exit code of errors that do
+/// not have one of their own (I/O errors and failed commands).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Argument validation and configuration, before any work is done.
+    Setup,
+    /// Download, configuration and build of the kernel.
+    Compile,
+    /// Installation of the kernel image, modules and headers.
+    Install,
+    /// Removal and installation of the DKMS modules.
+    Dkms,
+    /// Initramfs and bootloader update.
+    Boot,
+    /// Creation or installation of a bundle.
+    Bundle,
+    /// Creation of a package.
+    Package,
+}
+
+impl Phase {
+    /// Machine readable name, part of the `--output json` schema.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Setup => "setup",
+            Self::Compile => "compile",
+            Self::Install => "install",
+            Self::Dkms => "dkms",
+            Self::Boot => "boot",
+            Self::Bundle => "bundle",
+            Self::Package => "package",
+        }
+    }
+
+    /// Exit code of a generic failure in this phase.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Setup => KernelUpdaterError::EXIT_FAILURE,
+            Self::Compile => KernelUpdaterError::EXIT_COMPILE,
+            Self::Install => KernelUpdaterError::EXIT_INSTALL,
+            Self::Dkms => KernelUpdaterError::EXIT_DKMS,
+            Self::Boot => KernelUpdaterError::EXIT_BOOT,
+            Self::Bundle => KernelUpdaterError::EXIT_BUNDLE,
+            Self::Package => KernelUpdaterError::EXIT_PACKAGE,
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let title = match self {
+            Self::Setup => "Setup",
+            Self::Compile => "Kernel compilation",
+            Self::Install => "Kernel installation",
+            Self::Dkms => "DKMS update",
+            Self::Boot => "Boot configuration",
+            Self::Bundle => "Kernel bundling",
+            Self::Package => "Kernel packaging",
+        };
+        f.write_str(title)
+    }
+}
+
+impl KernelUpdaterError {
+    /// Unexpected failure without a more specific category.
+    pub const EXIT_FAILURE: i32 = 1;
+    /// Invalid arguments or input files given on the command line (same as clap's usage errors).
+    pub const EXIT_USAGE: i32 = 2;
+    /// Missing, unconfigured or invalid kernel configuration.
+    pub const EXIT_CONFIG: i32 = 3;
+    /// Kernel download or build failed.
+    pub const EXIT_COMPILE: i32 = 4;
+    /// Kernel installation failed.
+    pub const EXIT_INSTALL: i32 = 5;
+    /// A DKMS module could not be found, checked or built.
+    pub const EXIT_DKMS: i32 = 6;
+    /// Module signing failed.
+    pub const EXIT_SIGNING: i32 = 7;
+    /// Initramfs or bootloader update failed.
+    pub const EXIT_BOOT: i32 = 8;
+    /// Bundle creation, verification or installation failed.
+    pub const EXIT_BUNDLE: i32 = 9;
+    /// Package creation failed.
+    pub const EXIT_PACKAGE: i32 = 10;
+
+    /// Process exit code for this error: the category of the variant, or for generic errors
+    /// (I/O, failed commands) the phase in which they occurred.
+    pub fn exit_code(&self) -> i32 {
+        self.category_exit_code().unwrap_or(Self::EXIT_FAILURE)
+    }
+
+    /// Exit code of the variant, `None` for generic errors whose category depends on the phase.
+    fn category_exit_code(&self) -> Option<i32> {
+        match self {
+            Self::IoError(_)
+            | Self::IOError { .. }
+            | Self::CommandExecutionError { .. }
+            | Self::Utf8OutputError { .. }
+            | Self::RunReportSerializationError { .. } => None,
+            Self::VersionComparisonError { .. }
+            | Self::MissingRequiredArgument { .. }
+            | Self::DkmsModuleSpecParseError { .. }
+            | Self::NonInteractiveEditConfig { .. }
+            | Self::LsmodSnapshotNotFound { .. }
+            | Self::ConfigFragmentNotFound { .. }
+            | Self::OptionRuleParseError { .. }
+            | Self::VersionParseIntError { .. }
+            | Self::VersionParseFormatError { .. } => Some(Self::EXIT_USAGE),
+            Self::KernelConfigNotFound { .. }
+            | Self::SeedConfigNotFound { .. }
+            | Self::RequiredOptionsViolated { .. }
+            | Self::KernelNotConfigured { .. } => Some(Self::EXIT_CONFIG),
+            Self::KernelBinaryNotFound { .. } => Some(Self::EXIT_COMPILE),
+            Self::DkmsModuleNotFound
+            | Self::DkmsStatusParseError { .. }
+            | Self::DkmsPinnedVersionNotRegistered { .. }
+            | Self::DkmsModuleIncompatible { .. }
+            | Self::DkmsBuildFailed { .. } => Some(Self::EXIT_DKMS),
+            Self::ModuleSigningKeyNotFound { .. } | Self::ModuleSignatureMissing { .. } => {
+                Some(Self::EXIT_SIGNING)
+            }
+            Self::PackageNotProduced { .. } => Some(Self::EXIT_PACKAGE),
+            Self::BundleManifestInvalid { .. }
+            | Self::BundleMismatch { .. }
+            | Self::BundleChecksumMismatch { .. } => Some(Self::EXIT_BUNDLE),
+            Self::PhaseFailed { phase, source } => {
+                Some(source.category_exit_code().unwrap_or(phase.exit_code()))
+            }
+        }
+    }
+
+    /// Records the phase in which the error occurred. An error already attributed to a
+    /// phase keeps it.
+    pub fn in_phase(self, phase: Phase) -> Self {
+        match self {
+            Self::PhaseFailed { .. } => self,
+            source => Self::PhaseFailed {
+                phase,
+                source: Box::new(source),
+            },
+        }
+    }
+
+    /// Name of the variant, part of the stable `--output json` schema.
+    pub fn variant_name(&self) -> &'static str {
+        match self {
+            Self::IoError(_) => "IoError",
+            Self::IOError { .. } => "IOError",
+            Self::CommandExecutionError { .. } => "CommandExecutionError",
+            Self::Utf8OutputError { .. } => "Utf8OutputError",
+            Self::VersionComparisonError { .. } => "VersionComparisonError",
+            Self::MissingRequiredArgument { .. } => "MissingRequiredArgument",
+            Self::DkmsModuleNotFound => "DkmsModuleNotFound",
+            Self::DkmsStatusParseError { .. } => "DkmsStatusParseError",
+            Self::DkmsModuleSpecParseError { .. } => "DkmsModuleSpecParseError",
+            Self::DkmsPinnedVersionNotRegistered { .. } => "DkmsPinnedVersionNotRegistered",
+            Self::DkmsModuleIncompatible { .. } => "DkmsModuleIncompatible",
+            Self::DkmsBuildFailed { .. } => "DkmsBuildFailed",
+            Self::ModuleSigningKeyNotFound { .. } => "ModuleSigningKeyNotFound",
+            Self::ModuleSignatureMissing { .. } => "ModuleSignatureMissing",
+            Self::KernelConfigNotFound { .. } => "KernelConfigNotFound",
+            Self::SeedConfigNotFound { .. } => "SeedConfigNotFound",
+            Self::NonInteractiveEditConfig { .. } => "NonInteractiveEditConfig",
+            Self::LsmodSnapshotNotFound { .. } => "LsmodSnapshotNotFound",
+            Self::ConfigFragmentNotFound { .. } => "ConfigFragmentNotFound",
+            Self::OptionRuleParseError { .. } => "OptionRuleParseError",
+            Self::RequiredOptionsViolated { .. } => "RequiredOptionsViolated",
+            Self::KernelNotConfigured { .. } => "KernelNotConfigured",
+            Self::KernelBinaryNotFound { .. } => "KernelBinaryNotFound",
+            Self::PackageNotProduced { .. } => "PackageNotProduced",
+            Self::BundleManifestInvalid { .. } => "BundleManifestInvalid",
+            Self::BundleMismatch { .. } => "BundleMismatch",
+            Self::BundleChecksumMismatch { .. } => "BundleChecksumMismatch",
+            Self::RunReportSerializationError { .. } => "RunReportSerializationError",
+            Self::VersionParseIntError { .. } => "VersionParseIntError",
+            Self::VersionParseFormatError { .. } => "VersionParseFormatError",
+            Self::PhaseFailed { .. } => "PhaseFailed",
+        }
+    }
+
+    /// Fields of the variant as a JSON object, named as in the variant. Paths and versions
+    /// are strings, exit statuses their code (`null` when killed by a signal), sources and
+    /// I/O errors their message, phases their name and a nested `KernelUpdaterError`
+    /// `{variant, message, fields}`.
+    pub fn fields(&self) -> Value {
+        match self {
+            Self::IoError(io_error) => json!({ "io_error": io_error.to_string() }),
+            Self::IOError { path, io_error } => json!({
+                "path": path,
+                "io_error": io_error.to_string(),
+            }),
+            Self::CommandExecutionError {
+                command,
+                args,
+                status,
+                stderr_tail,
+            } => json!({
+                "command": command,
+                "args": args,
+                "status": status.code(),
+                "stderr_tail": stderr_tail,
+            }),
+            Self::Utf8OutputError { command, source } => json!({
+                "command": command,
+                "source": source.to_string(),
+            }),
+            Self::VersionComparisonError { new, old } => json!({
+                "new": new.to_string(),
+                "old": old.to_string(),
+            }),
+            Self::MissingRequiredArgument {
+                argument_name,
+                command,
+            } => json!({
+                "argument_name": argument_name,
+                "command": command.as_ref().map(Commands::name),
+            }),
+            Self::DkmsModuleNotFound => json!({}),
+            Self::DkmsStatusParseError { output, reason } => json!({
+                "output": output,
+                "reason": reason,
+            }),
+            Self::DkmsModuleSpecParseError { input }
+            | Self::OptionRuleParseError { input }
+            | Self::VersionParseFormatError { input } => json!({ "input": input }),
+            Self::DkmsPinnedVersionNotRegistered { module, version } => json!({
+                "module": module,
+                "version": version,
+            }),
+            Self::DkmsModuleIncompatible {
+                module,
+                version,
+                kernel,
+                reason,
+            } => json!({
+                "module": module,
+                "version": version,
+                "kernel": kernel,
+                "reason": reason,
+            }),
+            Self::DkmsBuildFailed {
+                module,
+                version,
+                kernel,
+                log_path,
+                source,
+            } => json!({
+                "module": module,
+                "version": version,
+                "kernel": kernel,
+                "log_path": log_path,
+                "source": source.nested_report(),
+            }),
+            Self::ModuleSigningKeyNotFound { path }
+            | Self::KernelConfigNotFound { path }
+            | Self::LsmodSnapshotNotFound { path }
+            | Self::ConfigFragmentNotFound { path } => json!({ "path": path }),
+            Self::ModuleSignatureMissing { module } => json!({ "module": module }),
+            Self::SeedConfigNotFound { seed, candidates } => json!({
+                "seed": seed.to_possible_value().map(|value| value.get_name().to_string()),
+                "candidates": candidates,
+            }),
+            Self::NonInteractiveEditConfig { editor, reason } => json!({
+                "editor": editor.make_target(),
+                "reason": reason,
+            }),
+            Self::RequiredOptionsViolated {
+                config_path,
+                violations,
+            } => json!({
+                "config_path": config_path,
+                "violations": violations.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            }),
+            Self::KernelNotConfigured { src_dir, version } => json!({
+                "src_dir": src_dir,
+                "version": version.to_string(),
+            }),
+            Self::KernelBinaryNotFound {
+                path,
+                src_dir,
+                version,
+            } => json!({
+                "path": path,
+                "src_dir": src_dir,
+                "version": version.to_string(),
+            }),
+            Self::PackageNotProduced { target, dir } => json!({
+                "target": target,
+                "dir": dir,
+            }),
+            Self::BundleManifestInvalid { bundle, reason } => json!({
+                "bundle": bundle,
+                "reason": reason,
+            }),
+            Self::BundleMismatch {
+                bundle,
+                field,
+                expected,
+                found,
+            } => json!({
+                "bundle": bundle,
+                "field": field,
+                "expected": expected,
+                "found": found,
+            }),
+            Self::BundleChecksumMismatch { bundle, files } => json!({
+                "bundle": bundle,
+                "files": files,
+            }),
+            Self::RunReportSerializationError { source } => {
+                json!({ "source": source.to_string() })
+            }
+            Self::VersionParseIntError { source } => json!({ "source": source.to_string() }),
+            Self::PhaseFailed { phase, source } => json!({
+                "phase": phase.name(),
+                "source": source.nested_report(),
+            }),
+        }
+    }
+
+    /// A nested error as `{variant, message, fields}`.
+    fn nested_report(&self) -> Value {
+        json!({
+            "variant": self.variant_name(),
+            "message": self.to_string(),
+            "fields": self.fields(),
+        })
+    }
+}
+
+/// Formats one violated kernel option per line for `RequiredOptionsViolated`.
+fn format_violations(violations: &[OptionViolation]) -> String {
+    violations
+        .iter()
+        .map(|violation| format!("  - {violation}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Formats one path per line for `BundleChecksumMismatch`.
+fn format_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| format!("  - {}", path.display()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_error
+#[cfg(test)]
+mod tests_error {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+
+    fn command_failure(command: &str) -> KernelUpdaterError {
+        KernelUpdaterError::CommandExecutionError {
+            command: command.to_string(),
+            args: String::new(),
+            status: ExitStatus::from_raw(2 << 8),
+            stderr_tail: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_variant_exit_codes() {
+        let cases = [
+            (
+                KernelUpdaterError::VersionComparisonError {
+                    new: Version::new(6, 15, 3),
+                    old: Version::new(6, 15, 4),
+                },
+                KernelUpdaterError::EXIT_USAGE,
+            ),
+            (
+                KernelUpdaterError::MissingRequiredArgument {
+                    argument_name: "--old".to_string(),
+                    command: None,
+                },
+                KernelUpdaterError::EXIT_USAGE,
+            ),
+            (
+                KernelUpdaterError::KernelConfigNotFound {
+                    path: PathBuf::from("/proc/config.gz"),
+                },
+                KernelUpdaterError::EXIT_CONFIG,
+            ),
+            (
+                KernelUpdaterError::KernelBinaryNotFound {
+                    path: PathBuf::from("arch/x86/boot/bzImage"),
+                    src_dir: PathBuf::from("/usr/src/linux-6.15.4"),
+                    version: Version::new(6, 15, 4),
+                },
+                KernelUpdaterError::EXIT_COMPILE,
+            ),
+            (
+                KernelUpdaterError::DkmsModuleNotFound,
+                KernelUpdaterError::EXIT_DKMS,
+            ),
+            (
+                KernelUpdaterError::DkmsPinnedVersionNotRegistered {
+                    module: "nvidia".to_string(),
+                    version: "550.40.07".to_string(),
+                },
+                KernelUpdaterError::EXIT_DKMS,
+            ),
+            (
+                KernelUpdaterError::ModuleSignatureMissing {
+                    module: PathBuf::from("nvidia.ko"),
+                },
+                KernelUpdaterError::EXIT_SIGNING,
+            ),
+            (
+                KernelUpdaterError::BundleManifestInvalid {
+                    bundle: PathBuf::from("kernel.tar.zst"),
+                    reason: "missing version".to_string(),
+                },
+                KernelUpdaterError::EXIT_BUNDLE,
+            ),
+            (
+                KernelUpdaterError::PackageNotProduced {
+                    target: "bindeb-pkg".to_string(),
+                    dir: PathBuf::from(".."),
+                },
+                KernelUpdaterError::EXIT_PACKAGE,
+            ),
+            (command_failure("make"), KernelUpdaterError::EXIT_FAILURE),
+        ];
+        for (error, exit_code) in cases {
+            assert_eq!(error.exit_code(), exit_code, "{}", error.variant_name());
+        }
+    }
+
+    #[test]
+    fn test_phase_exit_codes() {
+        for (phase, exit_code) in [
+            (Phase::Setup, KernelUpdaterError::EXIT_FAILURE),
+            (Phase::Compile, KernelUpdaterError::EXIT_COMPILE),
+            (Phase::Install, KernelUpdaterError::EXIT_INSTALL),
+            (Phase::Dkms, KernelUpdaterError::EXIT_DKMS),
+            (Phase::Boot, KernelUpdaterError::EXIT_BOOT),
+            (Phase::Bundle, KernelUpdaterError::EXIT_BUNDLE),
+            (Phase::Package, KernelUpdaterError::EXIT_PACKAGE),
+        ] {
+            assert_eq!(
+                command_failure("make").in_phase(phase).exit_code(),
+                exit_code
+            );
+        }
+
+        // The variant category wins over the phase
+        let signing = KernelUpdaterError::ModuleSigningKeyNotFound {
+            path: PathBuf::from("certs/signing_key.pem"),
+        };
+        assert_eq!(
+            signing.in_phase(Phase::Install).exit_code(),
+            KernelUpdaterError::EXIT_SIGNING
+        );
+    }
+
+    #[test]
+    fn test_phase_in_message() {
+        let error = command_failure("mkinitcpio").in_phase(Phase::Boot);
+        assert!(
+            error
+                .to_string()
+                .starts_with("Boot configuration failed: Command 'mkinitcpio ' failed")
+        );
+
+        // The first phase is kept
+        let error = error.in_phase(Phase::Dkms);
+        assert!(matches!(
+            error,
+            KernelUpdaterError::PhaseFailed {
+                phase: Phase::Boot,
+                ..
+            }
+        ));
+        assert_eq!(error.fields()["phase"], "boot");
+        assert_eq!(error.fields()["source"]["variant"], "CommandExecutionError");
+    }
 }
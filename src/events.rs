@@ -0,0 +1,315 @@
+use crate::{
+    args::OutputFormat,
+    error::KernelUpdaterError,
+    logging::{format_timestamp, output_format},
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+/// Version of the `--output json` event schema, written in every event.
+/// Bumped on any incompatible change (renamed or removed event or field).
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// Machine readable progress event printed as one JSON line on stdout with `--output json`.
+///
+/// Every line is an object with `schema`, `timestamp` (ISO 8601 UTC) and `event`
+/// (the snake_case variant name) followed by the variant fields; see the README for
+/// the documented schema.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// First event of a run.
+    RunStarted {
+        command: Option<String>,
+        version_new: Option<String>,
+        version_old: Option<String>,
+        run_log: Option<PathBuf>,
+    },
+    /// A phase (e.g. `Kernel Compilation`) started.
+    PhaseStarted { phase: String },
+    /// A phase ended, either because the next one started or the run ended.
+    PhaseFinished { phase: String, duration_secs: f64 },
+    /// An external command exited. `exit_code` is `null` when killed by a signal.
+    Command {
+        command: String,
+        args: Vec<String>,
+        exit_code: Option<i32>,
+        success: bool,
+        duration_secs: f64,
+    },
+    /// A non-fatal problem, e.g. a skipped DKMS module.
+    Warning { message: String },
+    /// Last event of a run; `error` is set when it failed.
+    Result {
+        success: bool,
+        duration_secs: f64,
+        error: Option<ErrorReport>,
+    },
+}
+
+/// A [`KernelUpdaterError`] as reported in the `result` event.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ErrorReport {
+    /// Variant name, e.g. `CommandExecutionError`.
+    pub variant: String,
+    /// Human readable message (the `Display` of the error).
+    pub message: String,
+    /// Variant fields, see [`KernelUpdaterError::fields`].
+    pub fields: Value,
+    /// Exit code of the process, see [`KernelUpdaterError::exit_code`].
+    pub exit_code: i32,
+    /// Remediation hint, see [`KernelUpdaterError::hint`].
+    pub hint: Option<String>,
+    /// Pipeline phase the error occurred in (see [`Phase::name`](crate::error::Phase::name)), if known.
+    pub phase: Option<String>,
+}
+
+impl ErrorReport {
+    /// Creates the report of an error. An error wrapped in `PhaseFailed` is reported with
+    /// the variant and fields of the original error, and the phase on its own.
+    pub fn from_error(error: &KernelUpdaterError) -> Self {
+        let (phase, cause) = match error {
+            KernelUpdaterError::PhaseFailed { phase, source } => {
+                (Some(phase.name().to_string()), source.as_ref())
+            }
+            error => (None, error),
+        };
+        Self {
+            variant: cause.variant_name().to_string(),
+            message: error.to_string(),
+            fields: cause.fields(),
+            exit_code: error.exit_code(),
+            hint: error.hint(),
+            phase,
+        }
+    }
+}
+
+/// Line wrapper putting the schema version and timestamp in front of the event fields.
+#[derive(Serialize)]
+struct Envelope<'a> {
+    schema: u32,
+    timestamp: String,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+impl Event {
+    /// Serializes the event as a single JSON line (without the trailing newline).
+    pub fn to_json_line(&self, time: SystemTime) -> String {
+        let envelope = Envelope {
+            schema: EVENT_SCHEMA_VERSION,
+            timestamp: format_timestamp(time),
+            event: self,
+        };
+        // Serializing plain structs, strings and numbers cannot fail
+        serde_json::to_string(&envelope).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Events emitted on this thread while a test runs [`capture_events`].
+    static CAPTURED_EVENTS: std::cell::RefCell<Option<Vec<Event>>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Runs `f` and returns the events it emitted on this thread instead of printing them.
+#[cfg(test)]
+pub(crate) fn capture_events(f: impl FnOnce()) -> Vec<Event> {
+    CAPTURED_EVENTS.with_borrow_mut(|events| *events = Some(Vec::new()));
+    f();
+    CAPTURED_EVENTS
+        .with_borrow_mut(Option::take)
+        .unwrap_or_default()
+}
+
+/// Prints the event on stdout when `--output json` is selected, does nothing otherwise.
+pub fn emit_event(event: Event) {
+    #[cfg(test)]
+    let event = match CAPTURED_EVENTS.with_borrow_mut(|events| match events {
+        Some(events) => {
+            events.push(event);
+            None
+        }
+        None => Some(event),
+    }) {
+        Some(event) => event,
+        None => return,
+    };
+
+    if output_format() != OutputFormat::Json {
+        return;
+    }
+    let line = event.to_json_line(SystemTime::now());
+    let mut stdout = io::stdout().lock();
+    // A closed pipe on the consumer side must not abort the run
+    let _ = writeln!(stdout, "{line}");
+    let _ = stdout.flush();
+}
+
+/// Duration in seconds rounded to milliseconds, as written in events.
+pub fn duration_secs(duration: Duration) -> f64 {
+    (duration.as_millis() as f64) / 1000.0
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_events
+#[cfg(test)]
+mod tests_events {
+    use super::*;
+    use crate::{Commands, Version, error::Phase};
+    use std::{os::unix::process::ExitStatusExt, process::ExitStatus};
+
+    fn time() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_750_000_000)
+    }
+
+    #[test]
+    fn test_progress_events_schema() {
+        let started = Event::RunStarted {
+            command: Some(Commands::KernelCompile.name().to_string()),
+            version_new: Some(Version::new(6, 15, 4).to_string()),
+            version_old: None,
+            run_log: Some(PathBuf::from("/var/log/kernel-updater/runs/run-1.log")),
+        };
+        assert_eq!(
+            started.to_json_line(time()),
+            r#"{"schema":1,"timestamp":"2025-06-15T15:06:40Z","event":"run_started","command":"kernel-compile","version_new":"6.15.4","version_old":null,"run_log":"/var/log/kernel-updater/runs/run-1.log"}"#
+        );
+
+        let phase = Event::PhaseFinished {
+            phase: "Kernel Compilation".to_string(),
+            duration_secs: duration_secs(Duration::from_micros(1_234_567)),
+        };
+        assert_eq!(
+            phase.to_json_line(time()),
+            r#"{"schema":1,"timestamp":"2025-06-15T15:06:40Z","event":"phase_finished","phase":"Kernel Compilation","duration_secs":1.234}"#
+        );
+
+        let command = Event::Command {
+            command: "make".to_string(),
+            args: vec!["-j8".to_string(), "bzImage".to_string()],
+            exit_code: Some(0),
+            success: true,
+            duration_secs: 2.5,
+        };
+        assert_eq!(
+            command.to_json_line(time()),
+            r#"{"schema":1,"timestamp":"2025-06-15T15:06:40Z","event":"command","command":"make","args":["-j8","bzImage"],"exit_code":0,"success":true,"duration_secs":2.5}"#
+        );
+
+        let warning = Event::Warning {
+            message: "DKMS module skipped".to_string(),
+        };
+        assert_eq!(
+            warning.to_json_line(time()),
+            r#"{"schema":1,"timestamp":"2025-06-15T15:06:40Z","event":"warning","message":"DKMS module skipped"}"#
+        );
+    }
+
+    #[test]
+    fn test_result_event_schema() {
+        let success = Event::Result {
+            success: true,
+            duration_secs: 10.0,
+            error: None,
+        };
+        assert_eq!(
+            success.to_json_line(time()),
+            r#"{"schema":1,"timestamp":"2025-06-15T15:06:40Z","event":"result","success":true,"duration_secs":10.0,"error":null}"#
+        );
+
+        let error = KernelUpdaterError::CommandExecutionError {
+            command: "mkinitcpio".to_string(),
+            args: "-p linux-6.15.4".to_string(),
+            status: ExitStatus::from_raw(1 << 8),
+            stderr_tail: vec!["==> ERROR: Failed to load preset".to_string()],
+        };
+        let failure = Event::Result {
+            success: false,
+            duration_secs: 1.5,
+            error: Some(ErrorReport::from_error(&error)),
+        };
+        assert_eq!(
+            failure.to_json_line(time()),
+            format!(
+                r#"{{"schema":1,"timestamp":"2025-06-15T15:06:40Z","event":"result","success":false,"duration_secs":1.5,"error":{{"variant":"CommandExecutionError","message":"{error}","fields":{{"args":"-p linux-6.15.4","command":"mkinitcpio","status":1,"stderr_tail":["==> ERROR: Failed to load preset"]}},"exit_code":1,"hint":"{hint}","phase":null}}}}"#,
+                hint = error.hint().unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_phase_error_report() {
+        let error = KernelUpdaterError::CommandExecutionError {
+            command: "mkinitcpio".to_string(),
+            args: "-p linux-6.15.4".to_string(),
+            status: ExitStatus::from_raw(1 << 8),
+            stderr_tail: vec!["==> ERROR: Failed to load preset".to_string()],
+        }
+        .in_phase(Phase::Boot);
+        let failure = Event::Result {
+            success: false,
+            duration_secs: 1.5,
+            error: Some(ErrorReport::from_error(&error)),
+        };
+        // The original variant and fields stay at the top level, as without a phase
+        assert_eq!(
+            failure.to_json_line(time()),
+            format!(
+                r#"{{"schema":1,"timestamp":"2025-06-15T15:06:40Z","event":"result","success":false,"duration_secs":1.5,"error":{{"variant":"CommandExecutionError","message":"{error}","fields":{{"args":"-p linux-6.15.4","command":"mkinitcpio","status":1,"stderr_tail":["==> ERROR: Failed to load preset"]}},"exit_code":{exit_code},"hint":"{hint}","phase":"boot"}}}}"#,
+                exit_code = KernelUpdaterError::EXIT_BOOT,
+                hint = error.hint().unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_error_report_fields() {
+        let error = KernelUpdaterError::MissingRequiredArgument {
+            argument_name: "--old".to_string(),
+            command: Some(Commands::DkmsInstall),
+        };
+        let report = ErrorReport::from_error(&error);
+        assert_eq!(report.variant, "MissingRequiredArgument");
+        assert_eq!(
+            report.fields,
+            serde_json::json!({"argument_name": "--old", "command": "dkms-install"})
+        );
+
+        let nested = KernelUpdaterError::DkmsBuildFailed {
+            module: "nvidia".to_string(),
+            version: "550.78".to_string(),
+            kernel: "6.15.4-TestSuffix".to_string(),
+            log_path: Some(PathBuf::from("/var/lib/dkms/nvidia/550.78/build/make.log")),
+            source: Box::new(KernelUpdaterError::CommandExecutionError {
+                command: "dkms".to_string(),
+                args: "build".to_string(),
+                status: ExitStatus::from_raw(9),
+                stderr_tail: Vec::new(),
+            }),
+        };
+        let fields = ErrorReport::from_error(&nested).fields;
+        assert_eq!(fields["source"]["variant"], "CommandExecutionError");
+        // Killed by a signal: no exit code
+        assert_eq!(fields["source"]["fields"]["status"], Value::Null);
+        assert_eq!(
+            fields["log_path"],
+            "/var/lib/dkms/nvidia/550.78/build/make.log"
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{Config, error::KernelUpdaterError, info, utils::copy_tree};
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+/// Source tree files needed by Kbuild to build external modules.
+const SOURCE_FILES: &[&str] = &["Makefile", "kernel/Makefile"];
+
+/// Source tree directories copied whole (the arch `include` directory is added per target).
+const SOURCE_DIRS: &[&str] = &["include", "scripts"];
+
+/// Directories whose private headers some external modules include directly.
+const SOURCE_HEADER_DIRS: &[&str] = &["drivers/md", "net/mac80211"];
+
+/// Generated files copied from the build tree when present (`vmlinux` is needed for module BTF).
+const BUILD_FILES: &[&str] = &[
+    ".config",
+    "Module.symvers",
+    "System.map",
+    "vmlinux",
+    "tools/objtool/objtool",
+    "tools/bpf/resolve_btfids/resolve_btfids",
+];
+
+/// Build tree files without which no external module can be built.
+const REQUIRED_BUILD_FILES: &[&str] = &[".config", "Module.symvers"];
+
+/// Generated build tree directories (config, generated headers and host tools).
+const BUILD_DIRS: &[&str] = &["include/config", "include/generated", "scripts"];
+
+/// Pruned kernel headers tree: the subset of the source and build trees needed to
+/// build external modules (DKMS) against the new kernel, without the full sources.
+///
+/// Mirrors what distributions ship as `linux-headers`: Makefiles, `include/`, `scripts/`,
+/// every `Kconfig*` file and the generated files (`.config`, `Module.symvers`, ...)
+/// for the target architecture only.
+pub struct KernelHeaders<'a> {
+    config: &'a Config,
+}
+
+impl<'a> KernelHeaders<'a> {
+    /// Creates a new `KernelHeaders` instance.
+    pub fn new(config: &'a Config) -> Self {
+        Self { config }
+    }
+
+    /// Copies the headers tree into `dest`, replacing any previous content.
+    /// Returns the number of files copied.
+    pub fn install(&self, dest: &Path) -> Result<usize, KernelUpdaterError> {
+        let src_dir = &self.config.kernel_src_dir_path;
+        let build_dir = &self.config.kernel_build_dir_path;
+        let kernel_arch = self.config.arch.kernel_arch();
+        let arch_dir = format!("arch/{kernel_arch}");
+
+        for required in REQUIRED_BUILD_FILES {
+            let path = build_dir.join(required);
+            if !path.is_file() {
+                return Err(KernelUpdaterError::IOError {
+                    path,
+                    io_error: io::Error::new(
+                        ErrorKind::NotFound,
+                        "required to build external modules; is the kernel compiled?",
+                    ),
+                });
+            }
+        }
+
+        info!("Installing kernel headers to: {}", dest.display());
+        if dest.exists() {
+            fs::remove_dir_all(dest)?;
+        }
+        fs::create_dir_all(dest)?;
+
+        let mut copied = 0;
+
+        // Sources first: for in-tree builds the generated files below simply overwrite them
+        let arch_makefile = format!("{arch_dir}/Makefile");
+        for file in SOURCE_FILES.iter().copied().chain([arch_makefile.as_str()]) {
+            copied += copy_tree(&src_dir.join(file), &dest.join(file))?;
+        }
+        let arch_include = format!("{arch_dir}/include");
+        for dir in SOURCE_DIRS.iter().copied().chain([arch_include.as_str()]) {
+            copied += copy_tree(&src_dir.join(dir), &dest.join(dir))?;
+        }
+        for dir in SOURCE_HEADER_DIRS {
+            copied += copy_headers(&src_dir.join(dir), &dest.join(dir))?;
+        }
+        copied += copy_kconfig_files(src_dir, src_dir, dest, kernel_arch)?;
+
+        let arch_generated = format!("{arch_dir}/include/generated");
+        for dir in BUILD_DIRS.iter().copied().chain([arch_generated.as_str()]) {
+            copied += copy_tree(&build_dir.join(dir), &dest.join(dir))?;
+        }
+        for file in BUILD_FILES {
+            copied += copy_tree(&build_dir.join(file), &dest.join(file))?;
+        }
+
+        info!("Installed {copied} header files.");
+        Ok(copied)
+    }
+}
+
+/// Copies the `*.h` files found directly in `source`.
+fn copy_headers(source: &Path, target: &Path) -> Result<usize, KernelUpdaterError> {
+    if !source.is_dir() {
+        return Ok(0);
+    }
+
+    let mut copied = 0;
+    for entry in fs::read_dir(source)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "h")
+            && let Some(file_name) = path.file_name()
+        {
+            copied += copy_tree(&path, &target.join(file_name))?;
+        }
+    }
+    Ok(copied)
+}
+
+/// Copies every `Kconfig*` file under `dir`, keeping its path relative to `root`.
+/// Other architectures under `arch/` and VCS metadata are skipped.
+fn copy_kconfig_files(
+    root: &Path,
+    dir: &Path,
+    dest: &Path,
+    kernel_arch: &str,
+) -> Result<usize, KernelUpdaterError> {
+    let mut copied = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            let other_arch = dir == root.join("arch") && name != kernel_arch;
+            if name.starts_with('.') || other_arch {
+                continue;
+            }
+            copied += copy_kconfig_files(root, &path, dest, kernel_arch)?;
+        } else if file_type.is_file()
+            && name.starts_with("Kconfig")
+            && let Ok(relative) = path.strip_prefix(root)
+        {
+            copied += copy_tree(&path, &dest.join(relative))?;
+        }
+    }
+    Ok(copied)
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_headers
+#[cfg(test)]
+mod tests_headers {
+    use super::*;
+    use crate::Commands;
+    use crate::test_utils::{TempDirGuard, staged_args};
+
+    /// Out-of-tree x86_64 build staged inside the temporary directory.
+    fn create_mock_config(temp_dir: &Path) -> Config {
+        let mut args = staged_args(temp_dir, Commands::KernelCompile);
+        args.build.build_dir = Some(temp_dir.join("build"));
+        Config::new(args).expect("Failed to create standard Config")
+    }
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_install_prunes_sources() {
+        let temp_dir = TempDirGuard::new("headers-install");
+        let config = create_mock_config(&temp_dir.path);
+        let src = &config.kernel_src_dir_path;
+        let build = &config.kernel_build_dir_path;
+
+        write(&src.join("Makefile"), "VERSION = 6\n");
+        write(&src.join("Kconfig"), "source \"arch/Kconfig\"\n");
+        write(&src.join("arch/x86/Makefile"), "");
+        write(&src.join("arch/x86/Kconfig"), "");
+        write(&src.join("arch/x86/include/asm/io.h"), "");
+        write(&src.join("arch/arm64/Kconfig"), "");
+        write(&src.join("include/linux/module.h"), "");
+        write(&src.join("drivers/md/dm.h"), "");
+        write(&src.join("drivers/md/dm.c"), "");
+        write(&src.join("drivers/gpu/Kconfig.debug"), "");
+        write(&src.join("kernel/sched/core.c"), "");
+        write(&build.join(".config"), "CONFIG_MODULES=y\n");
+        write(&build.join("Module.symvers"), "");
+        write(&build.join("include/generated/autoconf.h"), "");
+        write(&build.join("arch/x86/include/generated/asm/orc.h"), "");
+        write(&build.join("scripts/mod/modpost"), "");
+
+        let dest = temp_dir.path.join("usr/src/linux-6.15.4-TestSuffix");
+        let copied = KernelHeaders::new(&config).install(&dest).unwrap();
+
+        for present in [
+            "Makefile",
+            "Kconfig",
+            "arch/x86/Makefile",
+            "arch/x86/Kconfig",
+            "arch/x86/include/asm/io.h",
+            "arch/x86/include/generated/asm/orc.h",
+            "include/linux/module.h",
+            "include/generated/autoconf.h",
+            "drivers/md/dm.h",
+            "drivers/gpu/Kconfig.debug",
+            "scripts/mod/modpost",
+            ".config",
+            "Module.symvers",
+        ] {
+            assert!(dest.join(present).exists(), "{present} should be installed");
+        }
+        for absent in [
+            "arch/arm64/Kconfig",
+            "drivers/md/dm.c",
+            "kernel/sched/core.c",
+        ] {
+            assert!(!dest.join(absent).exists(), "{absent} should be pruned");
+        }
+        assert_eq!(copied, 13);
+    }
+
+    #[test]
+    fn test_install_requires_compiled_tree() {
+        let temp_dir = TempDirGuard::new("headers-uncompiled");
+        let config = create_mock_config(&temp_dir.path);
+        write(&config.kernel_build_dir_path.join(".config"), "");
+
+        let err = KernelHeaders::new(&config)
+            .install(&temp_dir.path.join("headers"))
+            .unwrap_err();
+        assert!(
+            matches!(&err, KernelUpdaterError::IOError { path, .. } if path.ends_with("Module.symvers"))
+        );
+    }
+}
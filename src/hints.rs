@@ -0,0 +1,336 @@
+use crate::error::KernelUpdaterError;
+use std::{io::ErrorKind, os::unix::process::ExitStatusExt, process::ExitStatus};
+
+/// Build tools a failing command may report missing, with the Arch package providing them.
+const TOOL_PACKAGES: &[(&str, &str)] = &[
+    ("bc", "bc"),
+    ("bison", "bison"),
+    ("bsdtar", "libarchive"),
+    ("cpio", "cpio"),
+    ("dpkg-buildpackage", "dpkg"),
+    ("flex", "flex"),
+    ("lz4", "lz4"),
+    ("pahole", "pahole"),
+    ("perl", "perl"),
+    ("rpmbuild", "rpm-tools"),
+    ("xz", "xz"),
+    ("zstd", "zstd"),
+];
+
+/// Messages a failing command may print on stderr, with the hint they lead to.
+const STDERR_HINTS: &[(&str, &str)] = &[
+    (
+        "No space left on device",
+        "The disk is full: free space in the source, build and /boot directories (a kernel build needs 20+ GB).",
+    ),
+    (
+        "openssl/opensslv.h",
+        "Install package `openssl`, needed for module signing (`pacman -S openssl`).",
+    ),
+    (
+        "gelf.h",
+        "Install package `libelf`, needed by objtool (`pacman -S libelf`).",
+    ),
+    (
+        "pahole (pahole) is not available",
+        "Install package `pahole`, needed for CONFIG_DEBUG_INFO_BTF (`pacman -S pahole`).",
+    ),
+    ("must be run as root", "Run kernel-updater with sudo."),
+    (
+        "Permission denied",
+        "Run kernel-updater with sudo (installing writes to /usr/src, /lib/modules and /boot).",
+    ),
+    (
+        "Operation not permitted",
+        "Run kernel-updater with sudo (installing writes to /usr/src, /lib/modules and /boot).",
+    ),
+];
+
+/// Programs whose failures have a hint of their own, found even behind wrappers
+/// such as `arch-chroot <root>` or `nice -n <N>`.
+const KNOWN_PROGRAMS: &[&str] = &[
+    "make",
+    "mkinitcpio",
+    "update-grub",
+    "grub-mkconfig",
+    "curl",
+    "wget",
+    "dkms",
+];
+
+impl KernelUpdaterError {
+    /// Remediation hint shown after the error: derived from the variant and, for failed
+    /// commands, from the program, its exit status and the last lines of its stderr.
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Self::IoError(io_error) | Self::IOError { io_error, .. } => match io_error.kind() {
+                ErrorKind::PermissionDenied => Some(
+                    "Run kernel-updater with sudo (installing writes to /usr/src, /lib/modules and /boot).".to_string(),
+                ),
+                ErrorKind::NotFound => Some(
+                    "A file or program was not found; check the prerequisites in the README (`base-devel`, `bc`, `cpio`, `pahole`, ...).".to_string(),
+                ),
+                _ => None,
+            },
+            Self::CommandExecutionError {
+                command,
+                args,
+                status,
+                stderr_tail,
+            } => command_hint(command, args, *status, stderr_tail),
+            Self::Utf8OutputError { .. }
+            | Self::DkmsStatusParseError { .. }
+            | Self::RunReportSerializationError { .. } => None,
+            Self::VersionComparisonError { .. } => Some(
+                "Pass the installed kernel as --old (see `uname -r`) and a newer release as --new.".to_string(),
+            ),
+            Self::MissingRequiredArgument { argument_name, .. } => Some(format!(
+                "Add {argument_name} <X.Y.Z>, e.g. the running kernel version from `uname -r`."
+            )),
+            Self::DkmsModuleNotFound => Some(
+                "Install the driver's DKMS package (e.g. `nvidia-dkms`), check `dkms status`, or select other modules with --dkms-module.".to_string(),
+            ),
+            Self::DkmsModuleSpecParseError { .. } => Some(
+                "Pass modules as --dkms-module zfs or --dkms-module nvidia@550.40.07:required.".to_string(),
+            ),
+            Self::DkmsPinnedVersionNotRegistered { module, version } => Some(format!(
+                "Register it with `dkms add {module}/{version}`, pin a version listed by `dkms status`, or mark it `{module}@{version}:optional`."
+            )),
+            Self::DkmsModuleIncompatible { module, kernel, .. } => Some(format!(
+                "Update `{module}` to a release supporting {kernel}, or mark it `{module}:optional` to install the kernel without it."
+            )),
+            Self::DkmsBuildFailed { module, kernel, .. } => Some(format!(
+                "Read the build log above; a newer `{module}` release usually fixes builds against new kernels. Mark it `{module}:optional` to install {kernel} without it."
+            )),
+            Self::ModuleSigningKeyNotFound { .. } => Some(
+                "Enable CONFIG_MODULE_SIG_ALL so the build generates certs/signing_key.pem, or pass --mok-key and --mok-cert.".to_string(),
+            ),
+            Self::ModuleSignatureMissing { .. } => Some(
+                "Check that the signing key and certificate match and that CONFIG_MODULE_SIG_HASH names an algorithm openssl supports.".to_string(),
+            ),
+            Self::KernelConfigNotFound { path } => Some(format!(
+                "Create it from the running kernel (`zcat /proc/config.gz > {}`) or pass --seed-config running.",
+                path.display()
+            )),
+            Self::SeedConfigNotFound { .. } => Some(
+                "Copy a kernel config to one of the listed paths, or try the other --seed-config source.".to_string(),
+            ),
+            Self::NonInteractiveEditConfig { .. } => Some(
+                "Run from an interactive terminal, or drop --edit-config for unattended runs.".to_string(),
+            ),
+            Self::LsmodSnapshotNotFound { path } => Some(format!(
+                "Record one with `lsmod > {}` while every device you need is in use.",
+                path.display()
+            )),
+            Self::ConfigFragmentNotFound { .. } => Some(
+                "Check the --config-fragment path, or that <config base>/profiles/<NAME>/ exists for --config-profile.".to_string(),
+            ),
+            Self::OptionRuleParseError { .. } => Some(
+                "Pass rules as --require-option CONFIG_X, !CONFIG_X or CONFIG_X=value.".to_string(),
+            ),
+            Self::RequiredOptionsViolated { .. } => Some(
+                "Set the options with a --config-fragment (or --edit-config), or enable the options they depend on.".to_string(),
+            ),
+            Self::KernelNotConfigured { .. } | Self::KernelBinaryNotFound { .. } => Some(
+                "Run `kernel-updater -n <version> kernel-compile` first and check that it succeeds.".to_string(),
+            ),
+            Self::PackageNotProduced { .. } => Some(
+                "Check the make output above; `deb` needs `dpkg-buildpackage` and `rpm` needs `rpmbuild`.".to_string(),
+            ),
+            Self::BundleManifestInvalid { .. } | Self::BundleChecksumMismatch { .. } => Some(
+                "The bundle is damaged or incomplete: copy it again, or recreate it with the `bundle` command.".to_string(),
+            ),
+            Self::BundleMismatch { field, .. } => Some(format!(
+                "Install the bundle built for this kernel, or pass the {field} it was built for."
+            )),
+            Self::VersionParseIntError { .. } | Self::VersionParseFormatError { .. } => Some(
+                "Write versions as Major.Minor.Patch, e.g. 6.15.4.".to_string(),
+            ),
+            Self::PhaseFailed { source, .. } => source.hint(),
+        }
+    }
+}
+
+/// Hint for a failed command: known stderr messages first, then the program and its exit status.
+fn command_hint(
+    command: &str,
+    args: &str,
+    status: ExitStatus,
+    stderr_tail: &[String],
+) -> Option<String> {
+    if let Some(hint) = missing_tool_hint(stderr_tail) {
+        return Some(hint);
+    }
+
+    let program = program_name(command, args);
+    if program == "mkinitcpio" && stderr_tail.iter().any(|line| line.contains("preset")) {
+        let preset = args
+            .split_whitespace()
+            .skip_while(|arg| *arg != "-p")
+            .nth(1)
+            .unwrap_or("<preset>");
+        return Some(format!(
+            "Create the preset /etc/mkinitcpio.d/{preset}.preset: copy /etc/mkinitcpio.d/linux.preset \
+            and point ALL_kver, default_image and fallback_image at the new kernel."
+        ));
+    }
+
+    for (pattern, hint) in STDERR_HINTS {
+        if stderr_tail.iter().any(|line| line.contains(pattern)) {
+            return Some(hint.to_string());
+        }
+    }
+
+    if status.signal() == Some(9) {
+        return Some(
+            "The command was killed (SIGKILL), usually by the OOM killer: lower --jobs or raise --mem-per-job."
+                .to_string(),
+        );
+    }
+
+    match (program, status.code()) {
+        ("curl", Some(6 | 7 | 28)) | ("wget", Some(4)) => Some(
+            "cdn.kernel.org could not be reached: check the network connection and proxy settings."
+                .to_string(),
+        ),
+        ("curl", Some(22)) | ("wget", Some(8)) => Some(
+            "The tarball was not found on kernel.org: check that the --new version has been released."
+                .to_string(),
+        ),
+        ("make", Some(2)) => Some(
+            "Look for the first `error:` line above; the full build output is in the run log."
+                .to_string(),
+        ),
+        ("update-grub" | "grub-mkconfig", _) => Some(
+            "Run `grub-mkconfig -o /boot/grub/grub.cfg` to see the error, and check /etc/default/grub."
+                .to_string(),
+        ),
+        _ => None,
+    }
+}
+
+/// Hint to install the package of a build tool the command reported missing.
+fn missing_tool_hint(stderr_tail: &[String]) -> Option<String> {
+    stderr_tail.iter().find_map(|line| {
+        TOOL_PACKAGES.iter().find_map(|(tool, package)| {
+            [
+                "not found",
+                "command not found",
+                "No such file or directory",
+            ]
+            .iter()
+            .any(|reason| line.contains(&format!("{tool}: {reason}")))
+            .then(|| {
+                format!(
+                    "Install package `{package}`, which provides `{tool}` (`pacman -S {package}`)."
+                )
+            })
+        })
+    })
+}
+
+/// The program a command line actually runs, looking past wrappers for known programs.
+fn program_name<'a>(command: &'a str, args: &'a str) -> &'a str {
+    std::iter::once(command)
+        .chain(args.split_whitespace())
+        .find(|token| KNOWN_PROGRAMS.contains(token))
+        .unwrap_or(command)
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_hints
+#[cfg(test)]
+mod tests_hints {
+    use super::*;
+    use crate::Phase;
+    use std::{io, path::PathBuf};
+
+    fn command_failure(
+        command: &str,
+        args: &str,
+        status: i32,
+        stderr: &[&str],
+    ) -> KernelUpdaterError {
+        KernelUpdaterError::CommandExecutionError {
+            command: command.to_string(),
+            args: args.to_string(),
+            status: ExitStatus::from_raw(status),
+            stderr_tail: stderr.iter().map(|line| line.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_command_hints() {
+        let bc = command_failure(
+            "make",
+            "-j8 bzImage",
+            2 << 8,
+            &[
+                "/bin/sh: 1: bc: not found",
+                "make[1]: *** [Kbuild:24: include/generated/timeconst.h] Error 127",
+            ],
+        );
+        assert_eq!(
+            bc.hint().unwrap(),
+            "Install package `bc`, which provides `bc` (`pacman -S bc`)."
+        );
+
+        let preset = command_failure(
+            "arch-chroot",
+            "/mnt mkinitcpio -p linux615_Custom",
+            1 << 8,
+            &["==> ERROR: Failed to load preset: `/etc/mkinitcpio.d/linux615_Custom.preset'"],
+        );
+        assert!(
+            preset
+                .hint()
+                .unwrap()
+                .starts_with("Create the preset /etc/mkinitcpio.d/linux615_Custom.preset")
+        );
+
+        let sudo = command_failure("ln", "-s a b", 1 << 8, &["ln: failed: Permission denied"]);
+        assert!(
+            sudo.hint()
+                .unwrap()
+                .starts_with("Run kernel-updater with sudo")
+        );
+
+        let oom = command_failure("nice", "-n 10 make -j64", 9, &[]);
+        assert!(oom.hint().unwrap().contains("--jobs"));
+
+        let not_released =
+            command_failure("curl", "-fL https://cdn.kernel.org/x -o x", 22 << 8, &[]);
+        assert!(not_released.hint().unwrap().contains("--new version"));
+
+        assert_eq!(
+            command_failure("true", "", 1 << 8, &["unknown"]).hint(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_variant_hints() {
+        let missing = KernelUpdaterError::KernelConfigNotFound {
+            path: PathBuf::from("/usr/src/config-Custom"),
+        };
+        assert_eq!(
+            missing.hint().unwrap(),
+            "Create it from the running kernel (`zcat /proc/config.gz > /usr/src/config-Custom`) or pass --seed-config running."
+        );
+
+        let denied = KernelUpdaterError::IoError(io::Error::from(ErrorKind::PermissionDenied));
+        assert!(denied.hint().unwrap().contains("sudo"));
+
+        // Phases keep the hint of the error they wrap
+        let wrapped = missing.in_phase(Phase::Compile);
+        assert!(wrapped.hint().unwrap().contains("--seed-config"));
+    }
+}
@@ -0,0 +1,731 @@
+use crate::{AtomicWriteExt, error::KernelUpdaterError};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt, fs,
+    path::Path,
+};
+
+/// Prefix of every kernel configuration symbol in a `.config` file.
+const SYMBOL_PREFIX: &str = "CONFIG_";
+
+/// Value assigned to a symbol in a kernel `.config` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigValue {
+    /// `CONFIG_X=y`
+    Yes,
+    /// `CONFIG_X=m`
+    Module,
+    /// `# CONFIG_X is not set`
+    NotSet,
+    /// `CONFIG_X="text"` (stored unquoted and unescaped)
+    Str(String),
+    /// `CONFIG_X=123` or `CONFIG_X=0x1000` (stored verbatim)
+    Number(String),
+}
+
+impl ConfigValue {
+    /// Parses the right-hand side of a `CONFIG_X=...` assignment.
+    fn parse(raw: &str) -> Self {
+        let raw = raw.trim();
+        match raw {
+            "y" => Self::Yes,
+            "m" => Self::Module,
+            "n" => Self::NotSet,
+            _ => match raw.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                Some(quoted) => Self::Str(quoted.replace("\\\"", "\"").replace("\\\\", "\\")),
+                None => Self::Number(raw.to_string()),
+            },
+        }
+    }
+
+    /// Checks if the option is built in or built as a module.
+    pub fn is_enabled(&self) -> bool {
+        matches!(self, Self::Yes | Self::Module)
+    }
+}
+
+impl fmt::Display for ConfigValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Yes => write!(f, "y"),
+            Self::Module => write!(f, "m"),
+            Self::NotSet => write!(f, "n"),
+            Self::Str(text) => write!(f, "\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"")),
+            Self::Number(number) => write!(f, "{number}"),
+        }
+    }
+}
+
+/// A single line of a `.config` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigLine {
+    /// A symbol assignment, including the `# CONFIG_X is not set` form.
+    Symbol { name: String, value: ConfigValue },
+    /// Comments, section headers and blank lines, kept verbatim.
+    Other(String),
+}
+
+impl fmt::Display for ConfigLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Symbol {
+                name,
+                value: ConfigValue::NotSet,
+            } => write!(f, "# {name} is not set"),
+            Self::Symbol { name, value } => write!(f, "{name}={value}"),
+            Self::Other(text) => write!(f, "{text}"),
+        }
+    }
+}
+
+/// Parsed kernel configuration (`.config`) preserving line order and comments.
+///
+/// Untouched lines are serialized back exactly as they were read, so parsing and
+/// writing a file without edits reproduces it byte for byte.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KernelConfig {
+    lines: Vec<ConfigLine>,
+    /// Original text of each line, cleared when the line is edited.
+    raw: Vec<Option<String>>,
+    index: HashMap<String, usize>,
+    trailing_newline: bool,
+}
+
+impl KernelConfig {
+    /// Reads and parses a `.config` file.
+    pub fn from_file(path: &Path) -> Result<Self, KernelUpdaterError> {
+        let content = fs::read_to_string(path).map_err(|io_error| KernelUpdaterError::IOError {
+            path: path.to_path_buf(),
+            io_error,
+        })?;
+        Ok(Self::parse(&content))
+    }
+
+    /// Parses the content of a `.config` file.
+    pub fn parse(content: &str) -> Self {
+        let mut config = Self {
+            trailing_newline: content.ends_with('\n'),
+            ..Self::default()
+        };
+        for line in content.lines() {
+            config.push_line(Self::parse_line(line), Some(line.to_string()));
+        }
+        config
+    }
+
+    /// Classifies a single line of a `.config` file.
+    fn parse_line(line: &str) -> ConfigLine {
+        let trimmed = line.trim();
+
+        if let Some(rest) = trimmed.strip_prefix("# ")
+            && let Some(name) = rest.strip_suffix(" is not set")
+            && name.starts_with(SYMBOL_PREFIX)
+            && !name.contains(char::is_whitespace)
+        {
+            return ConfigLine::Symbol {
+                name: name.to_string(),
+                value: ConfigValue::NotSet,
+            };
+        }
+
+        if trimmed.starts_with(SYMBOL_PREFIX)
+            && let Some((name, value)) = trimmed.split_once('=')
+        {
+            return ConfigLine::Symbol {
+                name: name.trim().to_string(),
+                value: ConfigValue::parse(value),
+            };
+        }
+
+        ConfigLine::Other(line.to_string())
+    }
+
+    /// Appends a line, keeping the symbol index up to date (the last assignment wins).
+    fn push_line(&mut self, line: ConfigLine, raw: Option<String>) {
+        if let ConfigLine::Symbol { name, .. } = &line {
+            self.index.insert(name.clone(), self.lines.len());
+        }
+        self.lines.push(line);
+        self.raw.push(raw);
+    }
+
+    /// Returns the value of a symbol (e.g. `CONFIG_DRM`), if it appears in the file.
+    pub fn get(&self, name: &str) -> Option<&ConfigValue> {
+        match self.lines.get(*self.index.get(name)?) {
+            Some(ConfigLine::Symbol { value, .. }) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Checks if a symbol is set to `y` or `m`.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.get(name).is_some_and(ConfigValue::is_enabled)
+    }
+
+    /// Iterates over every symbol in file order.
+    pub fn symbols(&self) -> impl Iterator<Item = (&str, &ConfigValue)> {
+        self.lines.iter().filter_map(|line| match line {
+            ConfigLine::Symbol { name, value } => Some((name.as_str(), value)),
+            ConfigLine::Other(_) => None,
+        })
+    }
+
+    /// Returns every line in file order.
+    pub fn lines(&self) -> &[ConfigLine] {
+        &self.lines
+    }
+
+    /// Assigns a value to a symbol, returning the previous value.
+    ///
+    /// An existing assignment is replaced in place; a new symbol is appended at the end.
+    pub fn set(&mut self, name: &str, value: ConfigValue) -> Option<ConfigValue> {
+        let line = ConfigLine::Symbol {
+            name: name.to_string(),
+            value,
+        };
+
+        match self.index.get(name) {
+            Some(&position) => {
+                let previous = std::mem::replace(&mut self.lines[position], line);
+                self.raw[position] = None;
+                match previous {
+                    ConfigLine::Symbol { value, .. } => Some(value),
+                    ConfigLine::Other(_) => None,
+                }
+            }
+            None => {
+                // Appended symbols always end with a newline, like files written by Kconfig
+                self.trailing_newline = true;
+                self.push_line(line, None);
+                None
+            }
+        }
+    }
+
+    /// Applies every symbol of a fragment on top of this configuration, in fragment order.
+    ///
+    /// Returns `(name, previous, new)` for each symbol whose existing value was redefined.
+    pub fn merge(&mut self, fragment: &KernelConfig) -> Vec<(String, ConfigValue, ConfigValue)> {
+        let mut redefined = Vec::new();
+        for (name, value) in fragment.symbols() {
+            if let Some(previous) = self.set(name, value.clone())
+                && previous != *value
+            {
+                redefined.push((name.to_string(), previous, value.clone()));
+            }
+        }
+        redefined
+    }
+
+    /// Marks a symbol as `# CONFIG_X is not set`, returning the previous value.
+    pub fn unset(&mut self, name: &str) -> Option<ConfigValue> {
+        self.set(name, ConfigValue::NotSet)
+    }
+
+    /// Atomically writes the configuration to a file.
+    pub fn write_to(&self, path: &Path) -> Result<(), KernelUpdaterError> {
+        path.atomic_write(|temp_path| {
+            fs::write(temp_path, self.to_string()).map_err(|io_error| KernelUpdaterError::IOError {
+                path: temp_path.to_path_buf(),
+                io_error,
+            })
+        })
+    }
+}
+
+impl fmt::Display for KernelConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (position, (line, raw)) in self.lines.iter().zip(&self.raw).enumerate() {
+            if position > 0 {
+                writeln!(f)?;
+            }
+            match raw {
+                Some(original) => write!(f, "{original}")?,
+                None => write!(f, "{line}")?,
+            }
+        }
+        if self.trailing_newline {
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Differences between two kernel configurations, each list sorted by symbol name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    /// Symbols only present in the new configuration (new options answered with defaults).
+    pub added: Vec<(String, ConfigValue)>,
+    /// Symbols only present in the old configuration (options that disappeared).
+    pub removed: Vec<(String, ConfigValue)>,
+    /// Symbols present in both with a different value: `(name, old, new)`.
+    pub changed: Vec<(String, ConfigValue, ConfigValue)>,
+}
+
+impl ConfigDiff {
+    /// Compares an old (base) configuration with a new one.
+    pub fn between(old: &KernelConfig, new: &KernelConfig) -> Self {
+        let old_symbols: BTreeMap<&str, &ConfigValue> = old.symbols().collect();
+        let new_symbols: BTreeMap<&str, &ConfigValue> = new.symbols().collect();
+
+        let mut diff = Self::default();
+        for (name, new_value) in &new_symbols {
+            match old_symbols.get(name) {
+                None => diff.added.push((name.to_string(), (*new_value).clone())),
+                Some(old_value) if old_value != new_value => diff.changed.push((
+                    name.to_string(),
+                    (*old_value).clone(),
+                    (*new_value).clone(),
+                )),
+                Some(_) => {}
+            }
+        }
+        for (name, old_value) in &old_symbols {
+            if !new_symbols.contains_key(name) {
+                diff.removed.push((name.to_string(), (*old_value).clone()));
+            }
+        }
+        diff
+    }
+
+    /// Checks if both configurations assign exactly the same values.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// One-line summary with the number of added, removed and changed symbols.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} added, {} removed, {} changed",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        )
+    }
+
+    /// Renders the full report, including Kconfig help text for added symbols when known.
+    pub fn render(&self, help: &HashMap<String, String>) -> String {
+        let mut report = format!("Kernel configuration diff: {}\n", self.summary());
+
+        report.push_str(&format!("\nAdded symbols ({}):\n", self.added.len()));
+        for (name, value) in &self.added {
+            report.push_str(&format!("  + {name}={value}\n"));
+            if let Some(text) = help.get(name) {
+                for line in text.lines() {
+                    report.push_str(&format!("      {line}\n"));
+                }
+            }
+        }
+
+        report.push_str(&format!("\nRemoved symbols ({}):\n", self.removed.len()));
+        for (name, value) in &self.removed {
+            report.push_str(&format!("  - {name}={value}\n"));
+        }
+
+        report.push_str(&format!("\nChanged symbols ({}):\n", self.changed.len()));
+        for (name, old_value, new_value) in &self.changed {
+            report.push_str(&format!("  ~ {name}: {old_value} -> {new_value}\n"));
+        }
+
+        report
+    }
+}
+
+/// Collects the Kconfig `help` text of the requested symbols (e.g. `CONFIG_DRM`)
+/// by scanning every `Kconfig*` file of a kernel source tree.
+pub fn collect_kconfig_help(
+    src_dir: &Path,
+    symbols: &HashSet<String>,
+) -> Result<HashMap<String, String>, KernelUpdaterError> {
+    let mut help = HashMap::new();
+    if symbols.is_empty() {
+        return Ok(help);
+    }
+
+    let mut pending = vec![src_dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if name.starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                pending.push(path);
+            } else if name.starts_with("Kconfig")
+                && let Ok(content) = fs::read_to_string(&path)
+            {
+                for (symbol, text) in parse_kconfig_help(&content) {
+                    if symbols.contains(&symbol) {
+                        help.entry(symbol).or_insert(text);
+                    }
+                }
+            }
+        }
+    }
+    Ok(help)
+}
+
+/// Extracts `(CONFIG_<NAME>, help text)` pairs from the content of a Kconfig file.
+pub fn parse_kconfig_help(content: &str) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    let mut current_symbol: Option<String> = None;
+    let mut lines = content.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+
+        if let Some(name) = trimmed
+            .strip_prefix("config ")
+            .or_else(|| trimmed.strip_prefix("menuconfig "))
+        {
+            current_symbol = Some(format!("{SYMBOL_PREFIX}{}", name.trim()));
+            continue;
+        }
+
+        if trimmed != "help" && trimmed != "---help---" {
+            continue;
+        }
+        let Some(symbol) = current_symbol.take() else {
+            continue;
+        };
+
+        // The help block lasts while lines are blank or at least as indented as its first line
+        let mut text: Vec<&str> = Vec::new();
+        let mut block_indent = None;
+        while let Some(next) = lines.peek() {
+            if next.trim().is_empty() {
+                text.push("");
+                lines.next();
+                continue;
+            }
+            let indent = indentation_width(next);
+            let minimum = *block_indent.get_or_insert(indent);
+            if indent < minimum || indent <= indentation_width(line) {
+                break;
+            }
+            text.push(next.trim());
+            lines.next();
+        }
+
+        let text = text.join("\n").trim().to_string();
+        if !text.is_empty() {
+            found.push((symbol, text));
+        }
+    }
+    found
+}
+
+/// Width of the leading whitespace of a line, counting tabs as eight columns like Kconfig does.
+fn indentation_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 8 } else { 1 })
+        .sum()
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_kconfig
+#[cfg(test)]
+mod tests_kconfig {
+    use super::*;
+
+    const BASE_CONFIG: &str = r#"#
+# Automatically generated file; DO NOT EDIT.
+# Linux/x86 6.15.3 Kernel Configuration
+#
+CONFIG_CC_VERSION_TEXT="gcc (GCC) 15.1.1 20250425"
+CONFIG_LOCALVERSION="-ClaudioFSR"
+CONFIG_MODULES=y
+CONFIG_DRM=m
+# CONFIG_DEBUG_INFO is not set
+CONFIG_LOG_BUF_SHIFT=17
+CONFIG_OLD_OPTION=y
+"#;
+
+    const NEW_CONFIG: &str = r#"#
+# Automatically generated file; DO NOT EDIT.
+# Linux/x86 6.15.4 Kernel Configuration
+#
+CONFIG_CC_VERSION_TEXT="gcc (GCC) 15.1.1 20250425"
+CONFIG_LOCALVERSION="-ClaudioFSR"
+CONFIG_MODULES=y
+CONFIG_DRM=y
+# CONFIG_DEBUG_INFO is not set
+CONFIG_LOG_BUF_SHIFT=18
+CONFIG_NEW_FEATURE=y
+# CONFIG_NEW_DEBUG is not set
+"#;
+
+    #[test]
+    fn test_parse_values() {
+        let config = KernelConfig::parse(BASE_CONFIG);
+
+        assert_eq!(config.get("CONFIG_MODULES"), Some(&ConfigValue::Yes));
+        assert_eq!(config.get("CONFIG_DRM"), Some(&ConfigValue::Module));
+        assert_eq!(config.get("CONFIG_DEBUG_INFO"), Some(&ConfigValue::NotSet));
+        assert_eq!(
+            config.get("CONFIG_LOCALVERSION"),
+            Some(&ConfigValue::Str("-ClaudioFSR".to_string()))
+        );
+        assert_eq!(
+            config.get("CONFIG_LOG_BUF_SHIFT"),
+            Some(&ConfigValue::Number("17".to_string()))
+        );
+        assert_eq!(config.get("CONFIG_MISSING"), None);
+
+        assert!(config.is_enabled("CONFIG_DRM"));
+        assert!(!config.is_enabled("CONFIG_DEBUG_INFO"));
+        assert_eq!(config.symbols().count(), 7);
+
+        // Comments are kept as plain lines
+        assert_eq!(
+            config.lines()[2],
+            ConfigLine::Other("# Linux/x86 6.15.3 Kernel Configuration".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_ignores_regular_comments() {
+        let config =
+            KernelConfig::parse("# CONFIG_X is not set because reasons\n# General setup\n");
+        assert_eq!(config.symbols().count(), 0);
+        assert_eq!(config.lines().len(), 2);
+    }
+
+    #[test]
+    fn test_config_diff_between() {
+        let old = KernelConfig::parse(BASE_CONFIG);
+        let new = KernelConfig::parse(NEW_CONFIG);
+        let diff = ConfigDiff::between(&old, &new);
+
+        assert_eq!(
+            diff.added,
+            vec![
+                ("CONFIG_NEW_DEBUG".to_string(), ConfigValue::NotSet),
+                ("CONFIG_NEW_FEATURE".to_string(), ConfigValue::Yes),
+            ]
+        );
+        assert_eq!(
+            diff.removed,
+            vec![("CONFIG_OLD_OPTION".to_string(), ConfigValue::Yes)]
+        );
+        assert_eq!(
+            diff.changed,
+            vec![
+                (
+                    "CONFIG_DRM".to_string(),
+                    ConfigValue::Module,
+                    ConfigValue::Yes
+                ),
+                (
+                    "CONFIG_LOG_BUF_SHIFT".to_string(),
+                    ConfigValue::Number("17".to_string()),
+                    ConfigValue::Number("18".to_string())
+                ),
+            ]
+        );
+        assert_eq!(diff.summary(), "2 added, 1 removed, 2 changed");
+        assert!(ConfigDiff::between(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_parse_kconfig_help() {
+        let kconfig = "\
+config NEW_FEATURE
+\tbool \"A brand new feature\"
+\tdepends on MODULES
+\thelp
+\t  Enables the brand new feature.
+
+\t  Say Y if unsure.
+
+config NEW_DEBUG
+\tbool \"Debugging for the new feature\"
+
+menuconfig DRM
+\ttristate \"Direct Rendering Manager\"
+\t---help---
+\t  Kernel-level support for DRI.
+endmenu
+";
+        let help = parse_kconfig_help(kconfig);
+        assert_eq!(
+            help,
+            vec![
+                (
+                    "CONFIG_NEW_FEATURE".to_string(),
+                    "Enables the brand new feature.\n\nSay Y if unsure.".to_string()
+                ),
+                (
+                    "CONFIG_DRM".to_string(),
+                    "Kernel-level support for DRI.".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_includes_help() {
+        let old = KernelConfig::parse(BASE_CONFIG);
+        let new = KernelConfig::parse(NEW_CONFIG);
+        let diff = ConfigDiff::between(&old, &new);
+
+        let help = HashMap::from([(
+            "CONFIG_NEW_FEATURE".to_string(),
+            "Enables the brand new feature.".to_string(),
+        )]);
+        let report = diff.render(&help);
+
+        assert!(
+            report.contains("  + CONFIG_NEW_FEATURE=y\n      Enables the brand new feature.\n")
+        );
+        assert!(report.contains("  - CONFIG_OLD_OPTION=y\n"));
+        assert!(report.contains("  ~ CONFIG_DRM: m -> y\n"));
+    }
+
+    /// Excerpt of a real x86_64 `.config`, including an odd spacing and escaped quotes.
+    const REAL_SNIPPET: &str = "#
+# Automatically generated file; DO NOT EDIT.
+# Linux/x86 6.15.4 Kernel Configuration
+#
+CONFIG_CC_VERSION_TEXT=\"gcc (GCC) 15.1.1 20250425\"
+CONFIG_CC_IS_GCC=y
+CONFIG_GCC_VERSION=150101
+
+#
+# General setup
+#
+CONFIG_INIT_ENV_ARG_LIMIT=32
+# CONFIG_COMPILE_TEST is not set
+CONFIG_LOCALVERSION=\"-ClaudioFSR\"
+CONFIG_DEFAULT_HOSTNAME=\"(none)\"
+CONFIG_CMDLINE=\"quiet splash acpi_osi=\\\"Windows 2020\\\"\"
+CONFIG_PHYSICAL_START=0x1000000
+CONFIG_DRM=m
+CONFIG_X86_X32_ABI=y  
+# end of General setup
+";
+
+    #[test]
+    fn test_round_trip_is_lossless() {
+        let config = KernelConfig::parse(REAL_SNIPPET);
+        assert_eq!(config.to_string(), REAL_SNIPPET);
+
+        // Without a trailing newline
+        let trimmed = REAL_SNIPPET.trim_end();
+        assert_eq!(KernelConfig::parse(trimmed).to_string(), trimmed);
+
+        assert_eq!(KernelConfig::parse("").to_string(), "");
+        assert_eq!(KernelConfig::parse(BASE_CONFIG).to_string(), BASE_CONFIG);
+    }
+
+    #[test]
+    fn test_parse_special_values() {
+        let config = KernelConfig::parse(REAL_SNIPPET);
+        assert_eq!(
+            config.get("CONFIG_CMDLINE"),
+            Some(&ConfigValue::Str(
+                "quiet splash acpi_osi=\"Windows 2020\"".to_string()
+            ))
+        );
+        assert_eq!(
+            config.get("CONFIG_PHYSICAL_START"),
+            Some(&ConfigValue::Number("0x1000000".to_string()))
+        );
+        assert_eq!(config.get("CONFIG_X86_X32_ABI"), Some(&ConfigValue::Yes));
+    }
+
+    #[test]
+    fn test_set_and_unset() {
+        let mut config = KernelConfig::parse(REAL_SNIPPET);
+
+        let previous = config.set("CONFIG_DRM", ConfigValue::Yes);
+        assert_eq!(previous, Some(ConfigValue::Module));
+        assert_eq!(config.get("CONFIG_DRM"), Some(&ConfigValue::Yes));
+
+        let previous = config.unset("CONFIG_CC_IS_GCC");
+        assert_eq!(previous, Some(ConfigValue::Yes));
+
+        let previous = config.set("CONFIG_COMPILE_TEST", ConfigValue::Yes);
+        assert_eq!(previous, Some(ConfigValue::NotSet));
+
+        let previous = config.set(
+            "CONFIG_LOCALVERSION",
+            ConfigValue::Str("-\"quoted\"".to_string()),
+        );
+        assert_eq!(previous, Some(ConfigValue::Str("-ClaudioFSR".to_string())));
+
+        assert_eq!(config.set("CONFIG_NEW_OPTION", ConfigValue::Module), None);
+
+        let serialized = config.to_string();
+        let expected = REAL_SNIPPET
+            .replace("CONFIG_DRM=m", "CONFIG_DRM=y")
+            .replace("CONFIG_CC_IS_GCC=y", "# CONFIG_CC_IS_GCC is not set")
+            .replace("# CONFIG_COMPILE_TEST is not set", "CONFIG_COMPILE_TEST=y")
+            .replace(
+                "CONFIG_LOCALVERSION=\"-ClaudioFSR\"",
+                "CONFIG_LOCALVERSION=\"-\\\"quoted\\\"\"",
+            )
+            + "CONFIG_NEW_OPTION=m\n";
+        assert_eq!(serialized, expected);
+
+        // The edited output parses back to the same values
+        let reparsed = KernelConfig::parse(&serialized);
+        assert_eq!(
+            reparsed.get("CONFIG_LOCALVERSION"),
+            Some(&ConfigValue::Str("-\"quoted\"".to_string()))
+        );
+        assert_eq!(
+            reparsed.get("CONFIG_NEW_OPTION"),
+            Some(&ConfigValue::Module)
+        );
+        assert!(ConfigDiff::between(&config, &reparsed).is_empty());
+    }
+
+    #[test]
+    fn test_set_appends_after_missing_trailing_newline() {
+        let mut config = KernelConfig::parse("CONFIG_A=y");
+        config.set("CONFIG_B", ConfigValue::Number("12".to_string()));
+        assert_eq!(config.to_string(), "CONFIG_A=y\nCONFIG_B=12\n");
+
+        let mut empty = KernelConfig::default();
+        empty.set("CONFIG_A", ConfigValue::Yes);
+        assert_eq!(empty.to_string(), "CONFIG_A=y\n");
+    }
+
+    #[test]
+    fn test_merge_fragment() {
+        let mut config = KernelConfig::parse(BASE_CONFIG);
+        let fragment = KernelConfig::parse(
+            "# debug.config\nCONFIG_DEBUG_INFO=y\nCONFIG_DRM=m\nCONFIG_KASAN=y\n",
+        );
+
+        let redefined = config.merge(&fragment);
+        assert_eq!(
+            redefined,
+            vec![(
+                "CONFIG_DEBUG_INFO".to_string(),
+                ConfigValue::NotSet,
+                ConfigValue::Yes
+            )]
+        );
+        assert_eq!(config.get("CONFIG_DEBUG_INFO"), Some(&ConfigValue::Yes));
+        assert_eq!(config.get("CONFIG_KASAN"), Some(&ConfigValue::Yes));
+        assert!(
+            config
+                .to_string()
+                .ends_with("CONFIG_OLD_OPTION=y\nCONFIG_KASAN=y\n")
+        );
+    }
+}
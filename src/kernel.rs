@@ -1,9 +1,36 @@
 use crate::{
-    AtomicWriteExt, Config, Downloader,
+    AtomicWriteExt, Config, ConfigEditor, ConfigSeed, Downloader, Toolchain,
     error::KernelUpdaterError,
-    utils::{get_cores, run_command},
+    headers::KernelHeaders,
+    info,
+    kconfig::{ConfigDiff, ConfigValue, KernelConfig, collect_kconfig_help},
+    logging::{file_stamp, run_log_path},
+    report::{self, ConfigDiffSummary},
+    requirements::{check_required_options, required_option_rules},
+    resources::{DEFAULT_MIB_PER_JOB, LTO_MIB_PER_JOB, available_memory_mib, jobs_for_memory},
+    utils::{
+        confirm, get_cores, run_command, run_command_interactive, run_command_line,
+        run_command_output,
+    },
+    warn,
 };
-use std::{env, fs, io::ErrorKind, os::unix::fs as unix_fs, path::Path};
+use std::{
+    collections::{BTreeMap, HashSet},
+    env, fs,
+    io::{self, ErrorKind, IsTerminal},
+    os::unix::fs as unix_fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Maximum number of added symbols listed on the console after `olddefconfig`.
+const DIFF_CONSOLE_LIMIT: usize = 20;
+
+/// Configuration of the running kernel, exposed by `CONFIG_IKCONFIG_PROC`.
+const PROC_CONFIG_GZ: &str = "/proc/config.gz";
+
+/// Values requested by config fragments, with the fragment that requested each one.
+pub type RequestedOptions = BTreeMap<String, (ConfigValue, PathBuf)>;
 
 /// Object-oriented controller for downloading, compiling, and installing kernel trees.
 pub struct KernelBuilder<'a> {
@@ -18,16 +45,225 @@ impl<'a> KernelBuilder<'a> {
 
     /// Handles compilation pipeline (download, extract, configure, make).
     pub fn compile(&self) -> Result<(), KernelUpdaterError> {
-        println!(
+        info!(
             "Initializing compilation pipeline for version {}...",
             self.config.version_new
         );
 
+        // Fail before downloading anything if the editor cannot be shown
+        if let Some(editor) = self.config.build.edit_config {
+            ensure_interactive(editor)?;
+        }
+
+        // Out-of-tree builds leave the source pristine, so an extracted tree can be reused
+        if self.can_reuse_source() {
+            info!(
+                "Reusing extracted source tree: {}",
+                self.config.kernel_src_dir_path.display()
+            );
+        } else {
+            self.fetch_source()?;
+        }
+
+        let build_dir = &self.config.kernel_build_dir_path;
+        fs::create_dir_all(build_dir)?;
+        env::set_current_dir(&self.config.kernel_src_dir_path)?;
+        if !self.config.config_file_path.exists() {
+            match self.config.build.seed_config {
+                Some(seed) => self.seed_base_config(seed)?,
+                None => {
+                    return Err(KernelUpdaterError::KernelConfigNotFound {
+                        path: self.config.config_file_path.clone(),
+                    });
+                }
+            }
+        }
+
+        info!(
+            "Applying configuration base from: {}",
+            self.config.config_file_path.display()
+        );
+
+        let dot_config = build_dir.join(".config");
+        dot_config.atomic_copy_from(&self.config.config_file_path)?;
+
+        let requested = self.apply_config_fragments()?;
+
+        self.make(&["olddefconfig"])?;
+
+        if !dot_config.exists() {
+            return Err(KernelUpdaterError::KernelNotConfigured {
+                src_dir: build_dir.clone(),
+                version: self.config.version_new.clone(),
+            });
+        }
+
+        self.check_fragment_overrides(&requested)?;
+
+        if let Some(editor) = self.config.build.edit_config {
+            info!(
+                "Opening configuration editor: make {}",
+                editor.make_target()
+            );
+            let make_args = self.make_args(&[editor.make_target()]);
+            let make_args: Vec<&str> = make_args.iter().map(String::as_str).collect();
+            run_command_interactive("make", &make_args)?;
+        }
+
+        self.validate_required_options()?;
+
+        // The report is informative only and must never abort the build
+        if let Err(err) = self.report_config_diff() {
+            warn!("Could not produce the configuration diff report: {err}");
+        }
+
+        if self.config.build.edit_config.is_some()
+            && confirm(&format!(
+                "Save the edited configuration to {}?",
+                self.config.config_file_path.display()
+            ))?
+        {
+            self.save_config_back()?;
+        }
+
+        let toolchain = Toolchain::new(&self.config.build);
+        if let Err(err) = toolchain.reset_cache_stats() {
+            warn!("Could not reset compiler cache statistics: {err}");
+        }
+
+        let jobs = self.build_jobs()?;
+        report::record(|report| {
+            report.jobs = Some(jobs);
+            report.toolchain = Some(toolchain.describe());
+            report.make_variables = Some(self.make_args(&[]).join(" "));
+            report.build_dir = Some(self.config.kernel_build_dir_path.clone());
+        });
+        info!(
+            "Compiling kernel tree with {jobs} jobs using {}...",
+            toolchain.describe()
+        );
+        run_command_line(&self.build_command(jobs), &[])?;
+
+        let cache_stats = toolchain.cache_stats().unwrap_or_else(|err| {
+            warn!("Could not read compiler cache statistics: {err}");
+            None
+        });
+        if let Some(stats) = &cache_stats {
+            info!("Compiler cache statistics:\n{}", stats.trim_end());
+        }
+        report::record(|report| report.compiler_cache_stats = cache_stats);
+
+        info!("Compilation phase finished successfully.");
+        Ok(())
+    }
+
+    /// Number of parallel jobs for the build: `--jobs` if given, otherwise all cores but one,
+    /// capped so each job gets its memory budget out of `MemAvailable`.
+    pub fn build_jobs(&self) -> Result<usize, KernelUpdaterError> {
+        if let Some(jobs) = self.config.build.jobs {
+            return Ok(jobs as usize);
+        }
+
+        let cores = get_cores(1)?;
+        let mib_per_job = self
+            .config
+            .build
+            .mem_per_job
+            .unwrap_or(if self.config.build.thin_lto {
+                LTO_MIB_PER_JOB
+            } else {
+                DEFAULT_MIB_PER_JOB
+            });
+
+        match available_memory_mib() {
+            Ok(available_mib) => {
+                let jobs = jobs_for_memory(cores, available_mib, mib_per_job);
+                if jobs < cores {
+                    info!(
+                        "Limiting build to {jobs} jobs: {available_mib} MiB available, \
+                        {mib_per_job} MiB per job (use --jobs or --mem-per-job to override)."
+                    );
+                }
+                Ok(jobs)
+            }
+            Err(err) => {
+                warn!("Could not read available memory, using {cores} jobs: {err}");
+                Ok(cores)
+            }
+        }
+    }
+
+    /// Full command line of the main build: `make -j N [-l LOAD]`, wrapped in
+    /// `ionice`/`nice` when a scheduling policy is configured.
+    pub fn build_command(&self, jobs: usize) -> Vec<String> {
+        let mut command = Vec::new();
+        if let Some(class) = self.config.build.ionice {
+            command.push("ionice".to_string());
+            command.extend(class.ionice_args().iter().map(|arg| arg.to_string()));
+        }
+        if let Some(niceness) = self.config.build.nice {
+            command.extend(["nice".to_string(), "-n".to_string(), niceness.to_string()]);
+        }
+        command.push("make".to_string());
+
+        let jobs = jobs.to_string();
+        let load_average = self.config.build.load_average.map(|load| load.to_string());
+        let mut make_args = vec!["-j", jobs.as_str()];
+        if let Some(load) = &load_average {
+            make_args.extend(["-l", load.as_str()]);
+        }
+        command.extend(self.make_args(&make_args));
+        command
+    }
+
+    /// Locates the boot image produced by the build for the target architecture.
+    pub fn kernel_image(&self) -> Result<PathBuf, KernelUpdaterError> {
+        let candidates: Vec<PathBuf> = self
+            .config
+            .arch
+            .image_candidates()
+            .iter()
+            .map(|image| self.config.kernel_build_dir_path.join(image))
+            .collect();
+
+        match candidates.iter().find(|path| path.is_file()) {
+            Some(image) => Ok(image.clone()),
+            None => Err(KernelUpdaterError::KernelBinaryNotFound {
+                path: candidates[0].clone(),
+                src_dir: self.config.kernel_build_dir_path.clone(),
+                version: self.config.version_new.clone(),
+            }),
+        }
+    }
+
+    /// Whether the source tree can be reused instead of downloading it again: only for
+    /// out-of-tree builds, and only once a previous extraction ran to completion.
+    fn can_reuse_source(&self) -> bool {
+        self.config.is_out_of_tree()
+            && self.config.kernel_src_dir_path.is_dir()
+            && self.extraction_marker().is_file()
+    }
+
+    /// File written next to the source tree (`<tree>.extracted`) once its tarball has been
+    /// fully extracted, so a tree left by an interrupted `tar` is never reused.
+    fn extraction_marker(&self) -> PathBuf {
+        let mut marker = self.config.kernel_src_dir_path.as_os_str().to_os_string();
+        marker.push(".extracted");
+        PathBuf::from(marker)
+    }
+
+    /// Downloads and extracts the source tarball under `kernel_src_base`.
+    fn fetch_source(&self) -> Result<(), KernelUpdaterError> {
         let kernel_src_base = &self.config.kernel_src_base;
         fs::create_dir_all(kernel_src_base)?;
         env::set_current_dir(kernel_src_base)?;
 
-        println!(
+        let marker = self.extraction_marker();
+        if marker.exists() {
+            fs::remove_file(&marker)?;
+        }
+
+        info!(
             "Downloading source tarball from: {}",
             self.config.download_link
         );
@@ -48,82 +284,427 @@ impl<'a> KernelBuilder<'a> {
             }
         }
 
-        println!("Extracting tarball content...");
+        info!("Extracting tarball content...");
         run_command("tar", &["-Jxvf", &self.config.tarball_name])?;
+        fs::write(&marker, format!("{}\n", self.config.tarball_name))?;
+        Ok(())
+    }
 
-        env::set_current_dir(&self.config.kernel_src_dir_path)?;
-        if !self.config.config_file_path.exists() {
-            return Err(KernelUpdaterError::KernelConfigNotFound {
-                path: self.config.config_file_path.clone(),
-            });
+    /// Arguments for a `make` invocation in the source tree: `O=` for out-of-tree builds and
+    /// the toolchain variables, followed by `args`.
+    pub fn make_args(&self, args: &[&str]) -> Vec<String> {
+        let mut make_args = Vec::with_capacity(args.len() + 1);
+        if self.config.is_out_of_tree() {
+            make_args.push(format!("O={}", self.config.kernel_build_dir_path.display()));
         }
-
-        println!(
-            "Applying configuration base from: {}",
-            self.config.config_file_path.display()
-        );
-
-        Path::new(".config").atomic_copy_from(&self.config.config_file_path)?;
-
-        run_command("make", &["olddefconfig"])?;
-
-        if !Path::new(".config").exists() {
-            return Err(KernelUpdaterError::KernelNotConfigured {
-                src_dir: self.config.kernel_src_dir_path.clone(),
-                version: self.config.version_new.clone(),
-            });
+        if self.config.is_cross_compiling() {
+            make_args.push(format!("ARCH={}", self.config.arch.kernel_arch()));
         }
+        if let Some(prefix) = self.config.cross_compile_prefix() {
+            make_args.push(format!("CROSS_COMPILE={prefix}"));
+        }
+        make_args.extend(Toolchain::new(&self.config.build).make_variables());
+        make_args.extend(args.iter().map(|arg| arg.to_string()));
+        make_args
+    }
 
-        let cores = get_cores(1)?;
-        println!("Compiling kernel tree with {cores} cores...");
-        run_command("make", &["-j", &cores.to_string()])?;
-
-        println!("Compilation phase finished successfully.");
-        Ok(())
+    /// Runs `make` in the current directory (the source tree) with [`Self::make_args`].
+    pub(crate) fn make(&self, args: &[&str]) -> Result<(), KernelUpdaterError> {
+        let make_args = self.make_args(args);
+        let make_args: Vec<&str> = make_args.iter().map(String::as_str).collect();
+        run_command("make", &make_args)
     }
 
     /// Installs target binaries, system maps, links, and builds modules.
     pub fn install(&self) -> Result<(), KernelUpdaterError> {
-        println!("Initializing installation pipeline...");
+        info!("Initializing installation pipeline...");
         env::set_current_dir(&self.config.kernel_src_dir_path)?;
 
-        let bzimage_source = Path::new("arch/x86/boot/bzImage");
-        if !bzimage_source.exists() {
-            return Err(KernelUpdaterError::KernelBinaryNotFound {
-                path: bzimage_source.to_path_buf(),
-                src_dir: self.config.kernel_src_dir_path.clone(),
-                version: self.config.version_new.clone(),
-            });
+        let image_source = self.kernel_image()?;
+
+        info!(
+            "Installing modules under {}...",
+            self.config.kernel_module_base.display()
+        );
+        if self.config.is_staged() {
+            let install_mod_path =
+                format!("INSTALL_MOD_PATH={}", self.config.target_root.display());
+            self.make(&["modules_install", &install_mod_path])?;
+        } else {
+            self.make(&["modules_install"])?;
         }
 
-        println!("Installing modules under /lib/modules...");
-        run_command("make", &["modules_install"])?;
+        if let Some(dtbs_path) = &self.config.dtbs_install_path {
+            info!("Installing device tree blobs to: {}", dtbs_path.display());
+            let install_dtbs_path = format!("INSTALL_DTBS_PATH={}", dtbs_path.display());
+            self.make(&["dtbs_install", &install_dtbs_path])?;
+        }
 
-        println!(
+        info!(
             "Deploying boot image target to: {}",
             self.config.vmlinuz_install_path.display()
         );
 
         self.config
             .vmlinuz_install_path
-            .atomic_copy_from(bzimage_source)?;
+            .atomic_copy_from(&image_source)?;
+
+        self.install_boot_artifacts()?;
 
         let kernel_ident_name = &self.config.kernel_ident_name_new;
         let target_modules_dir = self.config.kernel_module_base.join(kernel_ident_name);
 
+        // With a headers tree, nothing installed references the source tree any more
+        let (build_target, source_target) = if self.config.build.install_headers {
+            let headers_path = self.config.headers_install_path();
+            KernelHeaders::new(self.config).install(&headers_path)?;
+            (headers_path.clone(), headers_path)
+        } else {
+            (
+                self.config.kernel_build_dir_path.clone(),
+                self.config.kernel_src_dir_path.clone(),
+            )
+        };
+
+        // Links must resolve from inside the target root once it is booted
         self.ensure_symlink(
             &target_modules_dir.join("build"),
-            &self.config.kernel_src_dir_path,
+            &self.config.path_in_target(&build_target),
         )?;
         self.ensure_symlink(
             &target_modules_dir.join("source"),
-            &self.config.kernel_src_dir_path,
+            &self.config.path_in_target(&source_target),
         )?;
 
-        println!("Kernel installation successfully completed.");
+        if self.config.build.save_config {
+            self.save_config_back()?;
+        }
+
+        info!("Kernel installation successfully completed.");
         Ok(())
     }
 
+    /// Copies the final `.config` and `System.map` next to the kernel image as
+    /// `config-<ident>` and `System.map-<ident>`, so they outlive the source tree.
+    pub fn install_boot_artifacts(&self) -> Result<Vec<PathBuf>, KernelUpdaterError> {
+        let boot_dir = self.config.boot_dir();
+        let ident = &self.config.kernel_ident_name_new;
+        let artifacts = [
+            (".config", boot_dir.join(format!("config-{ident}"))),
+            ("System.map", boot_dir.join(format!("System.map-{ident}"))),
+        ];
+
+        let mut installed = Vec::new();
+        for (source_name, target) in artifacts {
+            let source = self.config.kernel_build_dir_path.join(source_name);
+            info!("Installing {source_name} to: {}", target.display());
+            target.atomic_copy_from(&source)?;
+            installed.push(target);
+        }
+        Ok(installed)
+    }
+
+    /// Replaces the base config with the tree's final `.config`, keeping the previous one
+    /// as `<config>.<timestamp>.bak`. Returns the backup path, or `None` if nothing changed.
+    pub fn save_config_back(&self) -> Result<Option<PathBuf>, KernelUpdaterError> {
+        let dot_config = self.config.kernel_build_dir_path.join(".config");
+        let base_path = &self.config.config_file_path;
+        let final_config =
+            fs::read(&dot_config).map_err(|io_error| KernelUpdaterError::IOError {
+                path: dot_config.clone(),
+                io_error,
+            })?;
+
+        let mut backup_path = None;
+        if base_path.exists() {
+            if fs::read(base_path)? == final_config {
+                info!(
+                    "Base configuration {} is already up to date.",
+                    base_path.display()
+                );
+                return Ok(None);
+            }
+
+            let mut backup_name = base_path.as_os_str().to_os_string();
+            backup_name.push(format!(".{}.bak", file_stamp(SystemTime::now())));
+            let backup = PathBuf::from(backup_name);
+
+            backup.atomic_copy_from(base_path)?;
+            info!(
+                "Previous base configuration backed up to {}",
+                backup.display()
+            );
+            backup_path = Some(backup);
+        }
+
+        base_path.atomic_copy_from(&dot_config)?;
+        info!("Final configuration saved to {}", base_path.display());
+        Ok(backup_path)
+    }
+
+    /// Creates the missing base config from the running or previous kernel's config,
+    /// optionally trimmed with `make localmodconfig`, and saves it to `config_file_path`.
+    pub fn seed_base_config(&self, seed: ConfigSeed) -> Result<(), KernelUpdaterError> {
+        let running_release = match seed {
+            ConfigSeed::Running => Some(run_command_output("uname", &["-r"])?.trim().to_string()),
+            ConfigSeed::Previous => None,
+        };
+        let candidates = self.seed_candidates(seed, running_release.as_deref());
+        let Some(source) = candidates.iter().find(|path| path.is_file()) else {
+            return Err(KernelUpdaterError::SeedConfigNotFound { seed, candidates });
+        };
+
+        info!("Seeding base configuration from: {}", source.display());
+        let dot_config = self.config.kernel_build_dir_path.join(".config");
+        if source.extension().is_some_and(|ext| ext == "gz") {
+            let content = run_command_output("gzip", &["-dc", &source.to_string_lossy()])?;
+            KernelConfig::parse(&content).write_to(&dot_config)?;
+        } else {
+            dot_config.atomic_copy_from(source)?;
+        }
+
+        if let Some(lsmod_file) = &self.config.build.localmodconfig {
+            if !lsmod_file.is_file() {
+                return Err(KernelUpdaterError::LsmodSnapshotNotFound {
+                    path: lsmod_file.clone(),
+                });
+            }
+            info!(
+                "Trimming configuration with localmodconfig ({})...",
+                lsmod_file.display()
+            );
+            // Accept the default answer for any option localmodconfig asks about
+            let lsmod_arg = format!("LSMOD={}", lsmod_file.display());
+            let make_args = self.make_args(&[&lsmod_arg, "localmodconfig"]);
+            let mut sh_args = vec!["-c", r#"yes "" | make "$@""#, "sh"];
+            sh_args.extend(make_args.iter().map(String::as_str));
+            run_command("sh", &sh_args)?;
+        }
+
+        if let Some(parent) = self.config.config_file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.config.config_file_path.atomic_copy_from(&dot_config)?;
+        info!(
+            "Seeded base configuration saved to {}",
+            self.config.config_file_path.display()
+        );
+        Ok(())
+    }
+
+    /// Config files tried, in order, by `--seed-config`.
+    pub fn seed_candidates(&self, seed: ConfigSeed, running_release: Option<&str>) -> Vec<PathBuf> {
+        let boot_dir = self.config.boot_dir();
+
+        match seed {
+            ConfigSeed::Running => {
+                let mut candidates = vec![PathBuf::from(PROC_CONFIG_GZ)];
+                if let Some(release) = running_release {
+                    candidates.push(boot_dir.join(format!("config-{release}")));
+                }
+                candidates
+            }
+            ConfigSeed::Previous => {
+                let mut candidates = Vec::new();
+                if let Some(old_ident) = &self.config.kernel_ident_name_old {
+                    candidates.push(boot_dir.join(format!("config-{old_ident}")));
+                    candidates.push(
+                        self.config
+                            .kernel_module_base
+                            .join(old_ident)
+                            .join("build/.config"),
+                    );
+                }
+                candidates
+            }
+        }
+    }
+
+    /// Resolves the config fragments to merge: every `*.config` file of the selected
+    /// profile (sorted by name), followed by the `--config-fragment` files in order.
+    pub fn config_fragments(&self) -> Result<Vec<PathBuf>, KernelUpdaterError> {
+        let mut fragments = Vec::new();
+
+        if let Some(profile) = &self.config.build.config_profile {
+            let profile_dir = self
+                .config
+                .kernel_config_base
+                .join("profiles")
+                .join(profile);
+            if !profile_dir.is_dir() {
+                return Err(KernelUpdaterError::ConfigFragmentNotFound { path: profile_dir });
+            }
+
+            let mut profile_fragments: Vec<PathBuf> = fs::read_dir(&profile_dir)?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<Result<_, _>>()?;
+            profile_fragments.retain(|path| {
+                path.is_file() && path.extension().is_some_and(|ext| ext == "config")
+            });
+            profile_fragments.sort();
+            fragments.extend(profile_fragments);
+        }
+
+        for fragment in &self.config.build.config_fragments {
+            if !fragment.is_file() {
+                return Err(KernelUpdaterError::ConfigFragmentNotFound {
+                    path: fragment.clone(),
+                });
+            }
+            fragments.push(fragment.clone());
+        }
+
+        Ok(fragments)
+    }
+
+    /// Merges the config fragments into the tree's `.config`, in order, followed by the
+    /// options the toolchain needs, and returns the values they requested so they can be
+    /// checked after `olddefconfig`.
+    pub fn apply_config_fragments(&self) -> Result<RequestedOptions, KernelUpdaterError> {
+        let mut fragments = Vec::new();
+        for fragment_path in self.config_fragments()? {
+            let fragment = KernelConfig::from_file(&fragment_path)?;
+            fragments.push((fragment_path, fragment));
+        }
+        let toolchain_fragment = Toolchain::new(&self.config.build).config_fragment();
+        if toolchain_fragment.symbols().next().is_some() {
+            fragments.push((PathBuf::from("toolchain options"), toolchain_fragment));
+        }
+
+        let mut requested = RequestedOptions::new();
+        if fragments.is_empty() {
+            return Ok(requested);
+        }
+
+        let dot_config = self.config.kernel_build_dir_path.join(".config");
+        let mut kernel_config = KernelConfig::from_file(&dot_config)?;
+
+        for (fragment_path, fragment) in &fragments {
+            info!("Merging config fragment: {}", fragment_path.display());
+
+            for (name, previous, value) in kernel_config.merge(fragment) {
+                match requested.get(&name) {
+                    Some((_, earlier)) => info!(
+                        "  Value of {name} is redefined by {}: {previous} -> {value} (was set by {})",
+                        fragment_path.display(),
+                        earlier.display()
+                    ),
+                    None => info!("  Value of {name} is redefined: {previous} -> {value}"),
+                }
+            }
+
+            for (name, value) in fragment.symbols() {
+                requested.insert(name.to_string(), (value.clone(), fragment_path.clone()));
+            }
+        }
+
+        kernel_config.write_to(&dot_config)?;
+        Ok(requested)
+    }
+
+    /// Warns about fragment values that `olddefconfig` did not keep, usually because of
+    /// unmet Kconfig dependencies. Returns `(name, requested, actual)` for each of them.
+    pub fn check_fragment_overrides(
+        &self,
+        requested: &RequestedOptions,
+    ) -> Result<Vec<(String, ConfigValue, ConfigValue)>, KernelUpdaterError> {
+        let mut overridden = Vec::new();
+        if requested.is_empty() {
+            return Ok(overridden);
+        }
+
+        let kernel_config =
+            KernelConfig::from_file(&self.config.kernel_build_dir_path.join(".config"))?;
+
+        for (name, (value, fragment)) in requested {
+            // A symbol missing from the final .config is not visible, hence not set
+            let actual = kernel_config
+                .get(name)
+                .cloned()
+                .unwrap_or(ConfigValue::NotSet);
+
+            if actual != *value {
+                warn!(
+                    "{name}={value} requested by {} was changed to {actual} by olddefconfig \
+                    (unmet Kconfig dependencies?)",
+                    fragment.display()
+                );
+                overridden.push((name.clone(), value.clone(), actual));
+            }
+        }
+        Ok(overridden)
+    }
+
+    /// Checks the `.config` produced by `olddefconfig` against the required/forbidden
+    /// option rules, failing with every violated option before any time is spent compiling.
+    pub fn validate_required_options(&self) -> Result<(), KernelUpdaterError> {
+        let config_path = self.config.kernel_build_dir_path.join(".config");
+        let kernel_config = KernelConfig::from_file(&config_path)?;
+        let rules = required_option_rules(self.config);
+
+        let violations = check_required_options(&kernel_config, &rules);
+        if !violations.is_empty() {
+            return Err(KernelUpdaterError::RequiredOptionsViolated {
+                config_path,
+                violations,
+            });
+        }
+
+        info!("All {} required kernel options are satisfied.", rules.len());
+        Ok(())
+    }
+
+    /// Compares the base configuration with the `.config` produced by `olddefconfig`,
+    /// printing a summary and saving the full report next to the run log.
+    pub fn report_config_diff(&self) -> Result<ConfigDiff, KernelUpdaterError> {
+        let base = KernelConfig::from_file(&self.config.config_file_path)?;
+        let current = KernelConfig::from_file(&self.config.kernel_build_dir_path.join(".config"))?;
+        let diff = ConfigDiff::between(&base, &current);
+        report::record(|report| report.config_diff = Some(ConfigDiffSummary::from(&diff)));
+
+        info!(
+            "Configuration changes after olddefconfig: {}",
+            diff.summary()
+        );
+        for (name, value) in diff.added.iter().take(DIFF_CONSOLE_LIMIT) {
+            info!("  + {name}={value}");
+        }
+        if diff.added.len() > DIFF_CONSOLE_LIMIT {
+            info!("  ... and {} more", diff.added.len() - DIFF_CONSOLE_LIMIT);
+        }
+        for (name, value) in &diff.removed {
+            info!("  - {name}={value}");
+        }
+
+        let added: HashSet<String> = diff.added.iter().map(|(name, _)| name.clone()).collect();
+        let help = collect_kconfig_help(&self.config.kernel_src_dir_path, &added)?;
+
+        let report_path = self.config_diff_report_path(run_log_path().as_deref());
+        report_path.atomic_write(|temp_path| {
+            fs::write(temp_path, diff.render(&help)).map_err(|io_error| {
+                KernelUpdaterError::IOError {
+                    path: temp_path.to_path_buf(),
+                    io_error,
+                }
+            })
+        })?;
+        info!(
+            "Configuration diff report saved to {}",
+            report_path.display()
+        );
+
+        Ok(diff)
+    }
+
+    /// Location of the configuration diff report: next to `run_log` as
+    /// `run-<stamp>.config-diff.txt`, or next to the `.config` it describes without a run log.
+    pub fn config_diff_report_path(&self, run_log: Option<&Path>) -> PathBuf {
+        match run_log {
+            Some(run_log) => run_log.with_extension("config-diff.txt"),
+            None => self.config.kernel_build_dir_path.join("config-diff.txt"),
+        }
+    }
+
     /// Rebuilds initramfs images targeting current profile structure.
     pub fn run_mkinitcpio(&self) -> Result<(), KernelUpdaterError> {
         let profile_name = format!(
@@ -132,8 +713,13 @@ impl<'a> KernelBuilder<'a> {
             self.config.custom_kernel_suffix
         );
 
-        println!("Rebuilding initramfs via mkinitcpio (profile: {profile_name})...");
-        run_command("mkinitcpio", &["-p", &profile_name])?;
+        info!("Rebuilding initramfs via mkinitcpio (profile: {profile_name})...");
+        run_command_line(
+            &self
+                .config
+                .target_command("mkinitcpio", &["-p", &profile_name]),
+            &[],
+        )?;
         Ok(())
     }
 
@@ -155,6 +741,28 @@ impl<'a> KernelBuilder<'a> {
     }
 }
 
+/// Rejects `--edit-config` when there is no terminal (or display) to show the editor on.
+fn ensure_interactive(editor: ConfigEditor) -> Result<(), KernelUpdaterError> {
+    let reason = if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        Some("stdin/stdout is not an interactive terminal")
+    } else if editor == ConfigEditor::Xconfig
+        && env::var_os("DISPLAY").is_none()
+        && env::var_os("WAYLAND_DISPLAY").is_none()
+    {
+        Some("no graphical display (DISPLAY/WAYLAND_DISPLAY) is available")
+    } else {
+        None
+    };
+
+    match reason {
+        Some(reason) => Err(KernelUpdaterError::NonInteractiveEditConfig {
+            editor,
+            reason: reason.to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
 //----------------------------------------------------------------------------//
 //                                   Tests                                    //
 //----------------------------------------------------------------------------//
@@ -168,72 +776,17 @@ impl<'a> KernelBuilder<'a> {
 #[cfg(test)]
 mod tests_kernel {
     use super::*;
-    use crate::{Arguments, Version};
+    use crate::test_utils::{CurrentDirGuard, TempDirGuard, stub_args};
     use std::path::PathBuf;
-    use std::str::FromStr;
-    use std::time::SystemTime;
-
-    /// Guard to manage creation and auto-deletion of temporary testing directories.
-    struct TempDirGuard {
-        path: PathBuf,
-    }
-
-    impl TempDirGuard {
-        fn new(prefix: &str) -> Self {
-            let nanos = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_nanos();
-            let mut path = std::env::temp_dir();
-            path.push(format!("kernel-updater-test-{prefix}-{nanos}"));
-            fs::create_dir_all(&path).expect("Failed to create temporary testing directory");
-            Self { path }
-        }
-    }
-
-    impl Drop for TempDirGuard {
-        fn drop(&mut self) {
-            let _ = fs::remove_dir_all(&self.path);
-        }
-    }
-
-    /// Guard to restore the original working directory of the process after directory changes.
-    struct CurrentDirGuard {
-        original: PathBuf,
-    }
-
-    impl CurrentDirGuard {
-        fn new() -> Self {
-            let original = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-            Self { original }
-        }
-    }
-
-    impl Drop for CurrentDirGuard {
-        fn drop(&mut self) {
-            let _ = std::env::set_current_dir(&self.original);
-        }
-    }
 
     /// Generates a mock configuration mapping paths into a temporary folder.
     fn create_mock_config(temp_dir: &Path) -> Config {
-        let args = Arguments {
-            downloader: Downloader::Curl,
-            suffix: "TestSuffix".to_string(),
-            new: Version::from_str("6.15.4").unwrap(),
-            old: Some(Version::from_str("6.15.3").unwrap()),
-            command: None,
-        };
+        let mut args = stub_args();
+        // Every system path is derived inside our secure TempDir
+        args.build.target_root = Some(temp_dir.to_path_buf());
 
         let mut config = Config::new(args).expect("Failed to create standard Config");
-
-        // Relocate critical directory paths inside our secure TempDir
-        config.kernel_src_base = temp_dir.join("lib/modules");
-        config.kernel_module_base = temp_dir.join("lib/modules");
-        config.kernel_config_base = temp_dir.join("lib/modules");
-        config.config_file_path = config.kernel_config_base.join("config-TestSuffix");
-        config.kernel_src_dir_path = config.kernel_src_base.join(&config.kernel_src_dir_name);
-        config.vmlinuz_install_path = temp_dir.join("boot").join("vmlinuz-6.15");
+        config.output.log_dir = temp_dir.join("log");
 
         config
     }
@@ -340,4 +893,455 @@ mod tests_kernel {
         let missing_config_path = &config.config_file_path;
         assert!(!missing_config_path.exists());
     }
+
+    #[test]
+    fn test_report_config_diff_saves_report() {
+        let temp_dir = TempDirGuard::new("config-diff");
+        let config = create_mock_config(&temp_dir.path);
+        let builder = KernelBuilder::new(&config);
+
+        fs::create_dir_all(config.kernel_src_dir_path.join("drivers/new")).unwrap();
+        fs::write(
+            &config.config_file_path,
+            "CONFIG_MODULES=y\nCONFIG_OLD_OPTION=y\n",
+        )
+        .unwrap();
+        fs::write(
+            config.kernel_src_dir_path.join(".config"),
+            "CONFIG_MODULES=y\nCONFIG_NEW_FEATURE=m\n",
+        )
+        .unwrap();
+        fs::write(
+            config.kernel_src_dir_path.join("drivers/new/Kconfig"),
+            "config NEW_FEATURE\n\ttristate \"New\"\n\thelp\n\t  Shiny new driver.\n",
+        )
+        .unwrap();
+
+        let diff = builder.report_config_diff().unwrap();
+        assert_eq!(diff.summary(), "1 added, 1 removed, 0 changed");
+
+        // No run log in tests: the report is saved next to the `.config`
+        let report_path = builder.config_diff_report_path(None);
+        assert_eq!(
+            report_path,
+            config.kernel_build_dir_path.join("config-diff.txt")
+        );
+        assert_eq!(
+            builder.config_diff_report_path(Some(Path::new("/log/runs/run-20250615-150640.log"))),
+            Path::new("/log/runs/run-20250615-150640.config-diff.txt")
+        );
+
+        let report = fs::read_to_string(report_path).unwrap();
+        assert!(report.contains("  + CONFIG_NEW_FEATURE=m\n      Shiny new driver.\n"));
+        assert!(report.contains("  - CONFIG_OLD_OPTION=y\n"));
+    }
+
+    #[test]
+    fn test_apply_config_fragments_in_order() {
+        let temp_dir = TempDirGuard::new("config-fragments");
+        let mut config = create_mock_config(&temp_dir.path);
+
+        let profile_dir = config.kernel_config_base.join("profiles/laptop");
+        fs::create_dir_all(&profile_dir).unwrap();
+        fs::create_dir_all(&config.kernel_src_dir_path).unwrap();
+        fs::write(
+            profile_dir.join("10-no-wifi.config"),
+            "# CONFIG_WLAN is not set\n",
+        )
+        .unwrap();
+        fs::write(profile_dir.join("20-debug.config"), "CONFIG_DEBUG_INFO=y\n").unwrap();
+        fs::write(profile_dir.join("notes.txt"), "ignored").unwrap();
+
+        let extra = temp_dir.path.join("extra.config");
+        fs::write(&extra, "# CONFIG_DEBUG_INFO is not set\nCONFIG_DRM=y\n").unwrap();
+
+        config.build.config_profile = Some("laptop".to_string());
+        config.build.config_fragments = vec![extra.clone()];
+        let builder = KernelBuilder::new(&config);
+
+        let fragments = builder.config_fragments().unwrap();
+        assert_eq!(
+            fragments,
+            vec![
+                profile_dir.join("10-no-wifi.config"),
+                profile_dir.join("20-debug.config"),
+                extra.clone(),
+            ]
+        );
+
+        let dot_config = config.kernel_src_dir_path.join(".config");
+        fs::write(&dot_config, "CONFIG_WLAN=y\nCONFIG_DRM=m\n").unwrap();
+
+        let requested = builder.apply_config_fragments().unwrap();
+        assert_eq!(
+            fs::read_to_string(&dot_config).unwrap(),
+            "# CONFIG_WLAN is not set\nCONFIG_DRM=y\n# CONFIG_DEBUG_INFO is not set\n"
+        );
+        // The last fragment wins
+        assert_eq!(
+            requested.get("CONFIG_DEBUG_INFO"),
+            Some(&(ConfigValue::NotSet, extra.clone()))
+        );
+
+        // Simulate olddefconfig dropping CONFIG_DRM=y back to a module
+        fs::write(
+            &dot_config,
+            "# CONFIG_WLAN is not set\nCONFIG_DRM=m\n# CONFIG_DEBUG_INFO is not set\n",
+        )
+        .unwrap();
+        let overridden = builder.check_fragment_overrides(&requested).unwrap();
+        assert_eq!(
+            overridden,
+            vec![(
+                "CONFIG_DRM".to_string(),
+                ConfigValue::Yes,
+                ConfigValue::Module
+            )]
+        );
+    }
+
+    #[test]
+    fn test_config_fragments_missing() {
+        let temp_dir = TempDirGuard::new("config-fragments-missing");
+        let mut config = create_mock_config(&temp_dir.path);
+        config.build.config_fragments = vec![temp_dir.path.join("missing.config")];
+
+        let err = KernelBuilder::new(&config).config_fragments().unwrap_err();
+        assert!(
+            matches!(&err, KernelUpdaterError::ConfigFragmentNotFound { path } if path.ends_with("missing.config")),
+            "Expected ConfigFragmentNotFound, received: {:?}",
+            err
+        );
+
+        config.build.config_fragments.clear();
+        config.build.config_profile = Some("nope".to_string());
+        let err = KernelBuilder::new(&config).config_fragments().unwrap_err();
+        assert!(matches!(
+            err,
+            KernelUpdaterError::ConfigFragmentNotFound { .. }
+        ));
+    }
+
+    #[test]
+    fn test_seed_base_config_from_previous_kernel() {
+        let temp_dir = TempDirGuard::new("seed-previous");
+        let config = create_mock_config(&temp_dir.path);
+        let builder = KernelBuilder::new(&config);
+
+        let boot_dir = temp_dir.path.join("boot");
+        assert_eq!(
+            builder.seed_candidates(ConfigSeed::Running, Some("6.15.3-arch1-1")),
+            vec![
+                PathBuf::from(PROC_CONFIG_GZ),
+                boot_dir.join("config-6.15.3-arch1-1")
+            ]
+        );
+        let candidates = builder.seed_candidates(ConfigSeed::Previous, None);
+        assert_eq!(
+            candidates,
+            vec![
+                boot_dir.join("config-6.15.3-TestSuffix"),
+                config
+                    .kernel_module_base
+                    .join("6.15.3-TestSuffix/build/.config"),
+            ]
+        );
+
+        // Nothing to seed from yet
+        fs::create_dir_all(&config.kernel_src_dir_path).unwrap();
+        let err = builder.seed_base_config(ConfigSeed::Previous).unwrap_err();
+        assert!(
+            matches!(&err, KernelUpdaterError::SeedConfigNotFound { candidates: tried, .. } if *tried == candidates),
+            "Expected SeedConfigNotFound, received: {:?}",
+            err
+        );
+
+        // The old tree's .config is used when /boot has no copy
+        let old_build = config.kernel_module_base.join("6.15.3-TestSuffix/build");
+        fs::create_dir_all(&old_build).unwrap();
+        fs::write(old_build.join(".config"), "CONFIG_MODULES=y\n").unwrap();
+
+        builder.seed_base_config(ConfigSeed::Previous).unwrap();
+        assert_eq!(
+            fs::read_to_string(&config.config_file_path).unwrap(),
+            "CONFIG_MODULES=y\n"
+        );
+        assert_eq!(
+            fs::read_to_string(config.kernel_src_dir_path.join(".config")).unwrap(),
+            "CONFIG_MODULES=y\n"
+        );
+    }
+
+    #[test]
+    fn test_install_boot_artifacts_and_save_config_back() {
+        let temp_dir = TempDirGuard::new("boot-artifacts");
+        let config = create_mock_config(&temp_dir.path);
+        let builder = KernelBuilder::new(&config);
+
+        fs::create_dir_all(&config.kernel_src_dir_path).unwrap();
+        fs::write(&config.config_file_path, "CONFIG_MODULES=y\n").unwrap();
+        fs::write(
+            config.kernel_src_dir_path.join(".config"),
+            "CONFIG_MODULES=y\nCONFIG_NEW_FEATURE=m\n",
+        )
+        .unwrap();
+        fs::write(
+            config.kernel_src_dir_path.join("System.map"),
+            "ffffffff81000000 T _text\n",
+        )
+        .unwrap();
+
+        let boot_dir = temp_dir.path.join("boot");
+        fs::create_dir_all(&boot_dir).unwrap();
+        let installed = builder.install_boot_artifacts().unwrap();
+        assert_eq!(
+            installed,
+            vec![
+                boot_dir.join("config-6.15.4-TestSuffix"),
+                boot_dir.join("System.map-6.15.4-TestSuffix"),
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(&installed[1]).unwrap(),
+            "ffffffff81000000 T _text\n"
+        );
+
+        let backup = builder
+            .save_config_back()
+            .unwrap()
+            .expect("Expected a backup");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "CONFIG_MODULES=y\n");
+        assert!(
+            backup
+                .to_string_lossy()
+                .starts_with(&*config.config_file_path.to_string_lossy())
+        );
+        // `<base>.<YYYYmmdd-HHMMSS>.bak`
+        let backup_name = backup.file_name().unwrap().to_string_lossy().into_owned();
+        let stamp = backup_name
+            .strip_suffix(".bak")
+            .and_then(|name| name.rsplit('.').next())
+            .unwrap();
+        assert_eq!(stamp.len(), "20250615-150640".len());
+        assert_eq!(stamp.as_bytes()[8], b'-');
+        assert_eq!(
+            fs::read_to_string(&config.config_file_path).unwrap(),
+            "CONFIG_MODULES=y\nCONFIG_NEW_FEATURE=m\n"
+        );
+
+        // Saving again is a no-op
+        assert_eq!(builder.save_config_back().unwrap(), None);
+    }
+
+    #[test]
+    fn test_edit_config_rejected_without_terminal() {
+        // Only meaningful when the test runner is not attached to a terminal (e.g. CI)
+        if io::stdin().is_terminal() && io::stdout().is_terminal() {
+            return;
+        }
+
+        let err = ensure_interactive(ConfigEditor::Nconfig).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                KernelUpdaterError::NonInteractiveEditConfig {
+                    editor: ConfigEditor::Nconfig,
+                    ..
+                }
+            ),
+            "Expected NonInteractiveEditConfig, received: {:?}",
+            err
+        );
+        assert!(
+            err.to_string()
+                .starts_with("--edit-config nconfig cannot run here")
+        );
+    }
+
+    #[test]
+    fn test_out_of_tree_build_paths() {
+        let temp_dir = TempDirGuard::new("out-of-tree");
+        let mut config = create_mock_config(&temp_dir.path);
+        assert!(!config.is_out_of_tree());
+        assert_eq!(
+            KernelBuilder::new(&config).make_args(&["olddefconfig"]),
+            vec!["olddefconfig"]
+        );
+
+        config.kernel_build_dir_path = temp_dir.path.join("build/6.15.4-TestSuffix");
+        assert!(config.is_out_of_tree());
+        let builder = KernelBuilder::new(&config);
+        assert_eq!(
+            builder.make_args(&["-j", "8"]),
+            vec![
+                format!("O={}", config.kernel_build_dir_path.display()),
+                "-j".to_string(),
+                "8".to_string(),
+            ]
+        );
+
+        // Artifacts are read from the build directory, not the source tree
+        fs::create_dir_all(&config.kernel_build_dir_path).unwrap();
+        fs::write(
+            config.kernel_build_dir_path.join(".config"),
+            "CONFIG_MODULES=y\n",
+        )
+        .unwrap();
+        fs::write(config.kernel_build_dir_path.join("System.map"), "").unwrap();
+
+        // A source tree is reused only once its extraction completed
+        fs::create_dir_all(&config.kernel_src_dir_path).unwrap();
+        assert!(!builder.can_reuse_source());
+        fs::write(builder.extraction_marker(), "linux-6.15.4.tar.xz\n").unwrap();
+        assert!(builder.can_reuse_source());
+        assert!(!KernelBuilder::new(&create_mock_config(&temp_dir.path)).can_reuse_source());
+
+        fs::create_dir_all(temp_dir.path.join("boot")).unwrap();
+        builder.install_boot_artifacts().unwrap();
+        assert_eq!(
+            fs::read_to_string(temp_dir.path.join("boot/config-6.15.4-TestSuffix")).unwrap(),
+            "CONFIG_MODULES=y\n"
+        );
+    }
+
+    #[test]
+    fn test_build_command_scheduling() {
+        let temp_dir = TempDirGuard::new("build-command");
+        let mut config = create_mock_config(&temp_dir.path);
+        config.build.jobs = Some(6);
+        assert_eq!(KernelBuilder::new(&config).build_jobs().unwrap(), 6);
+        assert_eq!(
+            KernelBuilder::new(&config).build_command(6),
+            vec!["make", "-j", "6"]
+        );
+
+        config.build.load_average = Some(12.5);
+        config.build.nice = Some(10);
+        config.build.ionice = Some(crate::IoSchedulingClass::Idle);
+        config.build.llvm = true;
+        assert_eq!(
+            KernelBuilder::new(&config).build_command(6),
+            vec![
+                "ionice", "-c", "3", "nice", "-n", "10", "make", "LLVM=1", "-j", "6", "-l", "12.5"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_kernel_image_per_arch() {
+        let temp_dir = TempDirGuard::new("kernel-image");
+        let mut config = create_mock_config(&temp_dir.path);
+        // An architecture foreign to the host, so that the cross-compilation path is always taken
+        config.arch = match crate::Arch::host() {
+            Some(crate::Arch::Arm64) => crate::Arch::Riscv64,
+            _ => crate::Arch::Arm64,
+        };
+        assert!(config.is_cross_compiling());
+
+        let boot = config
+            .kernel_build_dir_path
+            .join(format!("arch/{}/boot", config.arch.kernel_arch()));
+        fs::create_dir_all(&boot).unwrap();
+        let builder = KernelBuilder::new(&config);
+        assert!(matches!(
+            builder.kernel_image(),
+            Err(KernelUpdaterError::KernelBinaryNotFound { .. })
+        ));
+
+        fs::write(boot.join("Image.gz"), "compressed").unwrap();
+        assert_eq!(builder.kernel_image().unwrap(), boot.join("Image.gz"));
+        fs::write(boot.join("Image"), "uncompressed").unwrap();
+        assert_eq!(builder.kernel_image().unwrap(), boot.join("Image"));
+
+        let (arch, cross_compile) = match config.arch {
+            crate::Arch::Arm64 => ("ARCH=arm64", "CROSS_COMPILE=aarch64-linux-gnu-"),
+            _ => ("ARCH=riscv", "CROSS_COMPILE=riscv64-linux-gnu-"),
+        };
+        assert_eq!(
+            builder.make_args(&["olddefconfig"]),
+            vec![arch, cross_compile, "olddefconfig"]
+        );
+    }
+
+    #[test]
+    fn test_install_into_target_root() {
+        let temp_dir = TempDirGuard::new("install-target-root");
+        let _cwd_guard = CurrentDirGuard::new();
+        let config = create_mock_config(&temp_dir.path);
+        let builder = KernelBuilder::new(&config);
+
+        // A compiled tree whose Makefile stands in for Kbuild's install targets
+        let src_dir = &config.kernel_src_dir_path;
+        let image = src_dir.join(&config.arch.image_candidates()[0]);
+        fs::create_dir_all(image.parent().unwrap()).unwrap();
+        fs::write(&image, "kernel image").unwrap();
+        fs::write(src_dir.join(".config"), "CONFIG_MODULES=y\n").unwrap();
+        fs::write(src_dir.join("System.map"), "symbols\n").unwrap();
+        fs::write(
+            src_dir.join("Makefile"),
+            "modules_install:\n\
+             \tmkdir -p $(INSTALL_MOD_PATH)/lib/modules/6.15.4-TestSuffix/kernel\n\
+             \ttouch $(INSTALL_MOD_PATH)/lib/modules/6.15.4-TestSuffix/kernel/dummy.ko\n\
+             dtbs_install:\n\
+             \tmkdir -p $(INSTALL_DTBS_PATH)\n",
+        )
+        .unwrap();
+        fs::create_dir_all(temp_dir.path.join("boot")).unwrap();
+
+        builder.install().unwrap();
+
+        let root = &temp_dir.path;
+        let modules_dir = root.join("lib/modules/6.15.4-TestSuffix");
+        assert!(modules_dir.join("kernel/dummy.ko").exists());
+        assert_eq!(
+            fs::read_to_string(root.join("boot/vmlinuz-6.15")).unwrap(),
+            "kernel image"
+        );
+        assert!(root.join("boot/config-6.15.4-TestSuffix").exists());
+        assert!(root.join("boot/System.map-6.15.4-TestSuffix").exists());
+        assert_eq!(
+            fs::read_link(modules_dir.join("build")).unwrap(),
+            PathBuf::from("/lib/modules/linux-6.15.4")
+        );
+    }
+
+    #[test]
+    fn test_install_headers_makes_source_disposable() {
+        let temp_dir = TempDirGuard::new("install-headers");
+        let _cwd_guard = CurrentDirGuard::new();
+        let mut config = create_mock_config(&temp_dir.path);
+        config.build.install_headers = true;
+        let builder = KernelBuilder::new(&config);
+
+        let src_dir = &config.kernel_src_dir_path;
+        let image = src_dir.join(&config.arch.image_candidates()[0]);
+        fs::create_dir_all(image.parent().unwrap()).unwrap();
+        fs::write(&image, "kernel image").unwrap();
+        fs::write(src_dir.join(".config"), "CONFIG_MODULES=y\n").unwrap();
+        fs::write(src_dir.join("System.map"), "symbols\n").unwrap();
+        fs::write(src_dir.join("Module.symvers"), "").unwrap();
+        fs::create_dir_all(src_dir.join("include/linux")).unwrap();
+        fs::write(src_dir.join("include/linux/module.h"), "").unwrap();
+        fs::write(
+            src_dir.join("Makefile"),
+            "modules_install:\n\
+             \tmkdir -p $(INSTALL_MOD_PATH)/lib/modules/6.15.4-TestSuffix\n",
+        )
+        .unwrap();
+        fs::create_dir_all(temp_dir.path.join("boot")).unwrap();
+
+        builder.install().unwrap();
+
+        let headers_dir = temp_dir.path.join("usr/src/linux-6.15.4-TestSuffix");
+        assert!(headers_dir.join("include/linux/module.h").is_file());
+        assert!(headers_dir.join("Module.symvers").is_file());
+
+        let modules_dir = temp_dir.path.join("lib/modules/6.15.4-TestSuffix");
+        for link in ["build", "source"] {
+            assert_eq!(
+                fs::read_link(modules_dir.join(link)).unwrap(),
+                PathBuf::from("/usr/src/linux-6.15.4-TestSuffix")
+            );
+        }
+    }
 }
@@ -1,17 +1,58 @@
+mod arch;
 mod args;
+mod bundle;
 mod config;
 mod dkms;
+mod dkms_conf;
 mod error;
+mod events;
+mod headers;
+mod hints;
+mod kconfig;
 mod kernel;
+mod logging;
+mod package;
+mod report;
+mod requirements;
+mod resources;
+mod signing;
+#[cfg(test)]
+mod test_utils;
+mod toolchain;
 mod traits;
 mod utils;
 mod version;
 
-pub use args::{Arguments, Commands, Downloader};
+pub use arch::Arch;
+pub use args::{
+    Arguments, BuildArgs, Commands, CompilerCache, ConfigEditor, ConfigSeed, DkmsArgs, Downloader,
+    IoSchedulingClass, OutputArgs, OutputFormat, PackageFormat,
+};
+pub use bundle::{BundleManifest, KernelBundle};
 pub use config::Config;
-pub use dkms::{DkmsEntry, DkmsManager};
-pub use error::{KernelUpdaterError, KernelUpdaterResult};
+pub use dkms::{DkmsEntry, DkmsManager, DkmsModuleReport, DkmsModuleSpec, DkmsOutcome};
+pub use dkms_conf::{DkmsCompatibility, DkmsConf};
+pub use error::{KernelUpdaterError, KernelUpdaterResult, Phase};
+pub use events::{EVENT_SCHEMA_VERSION, ErrorReport, Event, duration_secs, emit_event};
+pub use headers::KernelHeaders;
+pub use kconfig::{ConfigDiff, ConfigLine, ConfigValue, KernelConfig};
 pub use kernel::KernelBuilder;
+pub use logging::{
+    LogLevel, RUNS_DIR, Verbosity, finish_run_log, log_message, log_phase, output_format,
+    run_log_path, set_output_format, set_verbosity, start_run_log,
+};
+pub use package::{PackageInfo, Packager};
+pub use report::{
+    ConfigDiffSummary, DkmsModuleVersion, ModuleStats, PhaseTiming, REPORT_SCHEMA_VERSION,
+    RunReport, finish_run_report, history_header, load_run_reports, record_artifacts,
+    start_run_report,
+};
+pub use requirements::{OptionRequirement, OptionRule, OptionViolation};
+pub use signing::{ModuleCompression, ModuleSigner, SigningKey};
+pub use toolchain::Toolchain;
 pub use traits::AtomicWriteExt;
-pub use utils::{get_cores, run_command, run_command_output, update_grub};
+pub use utils::{
+    get_cores, run_command, run_command_line, run_command_line_output, run_command_output,
+    run_command_with_env, update_grub,
+};
 pub use version::Version;
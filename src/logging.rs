@@ -0,0 +1,428 @@
+use crate::{
+    args::OutputFormat,
+    error::KernelUpdaterError,
+    events::{Event, duration_secs, emit_event},
+    report::{self, PhaseTiming},
+};
+use std::{
+    env, fmt,
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicU8, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Directory under `--log-dir` holding one log file per run.
+pub const RUNS_DIR: &str = "runs";
+
+/// Console verbosity selected with `-q`/`-v`. The run log always records everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Warnings and errors only; command output goes to the run log alone.
+    Quiet = 0,
+    /// Progress messages and command output.
+    Normal = 1,
+    /// Also debug messages: command durations and exit statuses, captured output sizes.
+    Verbose = 2,
+}
+
+impl Verbosity {
+    /// Verbosity selected by the `--quiet`/`--verbose` flags.
+    pub fn from_flags(quiet: bool, verbose: bool) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Self::Quiet,
+            (_, true) => Self::Verbose,
+            _ => Self::Normal,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Quiet,
+            2 => Self::Verbose,
+            _ => Self::Normal,
+        }
+    }
+}
+
+/// Kind of a run log entry, deciding its tag and whether the console shows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    /// A line written by a child process on stdout.
+    Stdout,
+    /// A line written by a child process on stderr.
+    Stderr,
+}
+
+impl LogLevel {
+    /// Tag written in front of every run log line.
+    fn tag(&self) -> &'static str {
+        match self {
+            Self::Error => "ERROR",
+            Self::Warn => "WARN ",
+            Self::Info => "INFO ",
+            Self::Debug => "DEBUG",
+            Self::Stdout => "OUT  ",
+            Self::Stderr => "ERR  ",
+        }
+    }
+
+    /// Least verbose console setting still showing this level.
+    fn console_threshold(&self) -> Verbosity {
+        match self {
+            Self::Error | Self::Warn => Verbosity::Quiet,
+            Self::Info | Self::Stdout | Self::Stderr => Verbosity::Normal,
+            Self::Debug => Verbosity::Verbose,
+        }
+    }
+
+    /// Whether the console line goes to stderr. With `--output json` everything does,
+    /// keeping stdout for the events.
+    fn is_stderr(&self) -> bool {
+        output_format() == OutputFormat::Json
+            || matches!(self, Self::Error | Self::Warn | Self::Stderr)
+    }
+}
+
+/// Open run log.
+struct RunLog {
+    file: File,
+    path: PathBuf,
+    started: Instant,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+static RUN_LOG: Mutex<Option<RunLog>> = Mutex::new(None);
+/// Phase currently in progress, tracked even without a run log for the phase events.
+static PHASE: Mutex<Option<(String, Instant)>> = Mutex::new(None);
+
+/// Sets the console verbosity.
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+/// Current console verbosity.
+pub fn verbosity() -> Verbosity {
+    Verbosity::from_u8(VERBOSITY.load(Ordering::Relaxed))
+}
+
+/// Sets the console output format.
+pub fn set_output_format(format: OutputFormat) {
+    JSON_OUTPUT.store(format == OutputFormat::Json, Ordering::Relaxed);
+}
+
+/// Current console output format.
+pub fn output_format() -> OutputFormat {
+    if JSON_OUTPUT.load(Ordering::Relaxed) {
+        OutputFormat::Json
+    } else {
+        OutputFormat::Text
+    }
+}
+
+/// Creates `<log_dir>/runs/run-<YYYYmmdd-HHMMSS>.log` and records every following message,
+/// command and command output in it. Returns the path of the run log.
+pub fn start_run_log(log_dir: &Path) -> Result<PathBuf, KernelUpdaterError> {
+    let runs_dir = log_dir.join(RUNS_DIR);
+    fs::create_dir_all(&runs_dir).map_err(|io_error| KernelUpdaterError::IOError {
+        path: runs_dir.clone(),
+        io_error,
+    })?;
+
+    let now = SystemTime::now();
+    let stamp = file_stamp(now);
+    // Runs started within the same second get `run-<stamp>-2.log`, `-3`, ...
+    let mut attempt = 1;
+    let (path, mut file) = loop {
+        let path = match attempt {
+            1 => runs_dir.join(format!("run-{stamp}.log")),
+            _ => runs_dir.join(format!("run-{stamp}-{attempt}.log")),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => break (path, file),
+            Err(io_error) if io_error.kind() == ErrorKind::AlreadyExists => attempt += 1,
+            Err(io_error) => return Err(KernelUpdaterError::IOError { path, io_error }),
+        }
+    };
+
+    let command_line: Vec<String> = env::args().collect();
+    writeln!(
+        file,
+        "# kernel-updater run started {}\n# Command line: {}",
+        format_timestamp(now),
+        command_line.join(" ")
+    )?;
+
+    *lock_run_log() = Some(RunLog {
+        file,
+        path: path.clone(),
+        started: Instant::now(),
+    });
+    Ok(path)
+}
+
+/// Path of the current run log, if one was started.
+pub fn run_log_path() -> Option<PathBuf> {
+    lock_run_log().as_ref().map(|run_log| run_log.path.clone())
+}
+
+/// Writes a message to the console (according to the verbosity) and to the run log.
+/// Warnings are printed as `Warning: <message>` and also emitted as `warning` events.
+pub fn log_message(level: LogLevel, message: fmt::Arguments) {
+    let message = message.to_string();
+    if verbosity() >= level.console_threshold() {
+        let line = match level {
+            LogLevel::Warn => format!("Warning: {message}"),
+            _ => message.clone(),
+        };
+        if level.is_stderr() {
+            eprintln!("{line}");
+        } else {
+            println!("{line}");
+        }
+    }
+    if level == LogLevel::Warn {
+        emit_event(Event::Warning {
+            message: message.trim().to_string(),
+        });
+    }
+    log_to_file(level, &message);
+}
+
+/// Writes a message to the run log only (e.g. output captured for parsing).
+pub fn log_to_file(level: LogLevel, message: &str) {
+    let mut guard = lock_run_log();
+    let Some(run_log) = guard.as_mut() else {
+        return;
+    };
+
+    let prefix = format!(
+        "{} +{:>8.1}s {}",
+        format_timestamp(SystemTime::now()),
+        run_log.started.elapsed().as_secs_f64(),
+        level.tag()
+    );
+    // Failing to write the log must never abort the run itself
+    for line in message.trim_start_matches('\n').lines() {
+        let _ = writeln!(run_log.file, "{prefix} {line}");
+    }
+    if message.is_empty() {
+        let _ = writeln!(run_log.file, "{prefix}");
+    }
+}
+
+/// Starts a new phase: prints `--- <title> ---`, records the phase markers and emits the
+/// phase events, closing the previous phase with its duration.
+pub fn log_phase(title: &str) {
+    end_phase();
+    if verbosity() >= Verbosity::Normal {
+        let banner = format!("\n--- {title} ---");
+        if LogLevel::Info.is_stderr() {
+            eprintln!("{banner}");
+        } else {
+            println!("{banner}");
+        }
+    }
+    log_to_file(LogLevel::Info, &format!("=== PHASE START: {title} ==="));
+    emit_event(Event::PhaseStarted {
+        phase: title.to_string(),
+    });
+    *lock_phase() = Some((title.to_string(), Instant::now()));
+}
+
+/// Closes the last phase and writes the final status and total duration of the run.
+pub fn finish_run_log(status: &str) {
+    end_phase();
+    let elapsed = match lock_run_log().as_ref() {
+        Some(run_log) => run_log.started.elapsed(),
+        None => return,
+    };
+    log_to_file(
+        LogLevel::Info,
+        &format!(
+            "# Run finished: {status} after {}",
+            format_duration(elapsed)
+        ),
+    );
+}
+
+/// Records the end of the current phase, if any.
+fn end_phase() {
+    let phase = lock_phase().take();
+    if let Some((title, started)) = phase {
+        let elapsed = started.elapsed();
+        log_to_file(
+            LogLevel::Info,
+            &format!("=== PHASE END: {title} ({}) ===", format_duration(elapsed)),
+        );
+        report::record(|report| {
+            report.phases.push(PhaseTiming {
+                phase: title.clone(),
+                duration_secs: duration_secs(elapsed),
+            })
+        });
+        emit_event(Event::PhaseFinished {
+            phase: title,
+            duration_secs: duration_secs(elapsed),
+        });
+    }
+}
+
+/// Locks the current phase, recovering it if a panicking thread poisoned the lock.
+fn lock_phase() -> std::sync::MutexGuard<'static, Option<(String, Instant)>> {
+    PHASE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Locks the run log, recovering it if a panicking thread poisoned the lock.
+fn lock_run_log() -> std::sync::MutexGuard<'static, Option<RunLog>> {
+    RUN_LOG
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Formats a duration as `1h02m03s`, `2m03s` or `3.4s`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..60 => format!("{:.1}s", duration.as_secs_f64()),
+        60..3600 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!(
+            "{}h{:02}m{:02}s",
+            seconds / 3600,
+            (seconds % 3600) / 60,
+            seconds % 60
+        ),
+    }
+}
+
+/// Formats a time as an ISO 8601 UTC timestamp (`2025-06-15T15:06:40Z`).
+pub fn format_timestamp(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = utc_fields(time);
+    format!("{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z")
+}
+
+/// Formats a time for file names (`20250615-150640`, UTC).
+pub fn file_stamp(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = utc_fields(time);
+    format!("{year:04}{month:02}{day:02}-{hour:02}{minute:02}{second:02}")
+}
+
+/// Splits a time into UTC calendar fields (year, month, day, hour, minute, second).
+fn utc_fields(time: SystemTime) -> (i64, u32, u32, u64, u64, u64) {
+    let seconds = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let seconds_of_day = seconds % 86400;
+    (
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        (seconds_of_day % 3600) / 60,
+        seconds_of_day % 60,
+    )
+}
+
+/// Converts days since 1970-01-01 to a proleptic Gregorian date (Howard Hinnant's algorithm).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Logs a progress message (shown unless `--quiet`).
+#[macro_export]
+macro_rules! info {
+    () => {
+        $crate::log_message($crate::LogLevel::Info, format_args!(""))
+    };
+    ($($arg:tt)*) => {
+        $crate::log_message($crate::LogLevel::Info, format_args!($($arg)*))
+    };
+}
+
+/// Logs a warning on stderr (always shown).
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log_message($crate::LogLevel::Warn, format_args!($($arg)*))
+    };
+}
+
+/// Logs a detail only shown with `--verbose` (always recorded in the run log).
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log_message($crate::LogLevel::Debug, format_args!($($arg)*))
+    };
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_logging
+#[cfg(test)]
+mod tests_logging {
+    use super::*;
+
+    #[test]
+    fn test_verbosity_from_flags() {
+        assert_eq!(Verbosity::from_flags(false, false), Verbosity::Normal);
+        assert_eq!(Verbosity::from_flags(true, false), Verbosity::Quiet);
+        assert_eq!(Verbosity::from_flags(false, true), Verbosity::Verbose);
+        assert!(Verbosity::Quiet < LogLevel::Info.console_threshold());
+        assert!(Verbosity::Quiet >= LogLevel::Warn.console_threshold());
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let epoch = SystemTime::UNIX_EPOCH;
+        assert_eq!(format_timestamp(epoch), "1970-01-01T00:00:00Z");
+
+        let time = epoch + Duration::from_secs(1_750_000_000);
+        assert_eq!(format_timestamp(time), "2025-06-15T15:06:40Z");
+        assert_eq!(file_stamp(time), "20250615-150640");
+
+        // Leap day
+        let leap = epoch + Duration::from_secs(1_709_210_096);
+        assert_eq!(format_timestamp(leap), "2024-02-29T12:34:56Z");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(3400)), "3.4s");
+        assert_eq!(format_duration(Duration::from_secs(123)), "2m03s");
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1h02m03s");
+    }
+}
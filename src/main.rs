@@ -1,70 +1,216 @@
-use clap::Parser;
 use kernel_updater::{
-    Arguments, Commands, Config, DkmsManager, KernelBuilder, KernelUpdaterResult, update_grub,
+    Arguments, Commands, Config, DkmsManager, ErrorReport, Event, KernelBuilder, KernelBundle,
+    KernelUpdaterError, KernelUpdaterResult, LogLevel, OutputFormat, Packager, Phase, RUNS_DIR,
+    Verbosity, duration_secs, emit_event, finish_run_log, finish_run_report, history_header, info,
+    load_run_reports, log_message, log_phase, output_format, record_artifacts, run_log_path,
+    set_output_format, set_verbosity, start_run_log, start_run_report, update_grub, warn,
 };
-use std::process;
+use std::{path::Path, process, time::Instant};
 
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("\nExecution stopped due to a fatal error:");
-        eprintln!("Error: {e}");
-        process::exit(1);
+    let started = Instant::now();
+    let args = Arguments::parse_args();
+    set_verbosity(Verbosity::from_flags(
+        args.output.quiet,
+        args.output.verbose,
+    ));
+    set_output_format(args.output.output_format);
+
+    // Listing past runs only reads their reports and is not recorded as a run itself
+    if let Some(Commands::History { limit }) = args.command {
+        if let Err(e) = show_history(&args.output.log_dir, limit) {
+            log_message(LogLevel::Error, format_args!("Error: {e}"));
+            process::exit(e.exit_code());
+        }
+        return;
+    }
+
+    // The run can proceed without its log (e.g. an unwritable --log-dir)
+    match start_run_log(&args.output.log_dir) {
+        Ok(path) => info!("Run log: {}", path.display()),
+        Err(err) => warn!("Run log disabled: {err}"),
+    }
+    emit_event(Event::RunStarted {
+        command: args
+            .command
+            .as_ref()
+            .map(|command| command.name().to_string()),
+        version_new: args.new.as_ref().map(ToString::to_string),
+        version_old: args.old.as_ref().map(ToString::to_string),
+        run_log: run_log_path(),
+    });
+    start_run_report(
+        args.command.as_ref(),
+        args.new.as_ref(),
+        args.old.as_ref(),
+        run_log_path(),
+    );
+
+    let result = run(args);
+    if let Err(e) = &result {
+        log_message(
+            LogLevel::Error,
+            format_args!("\nExecution stopped due to a fatal error:"),
+        );
+        log_message(LogLevel::Error, format_args!("Error: {e}"));
+        if let Some(hint) = e.hint() {
+            log_message(LogLevel::Error, format_args!("Hint: {hint}"));
+        }
+        finish_run_log("failed");
+        write_run_report(&result, started);
+        emit_event(Event::Result {
+            success: false,
+            duration_secs: duration_secs(started.elapsed()),
+            error: Some(ErrorReport::from_error(e)),
+        });
+        process::exit(e.exit_code());
+    }
+    info!("Execution completed with status: Success");
+    finish_run_log("success");
+    write_run_report(&result, started);
+    emit_event(Event::Result {
+        success: true,
+        duration_secs: duration_secs(started.elapsed()),
+        error: None,
+    });
+}
+
+/// Writes the timing and build report of the run next to its run log.
+fn write_run_report(result: &KernelUpdaterResult<()>, started: Instant) {
+    // The report is informative only and must never change the outcome of the run
+    match finish_run_report(result, started.elapsed()) {
+        Ok(Some(path)) => info!("Run report: {}", path.display()),
+        Ok(None) => {}
+        Err(err) => warn!("Could not write the run report: {err}"),
     }
-    println!("Execution completed with status: Success");
 }
 
-fn run() -> KernelUpdaterResult<()> {
-    let args = Arguments::parse();
-    let config = Config::new(args)?;
+/// Lists the most recent runs from their reports (JSON Lines with `--output json`).
+fn show_history(log_dir: &Path, limit: usize) -> KernelUpdaterResult<()> {
+    let reports = load_run_reports(log_dir)?;
+    let recent = &reports[reports.len().saturating_sub(limit)..];
+
+    if output_format() == OutputFormat::Json {
+        for report in recent {
+            let line = serde_json::to_string(report)
+                .map_err(|source| KernelUpdaterError::RunReportSerializationError { source })?;
+            println!("{line}");
+        }
+        return Ok(());
+    }
+
+    // The listing is the output of the command itself, printed whatever the verbosity
+    if recent.is_empty() {
+        println!(
+            "No run reports found in {}",
+            log_dir.join(RUNS_DIR).display()
+        );
+        return Ok(());
+    }
+    println!("{}", history_header());
+    for report in recent {
+        println!("{}", report.history_line());
+    }
+    Ok(())
+}
+
+fn run(args: Arguments) -> KernelUpdaterResult<()> {
+    let config = in_phase(Phase::Setup, || Config::new(args))?;
 
     config.show_summary();
 
-    let builder = KernelBuilder::new(&config);
-    let dkms = DkmsManager::new(&config);
+    let result = execute(&config);
+    record_artifacts(&config);
+    result
+}
+
+/// Runs the selected command (or the full update pipeline).
+fn execute(config: &Config) -> KernelUpdaterResult<()> {
+    let builder = KernelBuilder::new(config);
+    let dkms = DkmsManager::new(config);
 
     match &config.command {
         Some(Commands::KernelCompile) => {
-            println!("Executing: Kernel Compilation...");
-            builder.compile()?;
+            log_phase("Kernel Compilation");
+            in_phase(Phase::Compile, || builder.compile())?;
+
+            if config.dkms.precheck || config.dkms.trial_build {
+                in_phase(Phase::Dkms, || dkms.precheck_modules())?;
+            }
         }
-        Some(Commands::KernelInstall) => {
-            println!("Executing: Kernel Installation...");
-            builder.install()?;
+        Some(Commands::KernelInstall { from_bundle }) => {
+            log_phase("Kernel Installation");
+            match from_bundle {
+                Some(bundle) => {
+                    in_phase(Phase::Bundle, || KernelBundle::new(config).install(bundle))?;
+                }
+                None => in_phase(Phase::Install, || builder.install())?,
+            }
 
             // Build DKMS modules (nvidia, etc.) before creating the initramfs image
-            if let Err(err) = dkms.install_modules() {
-                eprintln!("Warning: DKMS installation failed or skipped: {err}");
-            }
+            in_phase(Phase::Dkms, || dkms.install_modules())?;
 
-            builder.run_mkinitcpio()?;
-            update_grub()?;
+            in_phase(Phase::Boot, || {
+                builder.run_mkinitcpio()?;
+                update_grub(config)
+            })?;
         }
         Some(Commands::DkmsInstall) => {
-            println!("Executing: DKMS Configuration...");
-            dkms.remove_modules()?;
-            dkms.install_modules()?;
-            builder.run_mkinitcpio()?;
-            update_grub()?;
+            log_phase("DKMS Configuration");
+            in_phase(Phase::Dkms, || {
+                dkms.remove_modules()?;
+                dkms.install_modules()
+            })?;
+            in_phase(Phase::Boot, || {
+                builder.run_mkinitcpio()?;
+                update_grub(config)
+            })?;
+        }
+        Some(Commands::Bundle { bundle_dir }) => {
+            log_phase("Kernel Bundling");
+            let bundle = in_phase(Phase::Bundle, || {
+                KernelBundle::new(config).create(bundle_dir)
+            })?;
+            info!("Bundle created: {}", bundle.display());
         }
+        Some(Commands::Package {
+            format,
+            package_dir,
+        }) => {
+            log_phase("Kernel Packaging");
+            in_phase(Phase::Package, || {
+                Packager::new(config).package(*format, package_dir)
+            })?;
+        }
+        Some(Commands::History { .. }) => unreachable!("history is handled before the run starts"),
         None => {
-            println!("Executing sequence: Complete Upgrade Pipeline...");
+            info!("Executing sequence: Complete Upgrade Pipeline...");
 
-            println!("\n--- Phase 1 of 4: Compiling Source Tree ---");
-            builder.compile()?;
+            log_phase("Phase 1 of 4: Compiling Source Tree");
+            in_phase(Phase::Compile, || builder.compile())?;
 
-            println!("\n--- Phase 2 of 4: Installing Target Kernel Tree ---");
-            builder.install()?;
+            // Stop before touching the installed system if a required module cannot build
+            if config.dkms.precheck || config.dkms.trial_build {
+                in_phase(Phase::Dkms, || dkms.precheck_modules())?;
+            }
 
-            println!("\n--- Phase 3 of 4: Updating DKMS Registries ---");
-            dkms.remove_modules()?;
-            dkms.install_modules()?;
+            log_phase("Phase 2 of 4: Installing Target Kernel Tree");
+            in_phase(Phase::Install, || builder.install())?;
 
-            println!("\n--- Phase 4 of 4: Rebuilding Boot Configurations ---");
-            builder.run_mkinitcpio()?;
-            update_grub()?;
+            log_phase("Phase 3 of 4: Updating DKMS Registries");
+            in_phase(Phase::Dkms, || {
+                dkms.remove_modules()?;
+                dkms.install_modules()
+            })?;
+
+            log_phase("Phase 4 of 4: Rebuilding Boot Configurations");
+            in_phase(Phase::Boot, || {
+                builder.run_mkinitcpio()?;
+                update_grub(config)
+            })?;
 
             if let Some(ref old) = config.version_old {
-                println!(
+                info!(
                     "\nKernel updated successfully: {old} -> {}",
                     config.version_new
                 );
@@ -74,3 +220,11 @@ fn run() -> KernelUpdaterResult<()> {
 
     Ok(())
 }
+
+/// Runs a step of the pipeline, attributing its failure to `phase` (message and exit code).
+fn in_phase<T>(
+    phase: Phase,
+    step: impl FnOnce() -> KernelUpdaterResult<T>,
+) -> KernelUpdaterResult<T> {
+    step().map_err(|err| err.in_phase(phase))
+}
@@ -0,0 +1,448 @@
+use crate::{
+    AtomicWriteExt, Config, KernelBuilder, PackageFormat, error::KernelUpdaterError,
+    headers::KernelHeaders, info, utils::run_command_in_dir,
+};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Release number of every package built from a given kernel version.
+const PACKAGE_RELEASE: u32 = 1;
+
+/// Packages a kernel package always depends on. DKMS modules are always managed: an empty
+/// `--dkms-module` list selects the default modules.
+const BASE_DEPENDS: &[&str] = &["coreutils", "kmod", "initramfs", "dkms"];
+
+/// Builds the `.MTREE` file list and the zstd-compressed archive inside the staging tree,
+/// as `makepkg` does (`makepkg` itself refuses to run as root). `$1` is the package path.
+const ARCHIVE_SCRIPT: &str = "set -e\n\
+    LANG=C bsdtar -czf .MTREE --format=mtree \
+    --options='!all,use-set,type,uid,gid,mode,time,size,md5,sha256,link' .PKGINFO *\n\
+    LANG=C bsdtar --zstd -cf \"$1\" .MTREE .PKGINFO *";
+
+/// Metadata of an Arch package, rendered as its `.PKGINFO`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageInfo {
+    pub name: String,
+    /// `pkgver-pkgrel`, e.g. `6.15.4-1`.
+    pub version: String,
+    pub description: String,
+    pub arch: String,
+    pub depends: Vec<String>,
+    /// Seconds since the Unix epoch.
+    pub build_date: u64,
+    /// Installed size in bytes.
+    pub size: u64,
+}
+
+impl PackageInfo {
+    /// Derives the metadata from the configuration: the package is named after the new
+    /// kernel ident (lowercase, as pacman requires), so several kernels can be installed.
+    pub fn from_config(config: &Config) -> Self {
+        let depends: Vec<String> = BASE_DEPENDS.iter().map(|dep| dep.to_string()).collect();
+
+        Self {
+            name: format!("linux-{}", config.kernel_ident_name_new.to_lowercase()),
+            version: format!("{}-{PACKAGE_RELEASE}", config.version_new),
+            description: format!(
+                "Linux {} kernel, modules and headers ({})",
+                config.version_new, config.custom_kernel_suffix
+            ),
+            arch: config.arch.machine().to_string(),
+            depends,
+            build_date: 0,
+            size: 0,
+        }
+    }
+
+    /// File name of the package archive.
+    pub fn file_name(&self) -> String {
+        format!("{}-{}-{}.pkg.tar.zst", self.name, self.version, self.arch)
+    }
+
+    /// Content of the `.PKGINFO` file read by pacman.
+    pub fn render(&self) -> String {
+        let mut pkginfo = format!(
+            "# Generated by kernel-updater\n\
+            pkgname = {name}\n\
+            pkgbase = {name}\n\
+            pkgver = {version}\n\
+            pkgdesc = {description}\n\
+            url = https://www.kernel.org/\n\
+            builddate = {build_date}\n\
+            packager = kernel-updater\n\
+            size = {size}\n\
+            arch = {arch}\n\
+            license = GPL-2.0-only\n",
+            name = self.name,
+            version = self.version,
+            description = self.description,
+            build_date = self.build_date,
+            size = self.size,
+            arch = self.arch,
+        );
+        for depend in &self.depends {
+            pkginfo.push_str(&format!("depend = {depend}\n"));
+        }
+        pkginfo
+    }
+}
+
+/// Produces distributable packages of the compiled kernel instead of installing it,
+/// leaving the host untouched.
+pub struct Packager<'a> {
+    config: &'a Config,
+}
+
+impl<'a> Packager<'a> {
+    /// Creates a new `Packager` instance.
+    pub fn new(config: &'a Config) -> Self {
+        Self { config }
+    }
+
+    /// Builds the packages of the given format and moves them into `package_dir`.
+    /// Returns the paths of the produced packages.
+    pub fn package(
+        &self,
+        format: PackageFormat,
+        package_dir: &Path,
+    ) -> Result<Vec<PathBuf>, KernelUpdaterError> {
+        // Fail early, with the usual hint, if the tree has not been compiled
+        KernelBuilder::new(self.config).kernel_image()?;
+
+        fs::create_dir_all(package_dir)?;
+        let package_dir = package_dir.canonicalize()?;
+
+        let packages = match format.make_target() {
+            Some(target) => self.kbuild_package(format, target, &package_dir)?,
+            None => vec![self.arch_package(&package_dir)?],
+        };
+
+        for package in &packages {
+            info!("Package created: {}", package.display());
+        }
+        Ok(packages)
+    }
+
+    /// Runs a Kbuild packaging target and collects what it produced.
+    fn kbuild_package(
+        &self,
+        format: PackageFormat,
+        target: &str,
+        package_dir: &Path,
+    ) -> Result<Vec<PathBuf>, KernelUpdaterError> {
+        let builder = KernelBuilder::new(self.config);
+        env::set_current_dir(&self.config.kernel_src_dir_path)?;
+
+        let started = SystemTime::now();
+        let jobs = format!("-j{}", builder.build_jobs()?);
+        let deb_version = format!(
+            "KDEB_PKGVERSION={}",
+            PackageInfo::from_config(self.config).version
+        );
+        let mut args = vec![jobs.as_str()];
+        if format == PackageFormat::Deb {
+            args.push(&deb_version);
+        }
+        args.push(target);
+
+        info!("Building {format:?} packages with `make {target}`...");
+        builder.make(&args)?;
+
+        let output_dir = self.kbuild_output_dir(format);
+        let produced = collect_packages(format, &output_dir, started)?;
+        if produced.is_empty() {
+            return Err(KernelUpdaterError::PackageNotProduced {
+                target: target.to_string(),
+                dir: output_dir,
+            });
+        }
+
+        produced
+            .iter()
+            .map(|package| move_file(package, package_dir))
+            .collect()
+    }
+
+    /// Directory where Kbuild leaves the packages of the given format.
+    pub fn kbuild_output_dir(&self, format: PackageFormat) -> PathBuf {
+        let build_dir = &self.config.kernel_build_dir_path;
+        match format {
+            // dpkg-buildpackage writes next to the build tree
+            PackageFormat::Deb => build_dir
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| build_dir.clone()),
+            PackageFormat::Rpm => build_dir.join("rpmbuild/RPMS"),
+            PackageFormat::Arch | PackageFormat::Tar => build_dir.clone(),
+        }
+    }
+
+    /// Stages the kernel into a scratch tree and archives it as `<name>-<ver>-<arch>.pkg.tar.zst`.
+    fn arch_package(&self, package_dir: &Path) -> Result<PathBuf, KernelUpdaterError> {
+        let mut info = PackageInfo::from_config(self.config);
+        let staging = package_dir.join(format!("{}-staging", info.name));
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+
+        self.stage_arch_package(&staging, &mut info)?;
+
+        let package_path = package_dir.join(info.file_name());
+        info!("Creating package archive: {}", package_path.display());
+        let package_arg = package_path.display().to_string();
+        run_command_in_dir(
+            "sh",
+            &["-c", ARCHIVE_SCRIPT, "sh", &package_arg],
+            Some(&staging),
+        )?;
+
+        fs::remove_dir_all(&staging)?;
+        Ok(package_path)
+    }
+
+    /// Installs the modules, kernel image, headers and device trees under `staging`
+    /// following the Arch layout (`/usr/lib/modules/<ident>/{vmlinuz,pkgbase,build}`),
+    /// then writes `.PKGINFO`, completing `info` with the build date and installed size.
+    pub fn stage_arch_package(
+        &self,
+        staging: &Path,
+        info: &mut PackageInfo,
+    ) -> Result<(), KernelUpdaterError> {
+        let builder = KernelBuilder::new(self.config);
+        env::set_current_dir(&self.config.kernel_src_dir_path)?;
+        let image = builder.kernel_image()?;
+
+        let usr_dir = staging.join("usr");
+        fs::create_dir_all(&usr_dir)?;
+        info!("Staging modules under {}...", usr_dir.display());
+        let install_mod_path = format!("INSTALL_MOD_PATH={}", usr_dir.display());
+        builder.make(&["modules_install", &install_mod_path])?;
+
+        let modules_dir = usr_dir
+            .join("lib/modules")
+            .join(&self.config.kernel_ident_name_new);
+        fs::create_dir_all(&modules_dir)?;
+
+        // Kbuild links these to the build machine's trees; the headers replace them
+        for link in ["build", "source"] {
+            let link_path = modules_dir.join(link);
+            if link_path.is_symlink() {
+                fs::remove_file(&link_path)?;
+            }
+        }
+
+        // mkinitcpio's pacman hook copies vmlinuz to /boot/vmlinuz-<pkgbase>
+        modules_dir.join("vmlinuz").atomic_copy_from(&image)?;
+        fs::write(modules_dir.join("pkgbase"), format!("{}\n", info.name))?;
+        KernelHeaders::new(self.config).install(&modules_dir.join("build"))?;
+
+        if let Some(dtbs_path) = &self.config.dtbs_install_path {
+            let relative = self.config.path_in_target(dtbs_path);
+            let staged_dtbs = staging.join(relative.strip_prefix("/").unwrap_or(&relative));
+            let install_dtbs_path = format!("INSTALL_DTBS_PATH={}", staged_dtbs.display());
+            builder.make(&["dtbs_install", &install_dtbs_path])?;
+        }
+
+        info.size = tree_size(staging)?;
+        info.build_date = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        fs::write(staging.join(".PKGINFO"), info.render())?;
+        Ok(())
+    }
+}
+
+/// Lists the packages of `format` modified since `since` in `dir`
+/// (and in its subdirectories for RPM, which sorts them by architecture).
+pub fn collect_packages(
+    format: PackageFormat,
+    dir: &Path,
+    since: SystemTime,
+) -> Result<Vec<PathBuf>, KernelUpdaterError> {
+    let mut packages = Vec::new();
+    if !dir.is_dir() {
+        return Ok(packages);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+
+        if metadata.is_dir() {
+            if format == PackageFormat::Rpm {
+                packages.extend(collect_packages(format, &path, since)?);
+            }
+        } else if format.matches(&entry.file_name().to_string_lossy())
+            && metadata.modified()? >= since
+        {
+            packages.push(path);
+        }
+    }
+
+    packages.sort();
+    Ok(packages)
+}
+
+/// Moves `file` into `dir`, copying when they are on different filesystems.
+fn move_file(file: &Path, dir: &Path) -> Result<PathBuf, KernelUpdaterError> {
+    let file_name = file.file_name().unwrap_or(file.as_os_str());
+    let target = dir.join(file_name);
+    if fs::rename(file, &target).is_err() {
+        target.atomic_copy_from(file)?;
+        fs::remove_file(file)?;
+    }
+    Ok(target)
+}
+
+/// Total size in bytes of the regular files under `dir`.
+fn tree_size(dir: &Path) -> Result<u64, KernelUpdaterError> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += tree_size(&entry.path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_package
+#[cfg(test)]
+mod tests_package {
+    use super::*;
+    use crate::Commands;
+    use crate::test_utils::{CurrentDirGuard, TempDirGuard, staged_config};
+    use std::time::Duration;
+
+    fn create_mock_config(temp_dir: &Path) -> Config {
+        staged_config(temp_dir, Commands::KernelCompile)
+    }
+
+    #[test]
+    fn test_package_info_from_config() {
+        let temp_dir = TempDirGuard::new("package-info");
+        let config = create_mock_config(&temp_dir.path);
+        let mut info = PackageInfo::from_config(&config);
+        info.build_date = 1750000000;
+        info.size = 4096;
+
+        assert_eq!(info.name, "linux-6.15.4-testsuffix");
+        assert_eq!(
+            info.file_name(),
+            "linux-6.15.4-testsuffix-6.15.4-1-x86_64.pkg.tar.zst"
+        );
+
+        let pkginfo = info.render();
+        assert!(pkginfo.contains("pkgname = linux-6.15.4-testsuffix\n"));
+        assert!(pkginfo.contains("pkgver = 6.15.4-1\n"));
+        assert!(pkginfo.contains("size = 4096\n"));
+        assert!(pkginfo.contains("arch = x86_64\n"));
+        // The default modules are managed without --dkms-module, so dkms is always needed
+        assert!(config.dkms.modules.is_empty());
+        assert!(pkginfo.ends_with("depend = kmod\ndepend = initramfs\ndepend = dkms\n"));
+    }
+
+    #[test]
+    fn test_collect_packages_by_format_and_age() {
+        let temp_dir = TempDirGuard::new("package-collect");
+        let dir = &temp_dir.path;
+        let rpm_dir = dir.join("rpmbuild/RPMS/x86_64");
+        fs::create_dir_all(&rpm_dir).unwrap();
+        for file in [
+            "linux-image-6.15.4_6.15.4-1_amd64.deb",
+            "linux-upstream_6.15.4-1_amd64.changes",
+            "linux-6.15.4-x86.tar",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        fs::write(rpm_dir.join("kernel-6.15.4-1.x86_64.rpm"), "").unwrap();
+
+        let since = SystemTime::now() - Duration::from_secs(60);
+        assert_eq!(
+            collect_packages(PackageFormat::Deb, dir, since).unwrap(),
+            vec![dir.join("linux-image-6.15.4_6.15.4-1_amd64.deb")]
+        );
+        assert_eq!(
+            collect_packages(PackageFormat::Tar, dir, since).unwrap(),
+            vec![dir.join("linux-6.15.4-x86.tar")]
+        );
+        assert_eq!(
+            collect_packages(PackageFormat::Rpm, &dir.join("rpmbuild/RPMS"), since).unwrap(),
+            vec![rpm_dir.join("kernel-6.15.4-1.x86_64.rpm")]
+        );
+
+        // Packages left over from an earlier run are ignored
+        let later = SystemTime::now() + Duration::from_secs(60);
+        assert!(
+            collect_packages(PackageFormat::Deb, dir, later)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_stage_arch_package_layout() {
+        let temp_dir = TempDirGuard::new("package-stage");
+        let _cwd_guard = CurrentDirGuard::new();
+        let config = create_mock_config(&temp_dir.path);
+        let packager = Packager::new(&config);
+
+        // A compiled tree whose Makefile stands in for Kbuild's modules_install
+        let src_dir = &config.kernel_src_dir_path;
+        let image = src_dir.join(&config.arch.image_candidates()[0]);
+        fs::create_dir_all(image.parent().unwrap()).unwrap();
+        fs::write(&image, "kernel image").unwrap();
+        fs::write(src_dir.join(".config"), "CONFIG_MODULES=y\n").unwrap();
+        fs::write(src_dir.join("Module.symvers"), "").unwrap();
+        fs::write(
+            src_dir.join("Makefile"),
+            "modules_install:\n\
+             \tmkdir -p $(INSTALL_MOD_PATH)/lib/modules/6.15.4-TestSuffix/kernel\n\
+             \ttouch $(INSTALL_MOD_PATH)/lib/modules/6.15.4-TestSuffix/kernel/dummy.ko\n\
+             \tln -s /somewhere $(INSTALL_MOD_PATH)/lib/modules/6.15.4-TestSuffix/build\n",
+        )
+        .unwrap();
+
+        let staging = temp_dir.path.join("staging");
+        let mut info = PackageInfo::from_config(&config);
+        packager.stage_arch_package(&staging, &mut info).unwrap();
+
+        let modules_dir = staging.join("usr/lib/modules/6.15.4-TestSuffix");
+        assert!(modules_dir.join("kernel/dummy.ko").exists());
+        assert_eq!(
+            fs::read_to_string(modules_dir.join("vmlinuz")).unwrap(),
+            "kernel image"
+        );
+        assert_eq!(
+            fs::read_to_string(modules_dir.join("pkgbase")).unwrap(),
+            "linux-6.15.4-testsuffix\n"
+        );
+        // The dangling link was replaced by the headers tree
+        assert!(modules_dir.join("build/.config").is_file());
+        assert!(info.size > 0);
+        assert_eq!(
+            fs::read_to_string(staging.join(".PKGINFO")).unwrap(),
+            info.render()
+        );
+        // Nothing was installed into the system paths of the (staged) host
+        assert!(!temp_dir.path.join("boot").exists());
+        assert!(!temp_dir.path.join("lib/modules/6.15.4-TestSuffix").exists());
+    }
+}
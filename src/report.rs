@@ -0,0 +1,679 @@
+use crate::{
+    Config, KernelBuilder, KernelUpdaterError, Version,
+    args::Commands,
+    dkms::{DkmsModuleReport, DkmsOutcome},
+    kconfig::ConfigDiff,
+    logging::{RUNS_DIR, format_duration, format_timestamp},
+    traits::AtomicWriteExt,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+    time::{Duration, SystemTime},
+};
+
+/// Version of the run report format, bumped on any incompatible change.
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// Extension of the JSON report written next to each run log (`run-<stamp>.report.json`).
+const REPORT_JSON_EXTENSION: &str = "report.json";
+
+/// Extension of the text report written next to each run log (`run-<stamp>.report.txt`).
+const REPORT_TEXT_EXTENSION: &str = "report.txt";
+
+/// Timing and build report of one run, filled in while the run progresses and written
+/// next to the run log at the end. Read back by the `history` command.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RunReport {
+    pub schema: u32,
+    /// Start of the run (ISO 8601 UTC).
+    pub started: String,
+    /// Subcommand name, `None` for the full update pipeline.
+    pub command: Option<String>,
+    pub version_new: Option<String>,
+    pub version_old: Option<String>,
+    /// Ident of the new kernel (`<version>-<suffix>`), known once the arguments are validated.
+    pub kernel: Option<String>,
+    pub success: bool,
+    pub exit_code: i32,
+    pub error: Option<String>,
+    pub total_secs: f64,
+    /// Duration of each phase, in the order they ran.
+    pub phases: Vec<PhaseTiming>,
+    /// Parallel make jobs of the kernel build.
+    pub jobs: Option<usize>,
+    /// Toolchain description, e.g. `clang (LLVM=1, ccache)`.
+    pub toolchain: Option<String>,
+    /// Variables passed on the `make` command line, e.g. `LLVM=1 CC=ccache clang`.
+    pub make_variables: Option<String>,
+    /// Directory the kernel was built in (the source tree, or `--build-dir`).
+    pub build_dir: Option<PathBuf>,
+    /// Statistics printed by `ccache -s`/`sccache -s` after the build.
+    pub compiler_cache_stats: Option<String>,
+    /// Size in bytes of the kernel image (installed image when the command installs the kernel,
+    /// otherwise the one in the build tree).
+    pub image_size: Option<u64>,
+    pub modules: Option<ModuleStats>,
+    /// DKMS modules handled for the new kernel.
+    pub dkms: Vec<DkmsModuleVersion>,
+    pub config_diff: Option<ConfigDiffSummary>,
+    pub run_log: Option<PathBuf>,
+}
+
+/// Duration of one phase.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PhaseTiming {
+    pub phase: String,
+    pub duration_secs: f64,
+}
+
+/// Number and total size in bytes of the kernel modules.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ModuleStats {
+    pub count: usize,
+    pub size: u64,
+}
+
+/// Outcome of one DKMS module: `installed` (with its version), `skipped` or `failed`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DkmsModuleVersion {
+    pub name: String,
+    pub version: Option<String>,
+    pub status: String,
+}
+
+/// Number of kernel options added, removed and changed by `olddefconfig`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ConfigDiffSummary {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+}
+
+impl From<&DkmsModuleReport> for DkmsModuleVersion {
+    fn from(report: &DkmsModuleReport) -> Self {
+        let (version, status) = match &report.outcome {
+            DkmsOutcome::Installed { version } => (Some(version.clone()), "installed"),
+            DkmsOutcome::NotRegistered => (None, "skipped"),
+            DkmsOutcome::Failed { .. } => (report.module.version.clone(), "failed"),
+        };
+        Self {
+            name: report.module.name.clone(),
+            version,
+            status: status.to_string(),
+        }
+    }
+}
+
+impl From<&ConfigDiff> for ConfigDiffSummary {
+    fn from(diff: &ConfigDiff) -> Self {
+        Self {
+            added: diff.added.len(),
+            removed: diff.removed.len(),
+            changed: diff.changed.len(),
+        }
+    }
+}
+
+/// Report of the current run.
+static RUN_REPORT: Mutex<Option<RunReport>> = Mutex::new(None);
+
+/// Starts collecting the report of this run.
+pub fn start_run_report(
+    command: Option<&Commands>,
+    version_new: Option<&Version>,
+    version_old: Option<&Version>,
+    run_log: Option<PathBuf>,
+) {
+    *lock_run_report() = Some(RunReport {
+        schema: REPORT_SCHEMA_VERSION,
+        started: format_timestamp(SystemTime::now()),
+        command: command.map(|command| command.name().to_string()),
+        version_new: version_new.map(ToString::to_string),
+        version_old: version_old.map(ToString::to_string),
+        run_log,
+        ..Default::default()
+    });
+}
+
+/// Updates the report of the current run, if one was started.
+pub fn record(update: impl FnOnce(&mut RunReport)) {
+    if let Some(report) = lock_run_report().as_mut() {
+        update(report);
+    }
+}
+
+/// Records the kernel and the size of its image and modules, as found on disk.
+pub fn record_artifacts(config: &Config) {
+    let image_size =
+        artifact_image(config).and_then(|image| fs::metadata(image).ok().map(|meta| meta.len()));
+    let modules = module_stats(config);
+
+    record(|report| {
+        report.kernel = Some(config.kernel_ident_name_new.clone());
+        report.image_size = image_size;
+        report.modules = modules;
+    });
+}
+
+/// Completes the report with the outcome of the run and writes it next to the run log as
+/// `run-<stamp>.report.json` and `run-<stamp>.report.txt`. Returns the JSON report path.
+pub fn finish_run_report(
+    result: &Result<(), KernelUpdaterError>,
+    total: Duration,
+) -> Result<Option<PathBuf>, KernelUpdaterError> {
+    let Some(mut report) = lock_run_report().take() else {
+        return Ok(None);
+    };
+    report.success = result.is_ok();
+    report.exit_code = result
+        .as_ref()
+        .map_or_else(KernelUpdaterError::exit_code, |_| 0);
+    report.error = result.as_ref().err().map(ToString::to_string);
+    report.total_secs = (total.as_millis() as f64) / 1000.0;
+
+    report.save()
+}
+
+/// Reads every run report in `<log_dir>/runs`, oldest first. Unreadable reports are skipped.
+pub fn load_run_reports(log_dir: &Path) -> Result<Vec<RunReport>, KernelUpdaterError> {
+    let runs_dir = log_dir.join(RUNS_DIR);
+    if !runs_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(&runs_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().ends_with(REPORT_JSON_EXTENSION))
+        })
+        .collect();
+    paths.sort_by_key(|path| run_order(path));
+
+    Ok(paths
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect())
+}
+
+impl RunReport {
+    /// Writes the report next to its run log (nothing without a run log).
+    /// Returns the JSON report path.
+    pub fn save(&self) -> Result<Option<PathBuf>, KernelUpdaterError> {
+        let Some(run_log) = &self.run_log else {
+            return Ok(None);
+        };
+        let json_path = run_log.with_extension(REPORT_JSON_EXTENSION);
+        let text_path = run_log.with_extension(REPORT_TEXT_EXTENSION);
+
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|source| KernelUpdaterError::RunReportSerializationError { source })?;
+        write_file(&json_path, &format!("{json}\n"))?;
+        write_file(&text_path, &self.render())?;
+        Ok(Some(json_path))
+    }
+
+    /// Human readable report.
+    pub fn render(&self) -> String {
+        let mut text = format!(
+            "Run started: {}\nCommand: {}\n",
+            self.started,
+            self.command_name()
+        );
+        if let Some(kernel) = &self.kernel {
+            text.push_str(&format!("Kernel: {kernel}\n"));
+        }
+        if let Some(old) = &self.version_old {
+            text.push_str(&format!("Previous version: {old}\n"));
+        }
+        text.push_str(&format!("Result: {}\n", self.result()));
+        if let Some(error) = &self.error {
+            text.push_str(&format!(
+                "Error: {}\n",
+                error.lines().next().unwrap_or_default()
+            ));
+        }
+        text.push_str(&format!("Total time: {}\n", format_secs(self.total_secs)));
+
+        if !self.phases.is_empty() {
+            text.push_str("\nPhases:\n");
+            for timing in &self.phases {
+                text.push_str(&format!(
+                    "  {:<48} {:>10}\n",
+                    timing.phase,
+                    format_secs(timing.duration_secs)
+                ));
+            }
+        }
+
+        let mut build = Vec::new();
+        if let Some(jobs) = self.jobs {
+            build.push(format!("Jobs: {jobs}"));
+        }
+        if let Some(toolchain) = &self.toolchain {
+            build.push(format!("Toolchain: {toolchain}"));
+        }
+        if let Some(variables) = &self.make_variables {
+            build.push(format!("Make variables: {variables}"));
+        }
+        if let Some(build_dir) = &self.build_dir {
+            build.push(format!("Build directory: {}", build_dir.display()));
+        }
+        if let Some(size) = self.image_size {
+            build.push(format!("Kernel image: {}", format_size(size)));
+        }
+        if let Some(modules) = self.modules {
+            build.push(format!(
+                "Modules: {} ({})",
+                modules.count,
+                format_size(modules.size)
+            ));
+        }
+        if let Some(diff) = self.config_diff {
+            build.push(format!(
+                "Config diff: {} added, {} removed, {} changed",
+                diff.added, diff.removed, diff.changed
+            ));
+        }
+        if !build.is_empty() {
+            text.push_str("\nBuild:\n");
+            for line in build {
+                text.push_str(&format!("  {line}\n"));
+            }
+        }
+
+        if let Some(stats) = &self.compiler_cache_stats {
+            text.push_str("\nCompiler cache statistics:\n");
+            for line in stats.lines() {
+                text.push_str(&format!("  {line}\n"));
+            }
+        }
+
+        if !self.dkms.is_empty() {
+            text.push_str("\nDKMS modules:\n");
+            for module in &self.dkms {
+                text.push_str(&format!(
+                    "  {:<24} {:<16} {}\n",
+                    module.name,
+                    module.version.as_deref().unwrap_or("-"),
+                    module.status
+                ));
+            }
+        }
+        text
+    }
+
+    /// One `history` line: start, command, kernel, result, total time and phase durations.
+    pub fn history_line(&self) -> String {
+        let phases = self
+            .phases
+            .iter()
+            .map(|timing| format!("{} {}", timing.phase, format_secs(timing.duration_secs)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "{:<20}  {:<14}  {:<28}  {:<16}  {:>9}  {phases}",
+            self.started,
+            self.command_name(),
+            self.kernel
+                .as_deref()
+                .or(self.version_new.as_deref())
+                .unwrap_or("-"),
+            self.result(),
+            format_secs(self.total_secs),
+        )
+        .trim_end()
+        .to_string()
+    }
+
+    /// Command name shown to users; the full pipeline is `update`.
+    fn command_name(&self) -> &str {
+        self.command.as_deref().unwrap_or("update")
+    }
+
+    /// `success`, or `failed (exit N)`.
+    fn result(&self) -> String {
+        if self.success {
+            "success".to_string()
+        } else {
+            format!("failed (exit {})", self.exit_code)
+        }
+    }
+}
+
+/// Header of the `history` listing, aligned with [`RunReport::history_line`].
+pub fn history_header() -> String {
+    format!(
+        "{:<20}  {:<14}  {:<28}  {:<16}  {:>9}  PHASES",
+        "STARTED", "COMMAND", "KERNEL", "RESULT", "TOTAL"
+    )
+}
+
+/// Sort key of a report file: its UTC stamp, then the counter of runs started within the
+/// same second (`run-<stamp>.report.json` is 1, `run-<stamp>-2.report.json` is 2).
+fn run_order(path: &Path) -> (String, u32) {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let run = name
+        .trim_start_matches("run-")
+        .trim_end_matches(REPORT_JSON_EXTENSION)
+        .trim_end_matches('.');
+    // The stamp itself is `YYYYmmdd-HHMMSS`
+    match run.rsplit_once('-') {
+        Some((stamp, counter)) if stamp.contains('-') => {
+            (stamp.to_string(), counter.parse().unwrap_or(1))
+        }
+        _ => (run.to_string(), 1),
+    }
+}
+
+/// Whether the command installs the new kernel, so that the installed image and modules
+/// are its artifacts. The others (`kernel-compile`, `bundle`, `package`) leave the system
+/// untouched, where an earlier install of the same version may linger.
+fn installs_kernel(config: &Config) -> bool {
+    matches!(
+        config.command,
+        None | Some(Commands::KernelInstall { .. } | Commands::DkmsInstall)
+    )
+}
+
+/// Image of the new kernel: the installed one for installing commands when present,
+/// otherwise the one in the build tree.
+fn artifact_image(config: &Config) -> Option<PathBuf> {
+    if installs_kernel(config) && config.vmlinuz_install_path.is_file() {
+        Some(config.vmlinuz_install_path.clone())
+    } else {
+        KernelBuilder::new(config).kernel_image().ok()
+    }
+}
+
+/// Counts the modules of the new kernel: installed ones for installing commands when
+/// present, otherwise those listed in the build tree's `modules.order`.
+fn module_stats(config: &Config) -> Option<ModuleStats> {
+    let installed = config
+        .kernel_module_base
+        .join(&config.kernel_ident_name_new)
+        .join("kernel");
+    if installs_kernel(config) && installed.is_dir() {
+        let mut stats = ModuleStats { count: 0, size: 0 };
+        collect_module_stats(&installed, &mut stats);
+        return Some(stats);
+    }
+
+    let build_dir = &config.kernel_build_dir_path;
+    let order = fs::read_to_string(build_dir.join("modules.order")).ok()?;
+    let mut stats = ModuleStats { count: 0, size: 0 };
+    for line in order.lines().filter(|line| !line.is_empty()) {
+        let module = build_dir.join(line).with_extension("ko");
+        if let Ok(metadata) = fs::metadata(module) {
+            stats.count += 1;
+            stats.size += metadata.len();
+        }
+    }
+    Some(stats)
+}
+
+/// Adds every `*.ko` file (compressed or not) under `dir`, without following symlinks.
+fn collect_module_stats(dir: &Path, stats: &mut ModuleStats) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if file_type.is_dir() {
+            collect_module_stats(&path, stats);
+        } else if file_type.is_file()
+            && entry.file_name().to_string_lossy().contains(".ko")
+            && let Ok(metadata) = entry.metadata()
+        {
+            stats.count += 1;
+            stats.size += metadata.len();
+        }
+    }
+}
+
+/// Writes a report file atomically.
+fn write_file(path: &Path, content: &str) -> Result<(), KernelUpdaterError> {
+    path.atomic_write(|temp_path| {
+        fs::write(temp_path, content).map_err(|io_error| KernelUpdaterError::IOError {
+            path: temp_path.to_path_buf(),
+            io_error,
+        })
+    })
+}
+
+/// Formats seconds as [`format_duration`] does.
+fn format_secs(secs: f64) -> String {
+    format_duration(Duration::from_secs_f64(secs.max(0.0)))
+}
+
+/// Formats a size in bytes as `12.3 MiB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// Locks the run report, recovering it if a panicking thread poisoned the lock.
+fn lock_run_report() -> MutexGuard<'static, Option<RunReport>> {
+    RUN_REPORT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_report
+#[cfg(test)]
+mod tests_report {
+    use super::*;
+    use crate::DkmsModuleSpec;
+    use crate::test_utils::{TempDirGuard, staged_args};
+
+    /// Out-of-tree x86_64 build staged inside the temporary directory.
+    fn create_mock_config(temp_dir: &Path) -> Config {
+        let mut args = staged_args(temp_dir, Commands::KernelCompile);
+        args.build.build_dir = Some(temp_dir.join("build"));
+        Config::new(args).expect("Failed to create standard Config")
+    }
+
+    fn sample_report(run_log: PathBuf) -> RunReport {
+        RunReport {
+            schema: REPORT_SCHEMA_VERSION,
+            started: "2025-06-15T15:06:40Z".to_string(),
+            command: None,
+            version_new: Some("6.15.4".to_string()),
+            version_old: Some("6.15.3".to_string()),
+            kernel: Some("6.15.4-TestSuffix".to_string()),
+            success: false,
+            exit_code: 6,
+            error: Some("DKMS update failed: ...\nBuild log: make.log".to_string()),
+            total_secs: 3723.0,
+            phases: vec![PhaseTiming {
+                phase: "Phase 1 of 4: Compiling Source Tree".to_string(),
+                duration_secs: 3600.5,
+            }],
+            jobs: Some(15),
+            toolchain: Some("clang (LLVM=1, sccache)".to_string()),
+            make_variables: Some("LLVM=1 CC=sccache clang".to_string()),
+            build_dir: Some(PathBuf::from("/lib/modules/linux-6.15.4")),
+            compiler_cache_stats: Some("Cache hits 42\nCache misses 7\n".to_string()),
+            image_size: Some(12 * 1024 * 1024),
+            modules: Some(ModuleStats {
+                count: 2,
+                size: 3 * 1024,
+            }),
+            dkms: vec![DkmsModuleVersion::from(&DkmsModuleReport {
+                module: DkmsModuleSpec::required("nvidia"),
+                outcome: DkmsOutcome::Installed {
+                    version: "550.78".to_string(),
+                },
+            })],
+            config_diff: Some(ConfigDiffSummary {
+                added: 3,
+                removed: 1,
+                changed: 2,
+            }),
+            run_log: Some(run_log),
+        }
+    }
+
+    #[test]
+    fn test_render_report() {
+        let report = sample_report(PathBuf::from("/var/log/kernel-updater/runs/run-1.log"));
+        let text = report.render();
+        for expected in [
+            "Command: update\n",
+            "Kernel: 6.15.4-TestSuffix\n",
+            "Result: failed (exit 6)\n",
+            "Error: DKMS update failed: ...\n",
+            "Total time: 1h02m03s\n",
+            "  Phase 1 of 4: Compiling Source Tree                1h00m00s\n",
+            "  Jobs: 15\n",
+            "  Toolchain: clang (LLVM=1, sccache)\n",
+            "  Make variables: LLVM=1 CC=sccache clang\n",
+            "  Build directory: /lib/modules/linux-6.15.4\n",
+            "\nCompiler cache statistics:\n  Cache hits 42\n  Cache misses 7\n",
+            "  Kernel image: 12.0 MiB\n",
+            "  Modules: 2 (3.0 KiB)\n",
+            "  Config diff: 3 added, 1 removed, 2 changed\n",
+            "  nvidia                   550.78           installed\n",
+        ] {
+            assert!(text.contains(expected), "missing {expected:?} in:\n{text}");
+        }
+        assert!(report.history_line().starts_with(
+            "2025-06-15T15:06:40Z  update          6.15.4-TestSuffix             failed (exit 6)    1h02m03s  Phase 1 of 4"
+        ));
+    }
+
+    #[test]
+    fn test_save_and_load_reports() {
+        let temp_dir = TempDirGuard::new("report-history");
+        let runs_dir = temp_dir.path.join(RUNS_DIR);
+        fs::create_dir_all(&runs_dir).unwrap();
+
+        let older = sample_report(runs_dir.join("run-20250615-150640.log"));
+        let same_second = sample_report(runs_dir.join("run-20250615-150640-2.log"));
+        let mut newer = sample_report(runs_dir.join("run-20250616-090000.log"));
+        newer.success = true;
+        newer.exit_code = 0;
+        newer.error = None;
+
+        // Saved out of order, listed by run time
+        let json_path = newer.save().unwrap().unwrap();
+        same_second.save().unwrap();
+        older.save().unwrap();
+        assert_eq!(json_path, runs_dir.join("run-20250616-090000.report.json"));
+        assert!(runs_dir.join("run-20250616-090000.report.txt").is_file());
+        fs::write(runs_dir.join("run-broken.report.json"), "{").unwrap();
+
+        let reports = load_run_reports(&temp_dir.path).unwrap();
+        assert_eq!(reports, vec![older, same_second, newer]);
+
+        // Nothing to save without a run log, nothing to list without runs
+        let mut detached = sample_report(PathBuf::new());
+        detached.run_log = None;
+        assert_eq!(detached.save().unwrap(), None);
+        assert!(
+            load_run_reports(&temp_dir.path.join("missing"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_module_stats() {
+        let temp_dir = TempDirGuard::new("report-modules");
+        let config = create_mock_config(&temp_dir.path);
+
+        // Built only: modules listed in modules.order
+        let build = &config.kernel_build_dir_path;
+        fs::create_dir_all(build.join("drivers/net")).unwrap();
+        fs::write(build.join("drivers/net/e1000.ko"), [0u8; 100]).unwrap();
+        fs::write(build.join("modules.order"), "drivers/net/e1000.o\n").unwrap();
+        assert_eq!(
+            module_stats(&config),
+            Some(ModuleStats {
+                count: 1,
+                size: 100
+            })
+        );
+
+        // Installed: every compressed or plain module under kernel/
+        let installed = config
+            .kernel_module_base
+            .join(&config.kernel_ident_name_new)
+            .join("kernel/drivers");
+        fs::create_dir_all(&installed).unwrap();
+        fs::write(installed.join("a.ko.zst"), [0u8; 10]).unwrap();
+        fs::write(installed.join("b.ko"), [0u8; 20]).unwrap();
+        fs::write(installed.join("modules.txt"), [0u8; 5]).unwrap();
+        let mut args = staged_args(
+            &temp_dir.path,
+            Commands::KernelInstall { from_bundle: None },
+        );
+        args.build.build_dir = Some(temp_dir.path.join("build"));
+        let install_config = Config::new(args).unwrap();
+        assert_eq!(
+            module_stats(&install_config),
+            Some(ModuleStats { count: 2, size: 30 })
+        );
+
+        // kernel-compile reports what it built, not an earlier install of the same version
+        assert_eq!(
+            module_stats(&config),
+            Some(ModuleStats {
+                count: 1,
+                size: 100
+            })
+        );
+    }
+
+    #[test]
+    fn test_artifact_image() {
+        let temp_dir = TempDirGuard::new("report-image");
+        let config = create_mock_config(&temp_dir.path);
+        let built = config.kernel_build_dir_path.join("arch/x86/boot/bzImage");
+        fs::create_dir_all(built.parent().unwrap()).unwrap();
+        fs::write(&built, "built").unwrap();
+        fs::create_dir_all(config.vmlinuz_install_path.parent().unwrap()).unwrap();
+        fs::write(&config.vmlinuz_install_path, "installed").unwrap();
+        assert_eq!(artifact_image(&config), Some(built));
+
+        let mut args = staged_args(
+            &temp_dir.path,
+            Commands::KernelInstall { from_bundle: None },
+        );
+        args.build.build_dir = Some(temp_dir.path.join("build"));
+        let install_config = Config::new(args).unwrap();
+        assert_eq!(
+            artifact_image(&install_config),
+            Some(install_config.vmlinuz_install_path.clone())
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use crate::{
+    Config,
+    error::KernelUpdaterError,
+    kconfig::{ConfigValue, KernelConfig},
+};
+use std::{fmt, str::FromStr};
+
+/// Condition a kernel option must satisfy in the final `.config`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionRequirement {
+    /// Built in or built as a module (`CONFIG_X`).
+    Enabled,
+    /// Not set (`!CONFIG_X`).
+    Disabled,
+    /// Exactly this value (`CONFIG_X=y`).
+    Equals(ConfigValue),
+}
+
+/// A required or forbidden kernel option, with the reason it is needed.
+///
+/// Parsed from `CONFIG_X` (enabled), `!CONFIG_X` (disabled) or `CONFIG_X=value` (exact value),
+/// the same notation DKMS uses for `BUILD_EXCLUSIVE_CONFIG`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionRule {
+    pub symbol: String,
+    pub requirement: OptionRequirement,
+    pub reason: String,
+}
+
+impl OptionRule {
+    /// Creates a rule requiring `symbol` to be enabled for the given reason.
+    pub fn enabled(symbol: &str, reason: &str) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            requirement: OptionRequirement::Enabled,
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Creates a rule requiring `symbol` to be built in for the given reason.
+    pub fn built_in(symbol: &str, reason: &str) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            requirement: OptionRequirement::Equals(ConfigValue::Yes),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Checks the rule against a configuration; a missing symbol counts as not set.
+    pub fn check(&self, kernel_config: &KernelConfig) -> Option<OptionViolation> {
+        let actual = kernel_config
+            .get(&self.symbol)
+            .cloned()
+            .unwrap_or(ConfigValue::NotSet);
+
+        let satisfied = match &self.requirement {
+            OptionRequirement::Enabled => actual.is_enabled(),
+            OptionRequirement::Disabled => !actual.is_enabled(),
+            OptionRequirement::Equals(expected) => actual == *expected,
+        };
+
+        (!satisfied).then(|| OptionViolation {
+            rule: self.clone(),
+            actual,
+        })
+    }
+}
+
+impl FromStr for OptionRule {
+    type Err = KernelUpdaterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let input = s.trim();
+        let (symbol, requirement) = if let Some(symbol) = input.strip_prefix('!') {
+            (symbol, OptionRequirement::Disabled)
+        } else if let Some((symbol, value)) = input.split_once('=') {
+            let value = KernelConfig::parse(&format!("{symbol}={value}"))
+                .get(symbol)
+                .cloned()
+                .unwrap_or(ConfigValue::NotSet);
+            (symbol, OptionRequirement::Equals(value))
+        } else {
+            (input, OptionRequirement::Enabled)
+        };
+
+        let valid_name = symbol.starts_with("CONFIG_")
+            && symbol.len() > "CONFIG_".len()
+            && symbol
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(KernelUpdaterError::OptionRuleParseError {
+                input: s.to_string(),
+            });
+        }
+
+        Ok(Self {
+            symbol: symbol.to_string(),
+            requirement,
+            reason: "requested with --require-option".to_string(),
+        })
+    }
+}
+
+impl fmt::Display for OptionRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.requirement {
+            OptionRequirement::Enabled => write!(f, "{} must be enabled", self.symbol),
+            OptionRequirement::Disabled => write!(f, "{} must not be set", self.symbol),
+            OptionRequirement::Equals(value) => write!(f, "{} must be {value}", self.symbol),
+        }
+    }
+}
+
+/// A rule that the final `.config` does not satisfy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionViolation {
+    pub rule: OptionRule,
+    pub actual: ConfigValue,
+}
+
+impl fmt::Display for OptionViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}), found {}",
+            self.rule, self.rule.reason, self.actual
+        )
+    }
+}
+
+/// Built-in rules for the features this tool relies on, followed by the user's `--require-option`s.
+pub fn required_option_rules(config: &Config) -> Vec<OptionRule> {
+    let mut rules = vec![
+        OptionRule::enabled(
+            "CONFIG_MODULES",
+            "needed by `make modules_install` and DKMS",
+        ),
+        OptionRule::built_in(
+            "CONFIG_BLK_DEV_INITRD",
+            "needed to boot the mkinitcpio initramfs",
+        ),
+    ];
+
+    // NVIDIA is managed by default when no module list is given
+    let manages_nvidia = config.dkms.modules.is_empty()
+        || config
+            .dkms
+            .modules
+            .iter()
+            .any(|spec| spec.name == "nvidia" || spec.is_all());
+    if manages_nvidia {
+        rules.push(OptionRule::enabled(
+            "CONFIG_DRM",
+            "needed by the NVIDIA DKMS module",
+        ));
+    }
+
+    if config.dkms.sign_modules {
+        rules.push(OptionRule::built_in(
+            "CONFIG_MODULE_SIG",
+            "needed by --sign-modules",
+        ));
+    }
+
+    rules.extend(config.build.required_options.iter().cloned());
+    rules
+}
+
+/// Returns every rule the configuration violates.
+pub fn check_required_options(
+    kernel_config: &KernelConfig,
+    rules: &[OptionRule],
+) -> Vec<OptionViolation> {
+    rules
+        .iter()
+        .filter_map(|rule| rule.check(kernel_config))
+        .collect()
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_requirements
+#[cfg(test)]
+mod tests_requirements {
+    use super::*;
+    use crate::DkmsModuleSpec;
+    use crate::test_utils::stub_config;
+
+    #[test]
+    fn test_option_rule_from_str() {
+        let rule = OptionRule::from_str("CONFIG_EFI_STUB").unwrap();
+        assert_eq!(rule.requirement, OptionRequirement::Enabled);
+
+        let rule = OptionRule::from_str("!CONFIG_TRIM_UNUSED_KSYMS").unwrap();
+        assert_eq!(rule.symbol, "CONFIG_TRIM_UNUSED_KSYMS");
+        assert_eq!(rule.requirement, OptionRequirement::Disabled);
+
+        let rule = OptionRule::from_str("CONFIG_HZ=1000").unwrap();
+        assert_eq!(
+            rule.requirement,
+            OptionRequirement::Equals(ConfigValue::Number("1000".to_string()))
+        );
+
+        let rule = OptionRule::from_str("CONFIG_LOCALVERSION=\"-ClaudioFSR\"").unwrap();
+        assert_eq!(
+            rule.requirement,
+            OptionRequirement::Equals(ConfigValue::Str("-ClaudioFSR".to_string()))
+        );
+
+        for invalid in ["", "DRM", "!", "CONFIG_", "CONFIG_A B"] {
+            assert!(
+                matches!(
+                    OptionRule::from_str(invalid),
+                    Err(KernelUpdaterError::OptionRuleParseError { .. })
+                ),
+                "Expected parse error for {invalid:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_required_options_lists_every_violation() {
+        let kernel_config = KernelConfig::parse(
+            "# CONFIG_MODULES is not set\nCONFIG_BLK_DEV_INITRD=y\nCONFIG_TRIM_UNUSED_KSYMS=y\nCONFIG_HZ=300\n",
+        );
+        let mut config = stub_config();
+        config.build.required_options = vec![
+            OptionRule::from_str("!CONFIG_TRIM_UNUSED_KSYMS").unwrap(),
+            OptionRule::from_str("CONFIG_HZ=300").unwrap(),
+        ];
+
+        let rules = required_option_rules(&config);
+        let violations = check_required_options(&kernel_config, &rules);
+        let violated: Vec<&str> = violations.iter().map(|v| v.rule.symbol.as_str()).collect();
+
+        assert_eq!(
+            violated,
+            vec!["CONFIG_MODULES", "CONFIG_DRM", "CONFIG_TRIM_UNUSED_KSYMS"]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "CONFIG_MODULES must be enabled (needed by `make modules_install` and DKMS), found n"
+        );
+    }
+
+    #[test]
+    fn test_default_rules_follow_features() {
+        let mut config = stub_config();
+        config.dkms.modules = vec![DkmsModuleSpec::required("zfs")];
+        config.dkms.sign_modules = true;
+
+        let symbols: Vec<String> = required_option_rules(&config)
+            .into_iter()
+            .map(|rule| rule.symbol)
+            .collect();
+        assert_eq!(
+            symbols,
+            vec![
+                "CONFIG_MODULES",
+                "CONFIG_BLK_DEV_INITRD",
+                "CONFIG_MODULE_SIG"
+            ]
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use crate::error::KernelUpdaterError;
+use std::{fs, path::Path};
+
+/// Kernel memory statistics, read for the `MemAvailable` estimate.
+const MEMINFO_PATH: &str = "/proc/meminfo";
+
+/// Memory budgeted for each parallel compile job (MiB).
+pub const DEFAULT_MIB_PER_JOB: u64 = 1024;
+
+/// Memory budgeted for each job when linking with LTO, which is far more demanding (MiB).
+pub const LTO_MIB_PER_JOB: u64 = 2048;
+
+/// Extracts `MemAvailable` from the content of `/proc/meminfo`, in MiB.
+pub fn parse_mem_available(meminfo: &str) -> Option<u64> {
+    meminfo.lines().find_map(|line| {
+        let value = line.strip_prefix("MemAvailable:")?;
+        let kib: u64 = value.split_whitespace().next()?.parse().ok()?;
+        Some(kib / 1024)
+    })
+}
+
+/// Reads the memory currently available for new processes, in MiB.
+pub fn available_memory_mib() -> Result<u64, KernelUpdaterError> {
+    let path = Path::new(MEMINFO_PATH);
+    let meminfo = fs::read_to_string(path).map_err(|io_error| KernelUpdaterError::IOError {
+        path: path.to_path_buf(),
+        io_error,
+    })?;
+
+    parse_mem_available(&meminfo).ok_or_else(|| KernelUpdaterError::IOError {
+        path: path.to_path_buf(),
+        io_error: std::io::Error::new(std::io::ErrorKind::InvalidData, "MemAvailable not found"),
+    })
+}
+
+/// Caps a job count so that every job gets `mib_per_job` of the available memory,
+/// never going below one job.
+pub fn jobs_for_memory(jobs: usize, available_mib: u64, mib_per_job: u64) -> usize {
+    let affordable = (available_mib / mib_per_job.max(1)) as usize;
+    jobs.min(affordable).max(1)
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_resources
+#[cfg(test)]
+mod tests_resources {
+    use super::*;
+
+    #[test]
+    fn test_parse_mem_available() {
+        let meminfo = "MemTotal:       16318480 kB\n\
+                       MemFree:         1204480 kB\n\
+                       MemAvailable:    9437184 kB\n\
+                       Buffers:          482112 kB\n";
+        assert_eq!(parse_mem_available(meminfo), Some(9216));
+        assert_eq!(parse_mem_available("MemTotal: 16318480 kB\n"), None);
+    }
+
+    #[test]
+    fn test_jobs_for_memory() {
+        // 16 cores but only 9 GiB available with LTO: 4 jobs
+        assert_eq!(jobs_for_memory(16, 9216, LTO_MIB_PER_JOB), 4);
+        // Plenty of memory: the core count wins
+        assert_eq!(jobs_for_memory(8, 65536, DEFAULT_MIB_PER_JOB), 8);
+        // Nearly no memory still runs one job
+        assert_eq!(jobs_for_memory(8, 300, DEFAULT_MIB_PER_JOB), 1);
+    }
+}
@@ -0,0 +1,390 @@
+use crate::{
+    Config,
+    error::KernelUpdaterError,
+    info,
+    kconfig::{ConfigValue, KernelConfig},
+    utils::{run_command, run_command_output},
+    warn,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Hash algorithm used when the kernel `.config` does not define `CONFIG_MODULE_SIG_HASH`.
+const DEFAULT_SIG_HASH: &str = "sha512";
+
+/// Compression formats a kernel module (`.ko`) may be installed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuleCompression {
+    None,
+    Zstd,
+    Xz,
+    Gzip,
+}
+
+impl ModuleCompression {
+    /// Detects the compression of a module file from its name.
+    /// Returns `None` if the path is not a kernel module at all.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        if name.ends_with(".ko") {
+            Some(Self::None)
+        } else if name.ends_with(".ko.zst") {
+            Some(Self::Zstd)
+        } else if name.ends_with(".ko.xz") {
+            Some(Self::Xz)
+        } else if name.ends_with(".ko.gz") {
+            Some(Self::Gzip)
+        } else {
+            None
+        }
+    }
+
+    /// External tool used to (de)compress the module, if any.
+    fn tool(self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Zstd => Some("zstd"),
+            Self::Xz => Some("xz"),
+            Self::Gzip => Some("gzip"),
+        }
+    }
+
+    /// Arguments of [`tool`](Self::tool) unpacking a module in place.
+    fn decompress_args(self) -> &'static [&'static str] {
+        &["-d", "-f", "-q"]
+    }
+
+    /// Arguments of [`tool`](Self::tool) packing a raw module in place.
+    /// xz needs the CRC32 check and 1 MiB dictionary of `scripts/Makefile.modinst`,
+    /// the kernel's in-kernel decompressor rejects the xz defaults.
+    fn compress_args(self) -> &'static [&'static str] {
+        match self {
+            Self::Xz => &["--check=crc32", "--lzma2=dict=1MiB", "-f", "-q"],
+            _ => &["-f", "-q"],
+        }
+    }
+}
+
+/// Key pair handed to `scripts/sign-file`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningKey {
+    pub private_key: PathBuf,
+    pub certificate: PathBuf,
+}
+
+/// Signs DKMS-built modules so they load on kernels built with `CONFIG_MODULE_SIG_FORCE`.
+pub struct ModuleSigner<'a> {
+    config: &'a Config,
+}
+
+impl<'a> ModuleSigner<'a> {
+    /// Creates a new `ModuleSigner` instance.
+    pub fn new(config: &'a Config) -> Self {
+        Self { config }
+    }
+
+    /// Locates the signing key: a configured MOK pair, or the key generated by the kernel build.
+    pub fn locate_key(&self) -> Result<SigningKey, KernelUpdaterError> {
+        let key = match (&self.config.dkms.mok_key, &self.config.dkms.mok_cert) {
+            (Some(private_key), Some(certificate)) => SigningKey {
+                private_key: private_key.clone(),
+                certificate: certificate.clone(),
+            },
+            _ => {
+                let certs_dir = self.config.kernel_build_dir_path.join("certs");
+                SigningKey {
+                    private_key: certs_dir.join("signing_key.pem"),
+                    certificate: certs_dir.join("signing_key.x509"),
+                }
+            }
+        };
+
+        for path in [&key.private_key, &key.certificate] {
+            if !path.exists() {
+                return Err(KernelUpdaterError::ModuleSigningKeyNotFound { path: path.clone() });
+            }
+        }
+
+        Ok(key)
+    }
+
+    /// Reads `CONFIG_MODULE_SIG_HASH` from the configured build tree, falling back to sha512.
+    pub fn hash_algorithm(&self) -> String {
+        let dot_config = self.config.kernel_build_dir_path.join(".config");
+        KernelConfig::from_file(&dot_config)
+            .ok()
+            .and_then(
+                |kernel_config| match kernel_config.get("CONFIG_MODULE_SIG_HASH") {
+                    Some(ConfigValue::Str(hash)) if !hash.is_empty() => Some(hash.clone()),
+                    _ => None,
+                },
+            )
+            .unwrap_or_else(|| DEFAULT_SIG_HASH.to_string())
+    }
+
+    /// Directory where DKMS installs modules for the new kernel.
+    pub fn dkms_modules_dir(&self) -> PathBuf {
+        self.config
+            .kernel_module_base
+            .join(&self.config.kernel_ident_name_new)
+            .join("updates")
+            .join("dkms")
+    }
+
+    /// Recursively collects every (possibly compressed) `.ko` file under `dir`.
+    pub fn collect_modules(dir: &Path) -> Result<Vec<PathBuf>, KernelUpdaterError> {
+        let mut modules = Vec::new();
+        if !dir.is_dir() {
+            return Ok(modules);
+        }
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                modules.extend(Self::collect_modules(&path)?);
+            } else if ModuleCompression::from_path(&path).is_some() {
+                modules.push(path);
+            }
+        }
+
+        modules.sort();
+        Ok(modules)
+    }
+
+    /// Signs every DKMS module installed for the new kernel and verifies the result.
+    pub fn sign_dkms_modules(&self) -> Result<(), KernelUpdaterError> {
+        let key = self.locate_key()?;
+        let hash = self.hash_algorithm();
+        let sign_file = self.config.kernel_build_dir_path.join("scripts/sign-file");
+        let modules_dir = self.dkms_modules_dir();
+        let modules = Self::collect_modules(&modules_dir)?;
+
+        if modules.is_empty() {
+            warn!(
+                "No DKMS modules found under {}. Nothing to sign.",
+                modules_dir.display()
+            );
+            return Ok(());
+        }
+
+        info!(
+            "Signing {} DKMS module(s) with {} ({hash})...",
+            modules.len(),
+            key.private_key.display()
+        );
+
+        for module in &modules {
+            self.sign_module(&sign_file, &hash, &key, module)?;
+        }
+
+        for module in &modules {
+            Self::verify_signature(module)?;
+        }
+
+        info!(
+            "All DKMS modules for {} are signed.",
+            self.config.kernel_ident_name_new
+        );
+        Ok(())
+    }
+
+    /// Signs a single module, transparently decompressing and recompressing it when needed.
+    fn sign_module(
+        &self,
+        sign_file: &Path,
+        hash: &str,
+        key: &SigningKey,
+        module: &Path,
+    ) -> Result<(), KernelUpdaterError> {
+        let compression = ModuleCompression::from_path(module).unwrap_or(ModuleCompression::None);
+        let module_str = module.to_string_lossy();
+
+        // sign-file only understands raw ELF objects, so compressed modules are unpacked first
+        let raw_module = match compression.tool() {
+            Some(tool) => {
+                let mut args = compression.decompress_args().to_vec();
+                args.push(&module_str);
+                run_command(tool, &args)?;
+                module.with_extension("")
+            }
+            None => module.to_path_buf(),
+        };
+
+        run_command(
+            &sign_file.to_string_lossy(),
+            &[
+                hash,
+                &key.private_key.to_string_lossy(),
+                &key.certificate.to_string_lossy(),
+                &raw_module.to_string_lossy(),
+            ],
+        )?;
+
+        if let Some(tool) = compression.tool() {
+            let raw_str = raw_module.to_string_lossy();
+            let mut args = compression.compress_args().to_vec();
+            args.push(&raw_str);
+            run_command(tool, &args)?;
+            // zstd keeps its input by default, unlike xz and gzip
+            if raw_module.exists() {
+                fs::remove_file(&raw_module)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Confirms via `modinfo` that a module carries a signature.
+    pub fn verify_signature(module: &Path) -> Result<(), KernelUpdaterError> {
+        let signer = run_command_output("modinfo", &["-F", "signer", &module.to_string_lossy()])?;
+        if signer.trim().is_empty() {
+            return Err(KernelUpdaterError::ModuleSignatureMissing {
+                module: module.to_path_buf(),
+            });
+        }
+        info!(
+            "Verified signature of {} (signer: {})",
+            module.display(),
+            signer.trim()
+        );
+        Ok(())
+    }
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_signing
+#[cfg(test)]
+mod tests_signing {
+    use super::*;
+    use crate::test_utils::{TempDirGuard, stub_config};
+
+    fn create_mock_config(temp_dir: &Path) -> Config {
+        let mut config = stub_config();
+        config.kernel_module_base = temp_dir.join("lib/modules");
+        config.kernel_src_dir_path = temp_dir.join("src/linux-6.15.4");
+        config.kernel_build_dir_path = config.kernel_src_dir_path.clone();
+        config
+    }
+
+    #[test]
+    fn test_module_compression_from_path() {
+        let detect = |name: &str| ModuleCompression::from_path(Path::new(name));
+        assert_eq!(detect("nvidia.ko"), Some(ModuleCompression::None));
+        assert_eq!(detect("nvidia.ko.zst"), Some(ModuleCompression::Zstd));
+        assert_eq!(detect("nvidia.ko.xz"), Some(ModuleCompression::Xz));
+        assert_eq!(detect("nvidia.ko.gz"), Some(ModuleCompression::Gzip));
+        assert_eq!(detect("nvidia.o"), None);
+        assert_eq!(detect("Module.symvers"), None);
+    }
+
+    #[test]
+    fn test_module_compression_commands() {
+        let command = |compression: ModuleCompression, args: &[&str]| {
+            compression
+                .tool()
+                .map(|tool| [&[tool], args].concat().join(" "))
+        };
+        let decompress =
+            |compression: ModuleCompression| command(compression, compression.decompress_args());
+        let compress =
+            |compression: ModuleCompression| command(compression, compression.compress_args());
+
+        assert_eq!(decompress(ModuleCompression::None), None);
+        assert_eq!(compress(ModuleCompression::None), None);
+        assert_eq!(
+            decompress(ModuleCompression::Zstd).unwrap(),
+            "zstd -d -f -q"
+        );
+        assert_eq!(compress(ModuleCompression::Zstd).unwrap(), "zstd -f -q");
+        assert_eq!(decompress(ModuleCompression::Xz).unwrap(), "xz -d -f -q");
+        assert_eq!(
+            compress(ModuleCompression::Xz).unwrap(),
+            "xz --check=crc32 --lzma2=dict=1MiB -f -q"
+        );
+        assert_eq!(
+            decompress(ModuleCompression::Gzip).unwrap(),
+            "gzip -d -f -q"
+        );
+        assert_eq!(compress(ModuleCompression::Gzip).unwrap(), "gzip -f -q");
+    }
+
+    #[test]
+    fn test_locate_key_missing_build_key() {
+        let temp_dir = TempDirGuard::new("signing-missing-key");
+        let config = create_mock_config(&temp_dir.path);
+        let signer = ModuleSigner::new(&config);
+
+        let err = signer.locate_key().unwrap_err();
+        assert!(
+            matches!(&err, KernelUpdaterError::ModuleSigningKeyNotFound { path } if path.ends_with("certs/signing_key.pem")),
+            "Expected ModuleSigningKeyNotFound, received: {:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_locate_key_prefers_mok() {
+        let temp_dir = TempDirGuard::new("signing-mok");
+        let mut config = create_mock_config(&temp_dir.path);
+        let mok_key = temp_dir.path.join("MOK.priv");
+        let mok_cert = temp_dir.path.join("MOK.der");
+        fs::write(&mok_key, "key").unwrap();
+        fs::write(&mok_cert, "cert").unwrap();
+        config.dkms.mok_key = Some(mok_key.clone());
+        config.dkms.mok_cert = Some(mok_cert.clone());
+
+        let key = ModuleSigner::new(&config).locate_key().unwrap();
+        assert_eq!(key.private_key, mok_key);
+        assert_eq!(key.certificate, mok_cert);
+    }
+
+    #[test]
+    fn test_hash_algorithm_from_config() {
+        let temp_dir = TempDirGuard::new("signing-hash");
+        let config = create_mock_config(&temp_dir.path);
+        let signer = ModuleSigner::new(&config);
+
+        // Falls back to the default when no .config exists
+        assert_eq!(signer.hash_algorithm(), DEFAULT_SIG_HASH);
+
+        fs::create_dir_all(&config.kernel_src_dir_path).unwrap();
+        fs::write(
+            config.kernel_src_dir_path.join(".config"),
+            "CONFIG_MODULE_SIG=y\nCONFIG_MODULE_SIG_HASH=\"sha256\"\n",
+        )
+        .unwrap();
+        assert_eq!(signer.hash_algorithm(), "sha256");
+    }
+
+    #[test]
+    fn test_collect_modules_recursive() {
+        let temp_dir = TempDirGuard::new("signing-collect");
+        let config = create_mock_config(&temp_dir.path);
+        let signer = ModuleSigner::new(&config);
+
+        let dkms_dir = signer.dkms_modules_dir();
+        fs::create_dir_all(dkms_dir.join("nested")).unwrap();
+        fs::write(dkms_dir.join("nvidia.ko.zst"), "").unwrap();
+        fs::write(dkms_dir.join("nested/v4l2loopback.ko"), "").unwrap();
+        fs::write(dkms_dir.join("README"), "").unwrap();
+
+        let modules = ModuleSigner::collect_modules(&dkms_dir).unwrap();
+        assert_eq!(modules.len(), 2);
+        assert!(modules.iter().any(|m| m.ends_with("nvidia.ko.zst")));
+        assert!(
+            modules
+                .iter()
+                .any(|m| m.ends_with("nested/v4l2loopback.ko"))
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Fixtures shared by the test modules.
+
+use crate::{Arch, Arguments, BuildArgs, Commands, Config, Downloader, Version};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Guard to manage creation and auto-deletion of temporary testing directories.
+pub struct TempDirGuard {
+    pub path: PathBuf,
+}
+
+impl TempDirGuard {
+    pub fn new(prefix: &str) -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("kernel-updater-test-{prefix}-{nanos}"));
+        fs::create_dir_all(&path).expect("Failed to create temporary testing directory");
+        Self { path }
+    }
+}
+
+impl Drop for TempDirGuard {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Guard to restore the original working directory of the process after directory changes.
+pub struct CurrentDirGuard {
+    original: PathBuf,
+}
+
+impl CurrentDirGuard {
+    pub fn new() -> Self {
+        let original = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        Self { original }
+    }
+}
+
+impl Drop for CurrentDirGuard {
+    fn drop(&mut self) {
+        let _ = std::env::set_current_dir(&self.original);
+    }
+}
+
+/// Arguments of a full update from 6.15.3 to 6.15.4 with the suffix `TestSuffix`.
+pub fn stub_args() -> Arguments {
+    Arguments {
+        downloader: Downloader::Curl,
+        suffix: "TestSuffix".to_string(),
+        new: Some(Version::new(6, 15, 4)),
+        old: Some(Version::new(6, 15, 3)),
+        command: None,
+        build: Default::default(),
+        dkms: Default::default(),
+        output: Default::default(),
+    }
+}
+
+/// Configuration of [`stub_args`], using the real system paths.
+pub fn stub_config() -> Config {
+    Config::new(stub_args()).expect("Failed to initialize test config")
+}
+
+/// Arguments of `command` for an x86_64 kernel 6.15.4 staged inside `root` (no `--old`).
+pub fn staged_args(root: &Path, command: Commands) -> Arguments {
+    Arguments {
+        old: None,
+        command: Some(command),
+        build: BuildArgs {
+            target_root: Some(root.to_path_buf()),
+            arch: Some(Arch::X86_64),
+            ..Default::default()
+        },
+        ..stub_args()
+    }
+}
+
+/// Configuration of [`staged_args`].
+pub fn staged_config(root: &Path, command: Commands) -> Config {
+    Config::new(staged_args(root, command)).expect("Failed to create standard Config")
+}
@@ -0,0 +1,248 @@
+use crate::{
+    args::BuildArgs,
+    error::KernelUpdaterError,
+    kconfig::{ConfigValue, KernelConfig},
+    utils::{run_command, run_command_output},
+};
+
+/// Compiler toolchain used for the kernel build, derived from the build options.
+///
+/// Produces the variables appended to every `make` invocation, the environment given to
+/// DKMS so out-of-tree modules are built with the same compiler, and the `.config`
+/// options a toolchain feature (ThinLTO) depends on.
+pub struct Toolchain<'a> {
+    build: &'a BuildArgs,
+}
+
+impl<'a> Toolchain<'a> {
+    /// Creates a new `Toolchain` instance.
+    pub fn new(build: &'a BuildArgs) -> Self {
+        Self { build }
+    }
+
+    /// The C compiler the kernel is built with, without any cache wrapper.
+    pub fn compiler(&self) -> &str {
+        match &self.build.cc {
+            Some(cc) => cc,
+            None if self.build.llvm => "clang",
+            None => "gcc",
+        }
+    }
+
+    /// Variables passed on the `make` command line (`LLVM=1`, `CC=ccache clang`, `KCFLAGS=...`).
+    pub fn make_variables(&self) -> Vec<String> {
+        let mut variables = Vec::new();
+
+        if self.build.llvm {
+            variables.push("LLVM=1".to_string());
+        }
+        if let Some(ias) = self.build.llvm_ias {
+            variables.push(format!("LLVM_IAS={ias}"));
+        }
+
+        for (name, value) in self.compiler_variables() {
+            variables.push(format!("{name}={value}"));
+        }
+        if let Some(kcflags) = &self.build.kcflags {
+            variables.push(format!("KCFLAGS={kcflags}"));
+        }
+
+        variables
+    }
+
+    /// `CC`/`HOSTCC` overrides of the kernel's default compilers.
+    fn compiler_variables(&self) -> Vec<(&'static str, String)> {
+        let mut variables = Vec::new();
+        // The cache wrapper needs an explicit CC, even for the default compiler
+        match &self.build.compiler_cache {
+            Some(cache) => {
+                variables.push(("CC", format!("{} {}", cache.program(), self.compiler())))
+            }
+            None => {
+                if let Some(cc) = &self.build.cc {
+                    variables.push(("CC", cc.clone()));
+                }
+            }
+        }
+        if let Some(hostcc) = &self.build.hostcc {
+            variables.push(("HOSTCC", hostcc.clone()));
+        }
+        variables
+    }
+
+    /// Environment for DKMS builds, so modules match the kernel's toolchain.
+    ///
+    /// `LLVM`, `LLVM_IAS` and `KCFLAGS` are honoured from the environment. Kbuild assigns
+    /// `CC`/`HOSTCC` in its Makefile, overriding the environment, so they are passed in
+    /// `MAKEFLAGS` instead, which make applies as command line variables to every sub-make.
+    pub fn dkms_environment(&self) -> Vec<(String, String)> {
+        let mut environment = Vec::new();
+        if self.build.llvm {
+            environment.push(("LLVM".to_string(), "1".to_string()));
+        }
+        if let Some(ias) = self.build.llvm_ias {
+            environment.push(("LLVM_IAS".to_string(), ias.to_string()));
+        }
+        if let Some(kcflags) = &self.build.kcflags {
+            environment.push(("KCFLAGS".to_string(), kcflags.clone()));
+        }
+
+        let overrides: Vec<String> = self
+            .compiler_variables()
+            .into_iter()
+            // Spaces inside a value (`ccache clang`) are escaped, as make itself does
+            .map(|(name, value)| format!("{name}={}", value.replace(' ', "\\ ")))
+            .collect();
+        if !overrides.is_empty() {
+            environment.push((
+                "MAKEFLAGS".to_string(),
+                format!("-- {}", overrides.join(" ")),
+            ));
+        }
+        environment
+    }
+
+    /// Options the selected toolchain features need in `.config` (empty when none apply).
+    pub fn config_fragment(&self) -> KernelConfig {
+        let mut fragment = KernelConfig::default();
+        if self.build.thin_lto {
+            fragment.set("CONFIG_LTO_NONE", ConfigValue::NotSet);
+            fragment.set("CONFIG_LTO_CLANG_THIN", ConfigValue::Yes);
+        }
+        fragment
+    }
+
+    /// Resets the compiler cache statistics so the report covers this build only.
+    pub fn reset_cache_stats(&self) -> Result<(), KernelUpdaterError> {
+        match &self.build.compiler_cache {
+            Some(cache) => run_command(cache.program(), &["--zero-stats"]),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the compiler cache statistics (hits, misses, ...) if a cache is in use.
+    pub fn cache_stats(&self) -> Result<Option<String>, KernelUpdaterError> {
+        match &self.build.compiler_cache {
+            Some(cache) => Ok(Some(run_command_output(
+                cache.program(),
+                &["--show-stats"],
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    /// One-line description of the toolchain, e.g. `clang (LLVM=1, ThinLTO, ccache)`.
+    pub fn describe(&self) -> String {
+        let mut features = Vec::new();
+        if self.build.llvm {
+            features.push("LLVM=1".to_string());
+        }
+        if let Some(ias) = self.build.llvm_ias {
+            features.push(format!("LLVM_IAS={ias}"));
+        }
+        if self.build.thin_lto {
+            features.push("ThinLTO".to_string());
+        }
+        if let Some(cache) = &self.build.compiler_cache {
+            features.push(cache.program().to_string());
+        }
+        if let Some(kcflags) = &self.build.kcflags {
+            features.push(format!("KCFLAGS=\"{kcflags}\""));
+        }
+
+        if features.is_empty() {
+            self.compiler().to_string()
+        } else {
+            format!("{} ({})", self.compiler(), features.join(", "))
+        }
+    }
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_toolchain
+#[cfg(test)]
+mod tests_toolchain {
+    use super::*;
+    use crate::CompilerCache;
+
+    #[test]
+    fn test_default_toolchain_adds_nothing() {
+        let build = BuildArgs::default();
+        let toolchain = Toolchain::new(&build);
+
+        assert!(toolchain.make_variables().is_empty());
+        assert!(toolchain.dkms_environment().is_empty());
+        assert!(toolchain.config_fragment().symbols().next().is_none());
+        assert_eq!(toolchain.describe(), "gcc");
+    }
+
+    #[test]
+    fn test_llvm_toolchain_with_cache() {
+        let build = BuildArgs {
+            llvm: true,
+            llvm_ias: Some(0),
+            thin_lto: true,
+            compiler_cache: Some(CompilerCache::Ccache),
+            kcflags: Some("-march=native".to_string()),
+            ..Default::default()
+        };
+        let toolchain = Toolchain::new(&build);
+
+        assert_eq!(
+            toolchain.make_variables(),
+            vec![
+                "LLVM=1",
+                "LLVM_IAS=0",
+                "CC=ccache clang",
+                "KCFLAGS=-march=native"
+            ]
+        );
+        assert_eq!(
+            toolchain.dkms_environment(),
+            vec![
+                ("LLVM".to_string(), "1".to_string()),
+                ("LLVM_IAS".to_string(), "0".to_string()),
+                ("KCFLAGS".to_string(), "-march=native".to_string()),
+                ("MAKEFLAGS".to_string(), r"-- CC=ccache\ clang".to_string()),
+            ]
+        );
+        assert_eq!(
+            toolchain.config_fragment().to_string(),
+            "# CONFIG_LTO_NONE is not set\nCONFIG_LTO_CLANG_THIN=y\n"
+        );
+        assert_eq!(
+            toolchain.describe(),
+            "clang (LLVM=1, LLVM_IAS=0, ThinLTO, ccache, KCFLAGS=\"-march=native\")"
+        );
+    }
+
+    #[test]
+    fn test_custom_compilers() {
+        let build = BuildArgs {
+            cc: Some("gcc-14".to_string()),
+            hostcc: Some("gcc-14".to_string()),
+            ..Default::default()
+        };
+        let toolchain = Toolchain::new(&build);
+        assert_eq!(
+            toolchain.make_variables(),
+            vec!["CC=gcc-14", "HOSTCC=gcc-14"]
+        );
+        // DKMS modules get the same compilers as the kernel
+        assert_eq!(
+            toolchain.dkms_environment(),
+            vec![(
+                "MAKEFLAGS".to_string(),
+                "-- CC=gcc-14 HOSTCC=gcc-14".to_string()
+            )]
+        );
+    }
+}
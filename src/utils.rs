@@ -1,87 +1,257 @@
-use crate::error::KernelUpdaterError;
+use crate::{
+    Config, LogLevel, OutputFormat, debug,
+    error::KernelUpdaterError,
+    events::{Event, duration_secs, emit_event},
+    info,
+    logging::{format_duration, log_message, log_to_file, output_format},
+};
 use std::{
-    process::{Command, Stdio},
+    collections::VecDeque,
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::fs as unix_fs,
+    path::Path,
+    process::{Command, ExitStatus, Stdio},
     thread,
+    time::Instant,
 };
 
-/// Runs a command, showing stderr on real-time, capturing stdout on success.
+/// Number of trailing stderr lines kept in `CommandExecutionError` to derive hints.
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Runs a command, streaming its output to the console and the run log.
 pub fn run_command(command: &str, args: &[&str]) -> Result<(), KernelUpdaterError> {
-    let args_joined = args.join(" ");
-    println!("Executing: {command} {args_joined}");
+    run_command_with_env(command, args, &[])
+}
 
-    let mut child = Command::new(command)
-        .args(args)
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .spawn()?;
+/// Runs a command line given as `[command, args...]` (see [`Config::target_command`]).
+pub fn run_command_line(
+    command_line: &[String],
+    envs: &[(String, String)],
+) -> Result<(), KernelUpdaterError> {
+    let (command, args) = split_command_line(command_line);
+    run_command_with_env(command, &args, envs)
+}
 
-    let status = child.wait()?;
+/// Runs a command line given as `[command, args...]`, capturing its stdout.
+pub fn run_command_line_output(command_line: &[String]) -> Result<String, KernelUpdaterError> {
+    let (command, args) = split_command_line(command_line);
+    run_command_output(command, &args)
+}
 
-    if status.success() {
-        Ok(())
-    } else {
-        Err(KernelUpdaterError::CommandExecutionError {
-            command: command.to_string(),
-            args: args_joined,
-            status,
-        })
+/// Splits `[command, args...]` into the program and its borrowed arguments.
+fn split_command_line(command_line: &[String]) -> (&str, Vec<&str>) {
+    match command_line.split_first() {
+        Some((command, args)) => (command, args.iter().map(String::as_str).collect()),
+        None => ("", Vec::new()),
     }
 }
 
-/// Executes a command in a specific directory (optional).
-#[allow(dead_code)]
-pub fn run_command_in_dir(
+/// Runs a command like [`run_command`], with additional environment variables.
+pub fn run_command_with_env(
     command: &str,
     args: &[&str],
-    dir: Option<&std::path::Path>,
+    envs: &[(String, String)],
 ) -> Result<(), KernelUpdaterError> {
-    let args_joined = args.join(" ");
-    println!("Executing: {command} {args_joined}");
+    let env_prefix: String = envs
+        .iter()
+        .map(|(key, value)| format!("{key}={value} "))
+        .collect();
+    info!("Executing: {env_prefix}{command} {}", args.join(" "));
 
     let mut cmd = Command::new(command);
     cmd.args(args)
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit());
+        .envs(envs.iter().map(|(key, value)| (key, value)));
+    run_logged(cmd, command, args, false).map(|_| ())
+}
+
+/// Executes a command in a specific directory (optional).
+pub fn run_command_in_dir(
+    command: &str,
+    args: &[&str],
+    dir: Option<&Path>,
+) -> Result<(), KernelUpdaterError> {
+    info!("Executing: {command} {}", args.join(" "));
 
+    let mut cmd = Command::new(command);
+    cmd.args(args);
     if let Some(path) = dir {
         cmd.current_dir(path);
     }
+    run_logged(cmd, command, args, false).map(|_| ())
+}
+
+/// Runs a command attached to the terminal (e.g. `make menuconfig`); only the command
+/// and its exit status are recorded in the run log.
+pub fn run_command_interactive(command: &str, args: &[&str]) -> Result<(), KernelUpdaterError> {
+    info!("Executing (interactive): {command} {}", args.join(" "));
+
+    let started = Instant::now();
+    let status = Command::new(command)
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .status()?;
+    check_status(command, args, status, started, Vec::new())
+}
+
+/// Executes system utilities demanding stdout capture and parsing.
+pub fn run_command_output(command: &str, args: &[&str]) -> Result<String, KernelUpdaterError> {
+    debug!("Executing (captured): {command} {}", args.join(" "));
+
+    let mut cmd = Command::new(command);
+    cmd.args(args);
+    let stdout = run_logged(cmd, command, args, true)?;
+    String::from_utf8(stdout).map_err(|source| KernelUpdaterError::Utf8OutputError {
+        command: command.to_string(),
+        source,
+    })
+}
+
+/// Spawns `cmd` with piped output: stderr (and stdout unless captured) is tee'd line by line
+/// to the console and the run log while the command runs. Captured stdout is returned and
+/// recorded in the run log only. Fails if the command exits unsuccessfully.
+fn run_logged(
+    mut cmd: Command,
+    command: &str,
+    args: &[&str],
+    capture_stdout: bool,
+) -> Result<Vec<u8>, KernelUpdaterError> {
+    let started = Instant::now();
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+
+    let stderr = child.stderr.take();
+    let stderr_thread = thread::spawn(move || match stderr {
+        Some(stderr) => tee_lines(stderr, LogLevel::Stderr),
+        None => Vec::new(),
+    });
+
+    let mut captured = Vec::new();
+    if let Some(mut stdout) = child.stdout.take() {
+        if capture_stdout {
+            stdout.read_to_end(&mut captured)?;
+            log_to_file(LogLevel::Stdout, &String::from_utf8_lossy(&captured));
+        } else {
+            tee_lines(stdout, LogLevel::Stdout);
+        }
+    }
 
-    let mut child = cmd.spawn()?;
     let status = child.wait()?;
+    let stderr_tail = stderr_thread.join().unwrap_or_default();
+    check_status(command, args, status, started, stderr_tail)?;
+    Ok(captured)
+}
+
+/// Forwards every line read from a child's pipe to the console and the run log.
+/// Returns the last `STDERR_TAIL_LINES` lines.
+fn tee_lines(pipe: impl Read, level: LogLevel) -> Vec<String> {
+    let mut reader = BufReader::new(pipe);
+    let mut line = Vec::new();
+    let mut tail = VecDeque::with_capacity(STDERR_TAIL_LINES);
+    while let Ok(read) = reader.read_until(b'\n', &mut line) {
+        if read == 0 {
+            break;
+        }
+        let text = String::from_utf8_lossy(&line);
+        let text = text.trim_end_matches(['\n', '\r']);
+        log_message(level, format_args!("{text}"));
+        if tail.len() == STDERR_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(text.to_string());
+        line.clear();
+    }
+    tail.into()
+}
+
+/// Records the exit status and duration of a command, failing on a non-zero exit.
+fn check_status(
+    command: &str,
+    args: &[&str],
+    status: ExitStatus,
+    started: Instant,
+    stderr_tail: Vec<String>,
+) -> Result<(), KernelUpdaterError> {
+    let elapsed = started.elapsed();
+    debug!(
+        "Finished: {command} ({status}) in {}",
+        format_duration(elapsed)
+    );
+    emit_event(Event::Command {
+        command: command.to_string(),
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+        exit_code: status.code(),
+        success: status.success(),
+        duration_secs: duration_secs(elapsed),
+    });
 
     if status.success() {
         Ok(())
     } else {
         Err(KernelUpdaterError::CommandExecutionError {
             command: command.to_string(),
-            args: args_joined,
+            args: args.join(" "),
             status,
+            stderr_tail,
         })
     }
 }
 
-/// Executes system utilities demanding stdout capture and parsing.
-pub fn run_command_output(command: &str, args: &[&str]) -> Result<String, KernelUpdaterError> {
-    let args_joined = args.join(" ");
+/// Asks a yes/no question on the terminal; anything but `y`/`yes` counts as no.
+/// With `--output json` the prompt goes to stderr, keeping stdout for the events.
+pub fn confirm(question: &str) -> Result<bool, KernelUpdaterError> {
+    if output_format() == OutputFormat::Json {
+        eprint!("{question} [y/N] ");
+        io::stderr().flush()?;
+    } else {
+        print!("{question} [y/N] ");
+        io::stdout().flush()?;
+    }
 
-    let output = Command::new(command)
-        .args(args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::inherit())
-        .output()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    log_to_file(
+        LogLevel::Info,
+        &format!("{question} [y/N] {}", answer.trim()),
+    );
+    Ok(matches!(
+        answer.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes"
+    ))
+}
 
-    if output.status.success() {
-        String::from_utf8(output.stdout).map_err(|source| KernelUpdaterError::Utf8OutputError {
-            command: command.to_string(),
-            source,
-        })
+/// Copies a file, symlink or directory tree, preserving symlinks and permissions.
+/// A missing source copies nothing. Returns the number of files and links copied.
+pub(crate) fn copy_tree(source: &Path, target: &Path) -> Result<usize, KernelUpdaterError> {
+    let Ok(metadata) = fs::symlink_metadata(source) else {
+        return Ok(0);
+    };
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        if fs::symlink_metadata(target).is_ok() {
+            fs::remove_file(target)?;
+        }
+        unix_fs::symlink(fs::read_link(source)?, target)?;
+        Ok(1)
+    } else if file_type.is_dir() {
+        fs::create_dir_all(target)?;
+        let mut copied = 0;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copied += copy_tree(&entry.path(), &target.join(entry.file_name()))?;
+        }
+        Ok(copied)
     } else {
-        Err(KernelUpdaterError::CommandExecutionError {
-            command: command.to_string(),
-            args: args_joined,
-            status: output.status,
-        })
+        fs::copy(source, target).map_err(|io_error| KernelUpdaterError::IOError {
+            path: source.to_path_buf(),
+            io_error,
+        })?;
+        Ok(1)
     }
 }
 
@@ -96,8 +266,41 @@ pub fn get_cores(spare: usize) -> Result<usize, KernelUpdaterError> {
     Ok(computed_cores)
 }
 
-/// Re-generates system boot menus targeting GRUB bootloader instances.
-pub fn update_grub() -> Result<(), KernelUpdaterError> {
-    println!("Updating GRUB entries...");
-    run_command("update-grub", &[])
+/// Re-generates system boot menus targeting GRUB bootloader instances (inside the target root).
+pub fn update_grub(config: &Config) -> Result<(), KernelUpdaterError> {
+    info!("Updating GRUB entries...");
+    run_command_line(&config.target_command("update-grub", &[]), &[])
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+// cargo test -- --help
+// cargo test -- --nocapture
+// cargo test -- --show-output
+
+/// Run tests with:
+/// cargo test -- --show-output tests_utils
+#[cfg(test)]
+mod tests_utils {
+    use super::*;
+
+    #[test]
+    fn test_run_command_output_captures_stdout() {
+        let output = run_command_output("sh", &["-c", "echo captured; echo streamed >&2"]).unwrap();
+        assert_eq!(output, "captured\n");
+    }
+
+    #[test]
+    fn test_run_command_reports_exit_status() {
+        assert!(run_command("sh", &["-c", "echo line one; echo line two"]).is_ok());
+
+        let err = run_command("sh", &["-c", "exit 3"]).unwrap_err();
+        assert!(matches!(
+            &err,
+            KernelUpdaterError::CommandExecutionError { command, status, .. }
+                if command == "sh" && status.code() == Some(3)
+        ));
+    }
 }